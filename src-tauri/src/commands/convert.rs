@@ -0,0 +1,37 @@
+//! Kommandoer for konvertering mellom bildeformater
+
+use crate::error::AppError;
+use crate::services::converter::{self, TargetFormat};
+use crate::services::jpeg::{self, ChromaSubsampling};
+use crate::services::scan_cache::ScanCache;
+use crate::services::sorter::OperationResult;
+use std::path::Path;
+use tauri::State;
+
+/// Konverterer bilder til et annet format ("jpeg", "png", "bmp" eller "gif")
+/// `quality` (1-100) og `subsampling` brukes kun for JPEG; standard er 90 og 4:4:4 som før.
+/// Uten `target_dir` lagres filene ved siden av originalene.
+#[tauri::command]
+pub async fn convert_images(
+    paths: Vec<String>,
+    target_format: String,
+    quality: Option<u8>,
+    subsampling: Option<ChromaSubsampling>,
+    target_dir: Option<String>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<OperationResult, AppError> {
+    let format = TargetFormat::from_name(&target_format)
+        .ok_or_else(|| AppError::UnsupportedFormat(target_format.clone()))?;
+    let quality = quality.unwrap_or(converter::DEFAULT_QUALITY);
+    jpeg::validate_quality(quality).map_err(AppError::InvalidInput)?;
+
+    let result = converter::convert_images(
+        paths,
+        format,
+        quality,
+        subsampling.unwrap_or_default(),
+        target_dir.as_deref().map(Path::new),
+    );
+    scan_cache.clear();
+    Ok(result)
+}
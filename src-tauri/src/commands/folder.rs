@@ -1,14 +1,21 @@
 //! Kommandoer for mappehåndtering og duplikatdeteksjon
 
-use crate::services::{hashing, scanner, thumbnail, sorter};
-use crate::services::sorter::{OperationResult, SortConfig};
-use crate::services::hashing::ComparableHash;
+use crate::services::{aspect, date_audit, duplicates, hashing, journal, metadata, path_codec, problems, scanner, stats, thumbnail, sorter};
+use crate::services::scanner::{MediaType, ScanError, ScanOptions};
+use crate::services::sorter::{CollisionStrategy, MergeDuplicateAction, OperationResult, RetryPolicy, SortConfig, SortMethod};
+use crate::services::hashing::{ComparableHash, HashSettings, ResizeFilter};
 use rayon::prelude::*;
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use crate::error::AppError;
 use crate::services::cache::HashCache;
+use crate::services::cancellation::CancellationToken;
+use crate::services::scan_cache::ScanCache;
+use tauri::State;
 
 #[derive(Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -17,6 +24,57 @@ pub struct ImageInfo {
     pub filename: String,
     pub extension: String,
     pub size_bytes: u64,
+    pub media_type: MediaType,
+    /// Kun satt når skanningen leser dimensjoner (`ScanOptions::read_dimensions`)
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub megapixels: Option<f64>,
+    /// Kildemappen bildet ble funnet i; kun satt av `find_duplicates_across`
+    pub source_folder: Option<String>,
+    /// Plassholder mens thumbnailen lastes; kun satt når skanningen ber om det (`include_blurhash`)
+    pub blurhash: Option<String>,
+}
+
+impl ImageInfo {
+    /// Bygger info fra sti og størrelse, uten dimensjoner
+    fn from_path(path_str: String, size_bytes: u64) -> Self {
+        let path = path_codec::decode(&path_str);
+        let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let extension = path.extension().unwrap_or_default().to_string_lossy().to_string().to_lowercase();
+        Self {
+            media_type: MediaType::from_extension(&extension),
+            path: path_str,
+            filename,
+            extension,
+            size_bytes,
+            width: None,
+            height: None,
+            megapixels: None,
+            source_folder: None,
+            blurhash: None,
+        }
+    }
+}
+
+impl From<scanner::ImageInfo> for ImageInfo {
+    fn from(img: scanner::ImageInfo) -> Self {
+        let megapixels = img
+            .width
+            .zip(img.height)
+            .map(|(width, height)| (width as f64 * height as f64 / 1_000_000.0 * 10.0).round() / 10.0);
+        Self {
+            path: img.path,
+            filename: img.filename,
+            extension: img.extension,
+            size_bytes: img.size_bytes,
+            media_type: img.media_type,
+            width: img.width,
+            height: img.height,
+            megapixels,
+            source_folder: None,
+            blurhash: None,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -25,6 +83,29 @@ pub struct ScanResult {
     pub image_count: usize,
     pub total_size_bytes: u64,
     pub images: Vec<ImageInfo>,
+    /// Filer som ikke kunne leses; de øvrige bildene er likevel med
+    pub errors: Vec<ScanError>,
+    pub cancelled: bool,
+}
+
+/// Standard antall bilder per `scan-batch`-event
+const DEFAULT_SCAN_BATCH_SIZE: usize = 500;
+
+/// Payload for `scan-batch`: bilder funnet siden forrige bolk
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanBatch {
+    pub images: Vec<ImageInfo>,
+}
+
+/// Payload for `scan-complete` (og returverdien til `scan_folder_streaming`)
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanSummary {
+    pub image_count: usize,
+    pub total_size_bytes: u64,
+    pub errors: Vec<ScanError>,
+    pub cancelled: bool,
 }
 
 #[derive(Serialize, Clone)]
@@ -34,10 +115,239 @@ pub struct ImageWithHash {
     pub hash: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct DuplicateGroup {
+    /// Id for `get_duplicate_group_page`; unik på tvers av søk i samme økt
+    pub id: usize,
+    /// Antall bilder i hele gruppen, også når `images` bare er første side
+    pub image_count: usize,
     pub images: Vec<ImageInfo>,
+    /// Likhet mot gruppens representant (første bilde), i samme rekkefølge som `images`
+    pub scores: Vec<SimilarityScore>,
+}
+
+/// Gruppene fra siste duplikatsøk, så store grupper kan hentes side for side
+/// i stedet for å sendes til frontend i én omgang
+#[derive(Default)]
+pub struct DuplicateStore(Mutex<StoredGroups>);
+
+#[derive(Default)]
+struct StoredGroups {
+    /// Id-en til `groups[0]`; øker for hvert søk så gamle id-er ikke treffer nye grupper
+    first_id: usize,
+    /// `None` før første søk. Delt, så eksporten kan skrive uten å holde låsen.
+    groups: Option<Arc<Vec<DuplicateGroup>>>,
+}
+
+impl DuplicateStore {
+    /// Gir gruppene id og antall og lagrer dem, i stedet for forrige søk
+    fn store(&self, groups: &mut [DuplicateGroup]) {
+        let mut stored = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        stored.first_id += stored.groups.as_ref().map_or(0, |groups| groups.len());
+        for (index, group) in groups.iter_mut().enumerate() {
+            group.id = stored.first_id + index;
+            group.image_count = group.images.len();
+        }
+        stored.groups = Some(Arc::new(groups.to_vec()));
+    }
+
+    /// Skriver gruppene fra siste søk til `writer`, én gruppe om gangen
+    /// `group_ids` velger ut enkelte grupper; `None` tar med alle. Stiene skrives som i
+    /// resten av API-et (`path_codec`). Gir feil hvis det ikke er gjort noe søk.
+    fn export(
+        &self,
+        format: ExportFormat,
+        group_ids: Option<&HashSet<usize>>,
+        writer: &mut impl std::io::Write,
+    ) -> Result<DuplicateExport, AppError> {
+        let stored = self.0.lock().unwrap_or_else(|e| e.into_inner()).groups.clone();
+        let stored = stored.ok_or_else(|| AppError::InvalidInput("ingen duplikatsøk å eksportere".to_string()))?;
+        let groups = stored.iter().filter(|group| group_ids.is_none_or(|ids| ids.contains(&group.id)));
+
+        let mut summary = DuplicateExport::default();
+        match format {
+            ExportFormat::Json => writer.write_all(b"[")?,
+            ExportFormat::Csv => writer.write_all(b"group_id,path,size_bytes,distance,similarity\n")?,
+        }
+        for group in groups {
+            let files = group.images.iter().zip(&group.scores).map(|(image, score)| ExportedFile {
+                path: image.path.clone(),
+                size_bytes: image.size_bytes,
+                distance: score.distance,
+                similarity: score.similarity,
+            });
+            match format {
+                ExportFormat::Json => {
+                    if summary.groups > 0 {
+                        writer.write_all(b",\n")?;
+                    }
+                    let exported = ExportedGroup { id: group.id, files: files.collect() };
+                    serde_json::to_writer(&mut *writer, &exported).map_err(|e| AppError::Other(e.to_string()))?;
+                }
+                ExportFormat::Csv => {
+                    for file in files {
+                        writeln!(
+                            writer,
+                            "{},{},{},{},{}",
+                            group.id,
+                            sorter::csv_field(&file.path),
+                            file.size_bytes,
+                            file.distance,
+                            file.similarity
+                        )?;
+                    }
+                }
+            }
+            summary.groups += 1;
+            summary.files += group.images.len();
+        }
+        if format == ExportFormat::Json {
+            writer.write_all(b"]\n")?;
+        }
+        writer.flush()?;
+        Ok(summary)
+    }
+
+    fn page(&self, group_id: usize, offset: usize, limit: usize) -> Option<DuplicateGroup> {
+        let stored = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let group = stored.groups.as_ref()?.get(group_id.checked_sub(stored.first_id)?)?;
+        let range = offset.min(group.images.len())..offset.saturating_add(limit).min(group.images.len());
+        Some(DuplicateGroup {
+            images: group.images[range.clone()].to_vec(),
+            scores: group.scores[range].to_vec(),
+            ..*group
+        })
+    }
+}
+
+/// Resultater med flere bilder enn dette sendes som sammendrag når frontend ikke har valgt
+/// sidestørrelse: hver gruppe kuttes til `DEFAULT_GROUP_PAGE_SIZE` bilder
+const SUMMARY_MIN_IMAGES: usize = 2000;
+const DEFAULT_GROUP_PAGE_SIZE: usize = 50;
+
+/// Lagrer gruppene i `store` og kutter hver gruppe til `page_size` bilder
+/// `None` kutter kun store resultater (se `SUMMARY_MIN_IMAGES`), `Some(0)` sender alt.
+fn store_duplicates(mut result: DuplicateResult, store: &DuplicateStore, page_size: Option<usize>) -> DuplicateResult {
+    store.store(&mut result.groups);
+    let total_images: usize = result.groups.iter().map(|group| group.images.len()).sum();
+    let page_size = match page_size {
+        Some(0) => None,
+        Some(page_size) => Some(page_size),
+        None => (total_images > SUMMARY_MIN_IMAGES).then_some(DEFAULT_GROUP_PAGE_SIZE),
+    };
+    if let Some(page_size) = page_size {
+        for group in &mut result.groups {
+            group.images.truncate(page_size);
+            group.scores.truncate(page_size);
+        }
+    }
+    result
+}
+
+/// Filformat for `export_duplicates`
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+/// Én gruppe i JSON-eksporten
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedGroup {
+    id: usize,
+    files: Vec<ExportedFile>,
+}
+
+/// Én fil i eksporten; distanse og likhet er mot gruppens første fil
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportedFile {
+    path: String,
+    size_bytes: u64,
+    distance: u32,
+    similarity: f64,
+}
+
+/// Resultat fra `export_duplicates`
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateExport {
+    pub groups: usize,
+    pub files: usize,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarityScore {
+    /// Hamming-distanse mellom hashene (0 = identisk)
+    pub distance: u32,
+    /// 0-100 %, beregnet som `(1 - distance/bits) * 100`
+    pub similarity: f64,
+}
+
+impl SimilarityScore {
+    /// Score for byte-identiske kopier
+    const IDENTICAL: SimilarityScore = SimilarityScore { distance: 0, similarity: 100.0 };
+
+    fn from_distance(distance: u32, bits: u32) -> Self {
+        Self {
+            distance,
+            similarity: duplicates::similarity_percent(distance, bits),
+        }
+    }
+}
+
+/// Ett treff fra `find_similar`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarImage {
+    pub image: ImageInfo,
+    pub score: SimilarityScore,
+}
+
+/// Resultat for én fil i `get_thumbnails`, og payload for `thumbnail-ready`
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailResult {
+    pub path: String,
+    pub thumbnail_path: Option<String>,
+    /// Animert GIF (thumbnailen viser første bilde)
+    pub animated: bool,
+    pub error: Option<String>,
+}
+
+/// Standard antall tråder for `prewarm_thumbnails`
+const PREWARM_THREADS: usize = 2;
+
+/// Resultat fra `prewarm_thumbnails`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PrewarmResult {
+    pub generated: usize,
+    pub already_cached: usize,
+    pub errors: Vec<ScanError>,
+    pub cancelled: bool,
+}
+
+/// Payload for `duplicate-progress`-eventet
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub errors: usize,
+}
+
+/// Payload for `sort-progress`-eventet
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SortProgress {
+    pub processed: usize,
+    pub total: usize,
+    pub current_file: String,
 }
 
 #[derive(Serialize)]
@@ -47,6 +357,48 @@ pub struct DuplicateResult {
     pub total_duplicates: usize,
     pub processed: usize,
     pub errors: usize,
+    pub cancelled: bool,
+}
+
+/// Resultat fra `find_duplicates_across`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CrossFolderDuplicateResult {
+    /// Grupper med bilder fra mer enn én kildemappe ("finnes allerede i arkivet")
+    pub cross_folder: Vec<DuplicateGroup>,
+    /// Grupper der alle bildene ligger i samme kildemappe
+    pub within_folder: Vec<DuplicateGroup>,
+    pub total_duplicates: usize,
+    pub processed: usize,
+    pub errors: usize,
+    pub cancelled: bool,
+}
+
+/// Struper progress-events fra parallelle jobber
+/// Sender hver `PROGRESS_EVERY`. fil, når jobben er ferdig, eller når det har gått `PROGRESS_INTERVAL`
+struct ProgressThrottle {
+    last_emit: Mutex<Instant>,
+}
+
+const PROGRESS_EVERY: usize = 50;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+impl ProgressThrottle {
+    fn new() -> Self {
+        Self { last_emit: Mutex::new(Instant::now()) }
+    }
+
+    fn should_emit(&self, done: usize, total: usize) -> bool {
+        let due = done.is_multiple_of(PROGRESS_EVERY) || done == total;
+        // try_lock: hvis en annen tråd sjekker samtidig, lar vi den ta seg av det
+        match self.last_emit.try_lock() {
+            Ok(mut last) if due || last.elapsed() >= PROGRESS_INTERVAL => {
+                *last = Instant::now();
+                true
+            }
+            _ => due,
+        }
+    }
 }
 
 /// Henter cache-mappe for thumbnails
@@ -55,331 +407,1547 @@ fn get_thumbnail_cache_dir() -> PathBuf {
     std::env::temp_dir().join("imagesorter-thumbnails")
 }
 
+/// Henter cache-mappe for perceptuelle hasher
+fn get_hash_cache_dir() -> PathBuf {
+    std::env::temp_dir().join("imagesorter-hashcache")
+}
+
+/// Henter mappe for angre-journaler
+fn get_journal_dir() -> PathBuf {
+    std::env::temp_dir().join("imagesorter-journal")
+}
+
+/// Lagrer angre-journalen for en operasjon og setter `operation_id` i resultatet
+fn save_journal(result: &mut OperationResult) {
+    if result.journal.is_empty() {
+        return;
+    }
+    match journal::save(&get_journal_dir(), &result.journal) {
+        Ok(id) => result.operation_id = Some(id),
+        Err(e) => {
+            result.operation_id = None;
+            result.error_messages.push(format!("Kunne ikke lagre angre-journal: {}", e));
+        }
+    }
+}
+
+/// Appens egne cache-mapper skal aldri dukke opp som bilder
+fn exclude_app_dirs(options: &mut ScanOptions) {
+    options.excluded_dirs = vec![get_thumbnail_cache_dir(), get_hash_cache_dir(), get_journal_dir()];
+}
+
+/// Teller bildene `scan_folder` med samme `options` ville funnet, uten å bygge bildelisten
+/// Rask nok til å kjøres før skanningen for å gi fremdriftsvisningen en nevner.
+#[tauri::command]
+pub async fn count_images(
+    path: String,
+    options: Option<ScanOptions>,
+    cancel: State<'_, CancellationToken>,
+) -> Result<usize, AppError> {
+    cancel.reset();
+    let mut options = options.unwrap_or_default();
+    exclude_app_dirs(&mut options);
+    Ok(scanner::count_images(&path, &options, &cancel)?)
+}
+
 /// Skanner en mappe og returnerer informasjon om bildene som ble funnet
+/// Kan avbrytes med `cancel_operation`; resultatet inneholder da bildene funnet så langt
+/// `include_blurhash` beregner plassholdere for rutenettet (genererer thumbnails, så det tar tid).
 #[tauri::command]
-pub async fn scan_folder(path: String) -> Result<ScanResult, String> {
-    let images = scanner::scan_directory(&path).map_err(|e| e.to_string())?;
+pub async fn scan_folder(
+    path: String,
+    options: Option<ScanOptions>,
+    include_blurhash: Option<bool>,
+    cancel: State<'_, CancellationToken>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<ScanResult, AppError> {
+    cancel.reset();
+    let mut options = options.unwrap_or_default();
+    exclude_app_dirs(&mut options);
+    let outcome = scanner::scan_directory_cancellable(&path, &options, &cancel)?;
+    let images = outcome.images;
+
+    // Avbrutte skanninger er ufullstendige og skal ikke gjenbrukes
+    if outcome.cancelled {
+        scan_cache.clear();
+    } else {
+        scan_cache.store(&path_codec::decode(&path), &options, images.iter().map(|img| img.path.clone()).collect());
+    }
 
     let total_size: u64 = images.iter().map(|img| img.size_bytes).sum();
     
-    let image_infos: Vec<ImageInfo> = images
+    let mut image_infos: Vec<ImageInfo> = images
         .into_iter()
-        .map(|img| ImageInfo {
-            path: img.path,
-            filename: img.filename,
-            extension: img.extension,
-            size_bytes: img.size_bytes,
-        })
+        .map(ImageInfo::from)
         .collect();
+    if include_blurhash.unwrap_or(false) {
+        add_blurhashes(&mut image_infos);
+    }
 
     Ok(ScanResult {
         image_count: image_infos.len(),
         total_size_bytes: total_size,
         images: image_infos,
+        errors: outcome.errors,
+        cancelled: outcome.cancelled,
     })
 }
 
+/// Som `scan_folder`, men sender bildene i `scan-batch`-events etter hvert som de
+/// blir funnet, og til slutt `scan-complete` med totaler. Gir et rutenett som fylles
+/// gradvis på store mapper. `batch_size` er antall bilder per event (standard 500).
+/// `include_blurhash` som i `scan_folder`.
+#[tauri::command]
+pub async fn scan_folder_streaming(
+    window: tauri::Window,
+    path: String,
+    options: Option<ScanOptions>,
+    batch_size: Option<usize>,
+    include_blurhash: Option<bool>,
+    cancel: State<'_, CancellationToken>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<ScanSummary, AppError> {
+    use tauri::Emitter;
+
+    cancel.reset();
+    let mut options = options.unwrap_or_default();
+    exclude_app_dirs(&mut options);
+
+    let mut paths = Vec::new();
+    let mut total_size_bytes = 0;
+    let outcome = scanner::scan_directory_batched(
+        &path,
+        &options,
+        &cancel,
+        batch_size.unwrap_or(DEFAULT_SCAN_BATCH_SIZE),
+        |batch| {
+            paths.extend(batch.iter().map(|img| img.path.clone()));
+            total_size_bytes += batch.iter().map(|img| img.size_bytes).sum::<u64>();
+            let mut images: Vec<ImageInfo> = batch.into_iter().map(ImageInfo::from).collect();
+            if include_blurhash.unwrap_or(false) {
+                add_blurhashes(&mut images);
+            }
+            let _ = window.emit("scan-batch", ScanBatch { images });
+        },
+    )?;
+
+    let image_count = paths.len();
+    // Avbrutte skanninger er ufullstendige og skal ikke gjenbrukes
+    if outcome.cancelled {
+        scan_cache.clear();
+    } else {
+        scan_cache.store(&path_codec::decode(&path), &options, paths);
+    }
+
+    let summary = ScanSummary {
+        image_count,
+        total_size_bytes,
+        errors: outcome.errors,
+        cancelled: outcome.cancelled,
+    };
+    let _ = window.emit("scan-complete", summary.clone());
+    Ok(summary)
+}
+
+/// Oversikt over en mappe: antall og størrelse per filtype, og datospennet
+/// Bruker samme filtre som `scan_folder`, men returnerer ingen bildeliste.
+#[tauri::command]
+pub async fn folder_stats(path: String, options: Option<ScanOptions>) -> Result<stats::FolderStats, AppError> {
+    let mut options = options.unwrap_or_default();
+    exclude_app_dirs(&mut options);
+    let images = scanner::scan_directory(&path, &options)?;
+    Ok(stats::folder_stats(&images))
+}
+
 /// Henter eller genererer en thumbnail for et bilde
-/// Returnerer stien til thumbnail-filen
+/// Returnerer stien til thumbnail-filen. Uten `config` brukes standard størrelse og JPEG.
 #[tauri::command]
-pub async fn get_thumbnail(path: String) -> Result<String, String> {
-    let image_path = Path::new(&path);
+pub async fn get_thumbnail(
+    path: String,
+    config: Option<thumbnail::ThumbnailConfig>,
+) -> Result<String, AppError> {
+    let image_path = path_codec::decode(&path);
     let cache_dir = get_thumbnail_cache_dir();
     
-    let thumbnail_path = thumbnail::get_or_create_thumbnail(image_path, &cache_dir)
-        .map_err(|e| e.to_string())?;
+    let config = config.unwrap_or_default();
+    config.check_format().map_err(AppError::UnsupportedFormat)?;
+
+    let thumbnail_path = thumbnail::get_or_create_thumbnail_with_config(&image_path, &cache_dir, &config)?;
     
     Ok(thumbnail_path.to_string_lossy().to_string())
 }
 
-/// Åpner et bilde i standard bildeviser
+/// Henter metadata (dato, GPS, kamera, dimensjoner) for ett bilde
 #[tauri::command]
-pub async fn open_image(path: String) -> Result<(), String> {
-    open::that(&path).map_err(|e| e.to_string())
+pub async fn get_image_metadata(path: String) -> Result<metadata::ImageMetadata, AppError> {
+    Ok(metadata::read_image_metadata(&path_codec::decode(&path))?)
 }
 
-/// Finner duplikater blant gitte bildestier ved hjelp av perceptuell hashing
-/// Optimalisert for store bildesamlinger med parallell prosessering
+/// Henter eller genererer thumbnails for flere bilder i ett kall
+/// Genereres parallelt; feil for enkeltfiler rapporteres per fil i stedet for å stoppe hele batchen
 #[tauri::command]
-pub async fn find_duplicates(app: tauri::AppHandle, paths: Vec<String>, threshold: u32) -> Result<DuplicateResult, String> {
-    use tauri::Emitter;
-    let error_count = Arc::new(Mutex::new(0usize));
-    
-    // --------------- STAGE 1: EXACT DUPLICATES (Rask filtrering) ---------------
-    // Grupperer filer basert på størrelse først, så partial hash for kandidater.
-    
-    let app_handle = app.clone();
-    let paths_len = paths.len();
-    
-    // 1.1 Samle filinfo (størrelse) raskt
-    let mut file_sizes: HashMap<u64, Vec<String>> = HashMap::new();
-    for path in &paths {
-         if let Ok(metadata) = std::fs::metadata(path) {
-             file_sizes.entry(metadata.len()).or_default().push(path.clone());
-         }
-    }
-    
-    // 1.2 Identifiser kandidater for eksakt match (samme størrelse)
-    let potential_exact_dupes: Vec<String> = file_sizes
-        .into_iter()
-        .filter(|(_, files)| files.len() > 1)
-        .flat_map(|(_, files)| files)
+pub async fn get_thumbnails(paths: Vec<String>) -> Result<Vec<ThumbnailResult>, AppError> {
+    let cache_dir = get_thumbnail_cache_dir();
+
+    let results = paths
+        .into_par_iter()
+        .map(|path| match thumbnail::get_thumbnail_info(&path_codec::decode(&path), &cache_dir, &Default::default()) {
+            Ok(info) => ThumbnailResult {
+                path,
+                thumbnail_path: Some(info.path.to_string_lossy().to_string()),
+                animated: info.animated,
+                error: None,
+            },
+            Err(e) => ThumbnailResult {
+                path,
+                thumbnail_path: None,
+                animated: false,
+                error: Some(e.to_string()),
+            },
+        })
         .collect();
 
-    // 1.3 Beregn partial hash for kandidater parallelt
-    let exact_dupe_cache = Arc::new(Mutex::new(HashMap::new()));
-    let exact_pool = rayon::ThreadPoolBuilder::new().num_threads(16).build().unwrap();
-    
-    let potential_ids: Vec<String> = potential_exact_dupes.clone();
-    
-    exact_pool.install(|| {
-        potential_ids.par_iter().for_each(|path_str| {
-            let path = Path::new(path_str);
-            if let Ok(p_hash) = hashing::compute_partial_hash(path) {
-                 exact_dupe_cache.lock().unwrap().insert(path_str.clone(), p_hash);
-            }
-        });
-    });
-    
-    // 1.4 Grupper eksakte duplikater
-    let mut exact_groups: HashMap<String, Vec<ImageInfo>> = HashMap::new();
-    let exact_cache_lock = exact_dupe_cache.lock().unwrap();
-    
-    for path_str in &potential_exact_dupes {
-        if let Some(hash) = exact_cache_lock.get(path_str) {
-             let path = Path::new(path_str);
-             let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-             let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-             
-             let extension = path.extension().unwrap_or_default().to_string_lossy().to_string().to_lowercase();
-             
-             let output_key = format!("{}_{}", size, hash); // Unik nøkkel for eksakt gruppe
-             
-             exact_groups.entry(output_key).or_default().push(ImageInfo {
-                 path: path_str.clone(),
-                 filename,
-                 extension,
-                 size_bytes: size
-             });
-        }
-    }
-    
-    // --------------- STAGE 2: VISUAL DUPLICATES (Perceptuell Hash) ---------------
-    // For alle bilder som IKKE er en del av en eksakt gruppe (eller vi velger 1 representant fra hver eksakt gruppe)
-    // kjører vi den tunge analysen.
-    
-    // Vi velger å kjøre visuell sjekk på ALLE unike bilder. 
-    // Hvis vi har 3 eksakte kopier av Bilde A, trenger vi bare å visuelt sjekke én av dem mot Bilde B.
-    
-    let mut files_to_visual_scan: Vec<String> = Vec::new();
-    let mut _handled_paths: std::collections::HashSet<String> = std::collections::HashSet::new();
+    Ok(results)
+}
 
-    // Legg til unike filer (de som ikke var i potential_exact_dupes)
-    let potential_set: std::collections::HashSet<_> = potential_exact_dupes.iter().collect();
-    for path in &paths {
-        if !potential_set.contains(path) {
-            files_to_visual_scan.push(path.clone());
-        }
-    }
-    
-    // For eksakte grupper, legg til den første som representant
-    for (_, group) in &exact_groups {
-        if let Some(first) = group.first() {
-            files_to_visual_scan.push(first.path.clone());
-            // Marker alle i gruppen som 'håndtert' i første omgang, 
-            // men vi må huske å merge dem tilbake i resultatet til slutt
-        }
-    }
-    
-    // Last inn cache for visuell hash
+/// Genererer thumbnails i bakgrunnen etter en skanning, så rutenettet slipper å vente
+/// Hver ferdig thumbnail sendes som `thumbnail-ready`-event (samme form som `get_thumbnails`).
+/// Thumbnails som allerede ligger i cachen hoppes over. Bruker få tråder (standard
+/// `PREWARM_THREADS`) for ikke å konkurrere med rutenettet om disken. Kan avbrytes.
+#[tauri::command]
+pub async fn prewarm_thumbnails(
+    window: tauri::Window,
+    cancel: State<'_, CancellationToken>,
+    paths: Vec<String>,
+    config: Option<thumbnail::ThumbnailConfig>,
+    max_threads: Option<usize>,
+) -> Result<PrewarmResult, AppError> {
+    use tauri::Emitter;
+
+    cancel.reset();
     let cache_dir = get_thumbnail_cache_dir();
-    let cache = Arc::new(RwLock::new(HashCache::new(&cache_dir)));
-    
-    let visual_pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(8)  // Lavere antall for å spare minne ved bilde-dekoding
-        .build()
-        .map_err(|e| format!("Kunne ikke starte trådpool: {}", e))?;
+    let config = config.unwrap_or_default();
+    config.check_format().map_err(AppError::UnsupportedFormat)?;
+    let pool = hashing_pool(Some(max_threads.unwrap_or(PREWARM_THREADS)))?;
 
-    let hashed_images: Vec<ImageWithHash> = visual_pool.install(|| {
-        files_to_visual_scan
-        .par_iter()
-        .filter_map(|path_str| {
-            let path = Path::new(path_str);
-            let metadata = match std::fs::metadata(path) {
-                Ok(m) => m,
-                Err(_) => {
-                    *error_count.lock().unwrap() += 1;
-                    return None;
-                }
-            };
-            
-            let mtime = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
-            let size_bytes = metadata.len();
-            let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
-
-            let extension = path.extension().unwrap_or_default().to_string_lossy().to_string().to_lowercase();
-
-            // Sjekk cache
-            {
-                let read_guard = cache.read().unwrap();
-                if let Some(cached_hash_str) = read_guard.get(path_str, mtime) {
-                    let _ = app_handle.emit("progress", serde_json::json!({ "tick": true }));
-                    return Some(ImageWithHash {
-                        info: ImageInfo { path: path_str.clone(), filename, extension, size_bytes },
-                        hash: cached_hash_str,
-                    });
-                }
-            }
+    let (cached, missing): (Vec<String>, Vec<String>) = paths
+        .into_iter()
+        .partition(|path| thumbnail::cached_thumbnail(&path_codec::decode(path), &cache_dir, &config).is_some());
 
-            // Beregn hash
-            match hashing::load_image(path) {
-                Ok(img) => {
-                    match hashing::compute_perceptual_hash(&img, hashing::HashType::Difference) {
-                        Ok(hash) => {
-                            let hash_str = hash.to_base64();
-                            {
-                                let mut write_guard = cache.write().unwrap();
-                                write_guard.insert(path_str.clone(), mtime, hash_str.clone());
-                            }
-                            let _ = app_handle.emit("progress", serde_json::json!({ "tick": true }));
-                            Some(ImageWithHash {
-                                info: ImageInfo { path: path_str.clone(), filename, extension, size_bytes },
-                                hash: hash_str,
-                            })
-                        }
-                        Err(_) => {
-                            *error_count.lock().unwrap() += 1;
-                            None
-                        }
-                    }
-                }
-                Err(_) => {
-                    *error_count.lock().unwrap() += 1;
-                    None
+    let generated = AtomicUsize::new(0);
+    let errors = Mutex::new(Vec::new());
+    pool.install(|| {
+        missing.into_par_iter().for_each(|path| {
+            if cancel.is_cancelled() {
+                return;
+            }
+            match thumbnail::get_thumbnail_info(&path_codec::decode(&path), &cache_dir, &config) {
+                Ok(info) => {
+                    generated.fetch_add(1, Ordering::Relaxed);
+                    let _ = window.emit("thumbnail-ready", ThumbnailResult {
+                        path,
+                        thumbnail_path: Some(info.path.to_string_lossy().to_string()),
+                        animated: info.animated,
+                        error: None,
+                    });
                 }
+                Err(e) => errors.lock().unwrap().push(ScanError { path, message: e.to_string() }),
             }
-        })
-        .collect()
+        });
     });
 
-    // Lagre cache
-    if let Ok(read_guard) = cache.read() {
-        let _ = read_guard.save();
-    }
-    
-    // Bygg BK-Tree for visuelt søk
-    let mut tree = bk_tree::BKTree::new(hashing::PerceptualMetric);
-    let mut hash_to_indices: HashMap<ComparableHash, Vec<usize>> = HashMap::new();
-
-    for (idx, img) in hashed_images.iter().enumerate() {
-        if let Ok(hash) = img_hash::ImageHash::<Box<[u8]>>::from_base64(&img.hash) {
-             let comp_hash = ComparableHash(hash);
-             tree.add(comp_hash.clone());
-             hash_to_indices.entry(comp_hash).or_default().push(idx);
-        }
-    }
+    Ok(PrewarmResult {
+        generated: generated.into_inner(),
+        already_cached: cached.len(),
+        errors: errors.into_inner().unwrap_or_default(),
+        cancelled: cancel.is_cancelled(),
+    })
+}
 
-    // Finn visuelle grupper
-    let mut final_groups: Vec<Vec<ImageInfo>> = Vec::new();
-    let mut visited: std::collections::HashSet<usize> = std::collections::HashSet::new();
-
-    for (i, img) in hashed_images.iter().enumerate() {
-        if visited.contains(&i) { continue; }
-
-        if let Ok(hash) = img_hash::ImageHash::<Box<[u8]>>::from_base64(&img.hash) {
-            let comp_hash = ComparableHash(hash);
-            let matches = tree.find(&comp_hash, threshold);
-            
-            let mut group_members: Vec<ImageInfo> = Vec::new();
-            
-            // Hvis vi finner matcher, må vi utvide resultatet med evt eksakte kopier
-            // som vi filtrerte ut tidligere.
-            for (_dist, found_hash) in matches {
-                if let Some(indices) = hash_to_indices.get(found_hash) {
-                    for &idx in indices {
-                        if !visited.contains(&idx) {
-                            visited.insert(idx);
-                            
-                            // 1. Legg til den visuelle matchen (representanten)
-                            let rep = &hashed_images[idx];
-                            group_members.push(rep.info.clone());
-                            
-                            // 2. Sjekk om denne representanten har eksakte kopier
-                            // Vi må finne dem ved å søke gjennom exact_groups
-                            // Dette er litt tregt (lineært søk), men antall grupper er forhåpentligvis håndterbart.
-                            // Optimalisering: Kunne lagd en map: path -> group_id
-                            
-                            for group in exact_groups.values() {
-                                // Hvis representanten finnes i en eksakt gruppe...
-                                if group.iter().any(|g| g.path == rep.info.path) {
-                                    // ...legg til resten av gruppen også
-                                    for member in group {
-                                        if member.path != rep.info.path {
-                                            group_members.push(member.clone());
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+/// Blurhash for ett bilde, til en plassholder mens thumbnailen lastes
+/// Caches sammen med thumbnailene, så gjentatte kall er billige.
+#[tauri::command]
+pub async fn get_blurhash(path: String) -> Result<String, AppError> {
+    Ok(thumbnail::compute_blurhash(&path_codec::decode(&path), &get_thumbnail_cache_dir())?)
+}
 
-            if group_members.len() > 1 {
-                final_groups.push(group_members);
-            }
+/// Fyller inn `blurhash` for bildene (parallelt). Filer som ikke kan leses får `None`.
+fn add_blurhashes(images: &mut [ImageInfo]) {
+    let cache_dir = get_thumbnail_cache_dir();
+    images.par_iter_mut().for_each(|image| {
+        if image.media_type == MediaType::Image {
+            image.blurhash = thumbnail::compute_blurhash(&path_codec::decode(&image.path), &cache_dir).ok();
         }
-    }
-    
-    // Legg til eventuelle "rene" eksakte grupper som ikke ble fanget opp av visuelt søk? 
-    // (Det burde ikke skje, siden representanten er med i visuelt søk, og vil matche seg selv med distanse 0).
-    
-    let duplicate_groups: Vec<DuplicateGroup> = final_groups
-        .into_iter()
-        .map(|images| DuplicateGroup { images })
-        .collect();
-
-    let total_duplicates: usize = duplicate_groups.iter().map(|g| g.images.len() - 1).sum();
-    let errors = *error_count.lock().unwrap();
-
-    Ok(DuplicateResult {
-        groups: duplicate_groups,
-        total_duplicates,
-        processed: paths_len,
-        errors,
-    })
+    });
 }
 
+/// Henter eller genererer en thumbnail og returnerer den som base64 `data:`-URL
+/// Alternativ til `get_thumbnail` for oppsett der asset-protokollen ikke fungerer
+#[tauri::command]
+pub async fn get_thumbnail_data_url(path: String) -> Result<String, AppError> {
+    let image_path = path_codec::decode(&path);
+    let cache_dir = get_thumbnail_cache_dir();
+
+    let thumbnail_path = thumbnail::get_or_create_thumbnail(&image_path, &cache_dir)?;
 
+    Ok(thumbnail::thumbnail_data_url(&thumbnail_path)?)
+}
 
-/// Sorterer bilder basert på dato til en målsti (År/Måned)
+/// Finner tomme, ødelagte og for små bilder (under `min_dimension` piksler, standard 32)
+/// Kun headeren leses, med full dekoding bare for mistenkelige filer. Kjøres parallelt.
 #[tauri::command]
-pub async fn sort_images_by_date(
+pub async fn find_problem_images(
     paths: Vec<String>,
-    method: String, // "copy" eller "move"
-    target_dir: String,
-    options: Option<SortConfig>,
-) -> Result<OperationResult, String> {
-    
-    let config = options.unwrap_or(SortConfig {
-        use_day_folder: false,
-        use_month_names: false,
-    });
-
-    let result = sorter::sort_images(paths, &target_dir, &method, config);
-    Ok(result)
+    min_dimension: Option<u32>,
+) -> Result<Vec<problems::ProblemImage>, AppError> {
+    Ok(problems::find_problem_images(&paths, min_dimension.unwrap_or(problems::DEFAULT_MIN_DIMENSION)))
 }
 
-/// Sletter bilder (flytter til papirkurv hvis mulig)
+/// Finner bilder uten dato, med epoke-dato (1970 osv.), dato i fremtiden eller eldre enn
+/// `min_year` (standard 1990). Kun lesing, kjøres parallelt; nyttig før en stor sortering.
 #[tauri::command]
-pub async fn delete_images(paths: Vec<String>) -> Result<OperationResult, String> {
-    let result = sorter::delete_images(paths);
-    Ok(result)
+pub async fn audit_dates(paths: Vec<String>, min_year: Option<i32>) -> Result<Vec<date_audit::DateIssue>, AppError> {
+    Ok(date_audit::audit_dates(&paths, min_year.unwrap_or(date_audit::DEFAULT_MIN_YEAR)))
 }
 
-/// Flytter bilder til valgt mappe (uten datosortering)
+/// Deler bildene i liggende, stående og kvadratiske (forhold 0,95-1,05) ut fra filheaderen
+/// Filer der dimensjonene ikke kan leses listes i `unreadable`. I mappemaler gir
+/// `{orientation}` samme inndeling.
 #[tauri::command]
-pub async fn move_images(paths: Vec<String>, target_dir: String) -> Result<OperationResult, String> {
-    let result = sorter::move_images(paths, &target_dir);
-    Ok(result)
+pub async fn group_by_orientation(paths: Vec<String>) -> Result<aspect::OrientationGroups, AppError> {
+    Ok(aspect::group_by_orientation(&paths))
+}
+
+/// Sjekker at `path` er en eksisterende fil, så OS-et ikke gir en kryptisk feil
+fn existing_file(path: &str) -> Result<PathBuf, AppError> {
+    let file = path_codec::decode(path);
+    if !file.exists() {
+        return Err(AppError::NotFound(path.to_string()));
+    }
+    if !file.is_file() {
+        return Err(AppError::InvalidInput(format!("{} er ikke en fil", path)));
+    }
+    Ok(file)
+}
+
+/// Åpner et bilde i standard bildeviser
+#[tauri::command]
+pub async fn open_image(path: String) -> Result<(), AppError> {
+    let file = existing_file(&path)?;
+    Ok(open::that(file)?)
+}
+
+/// Standard maks antall bilder `open_images` åpner i ett kall
+const DEFAULT_MAX_OPEN_IMAGES: usize = 20;
+
+/// Åpner flere bilder i standard bildeviser
+/// Feil for enkeltfiler samles i resultatet. Flere enn `max_files` (standard 20) avvises
+/// før noe åpnes, så et feilklikk ikke starter hundrevis av vinduer.
+#[tauri::command]
+pub async fn open_images(paths: Vec<String>, max_files: Option<usize>) -> Result<OperationResult, AppError> {
+    let max_files = max_files.unwrap_or(DEFAULT_MAX_OPEN_IMAGES);
+    if paths.len() > max_files {
+        return Err(AppError::InvalidInput(format!(
+            "vil åpne {} bilder, maks er {}",
+            paths.len(),
+            max_files
+        )));
+    }
+
+    let mut result = OperationResult::new();
+    result.processed = paths.len();
+    for path in &paths {
+        match existing_file(path).and_then(|file| Ok(open::that(file)?)) {
+            Ok(()) => result.add_success(),
+            Err(e) => result.add_error(format!("Kunne ikke åpne {}: {}", path, e)),
+        }
+    }
+    Ok(result)
+}
+
+/// Viser filen i filbehandleren: markert i Utforsker (Windows) og Finder (macOS),
+/// ellers åpnes mappen den ligger i.
+#[tauri::command]
+pub async fn reveal_in_file_manager(path: String) -> Result<(), AppError> {
+    let file = existing_file(&path)?;
+    reveal(&file)
+}
+
+#[cfg(target_os = "windows")]
+fn reveal(file: &Path) -> Result<(), AppError> {
+    use std::os::windows::process::CommandExt;
+    // Utforsker tolker ikke Rusts vanlige sitering av argumenter, så stien siteres selv.
+    // Den returnerer feilkode også når det går bra, så kun oppstarten sjekkes.
+    let child = std::process::Command::new("explorer")
+        .raw_arg(format!("/select,\"{}\"", file.display()))
+        .spawn()?;
+    reap(child);
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn reveal(file: &Path) -> Result<(), AppError> {
+    reap(std::process::Command::new("open").arg("-R").arg(file).spawn()?);
+    Ok(())
+}
+
+/// Venter på prosessen i bakgrunnen, så den ikke blir liggende som zombie
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn reap(mut child: std::process::Child) {
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn reveal(file: &Path) -> Result<(), AppError> {
+    // xdg-open kan ikke markere filer, så vi åpner mappen. `open::that` venter selv på
+    // xdg-open, så ingen prosess blir liggende igjen.
+    let folder = file.parent().unwrap_or(Path::new("."));
+    Ok(open::that(folder)?)
+}
+
+/// Avbryter pågående skanning eller duplikatsøk
+#[tauri::command]
+pub async fn cancel_operation(cancel: State<'_, CancellationToken>) -> Result<(), AppError> {
+    cancel.cancel();
+    Ok(())
+}
+
+/// Finner duplikater blant gitte bildestier
+/// `hash_mode`: "exact" (SHA-256, ignorerer threshold), "blake3" (som exact, men raskere),
+/// "difference" (standard), "perceptual", "average"
+/// eller "crop_resistant" (finner også beskårne kopier; ca. 3x tregere, anbefalt threshold 8-10)
+/// "rotation_invariant" finner også kopier rotert 90/180/270 grader (ca. 4x tregere enn "difference")
+/// "color" sammenligner fargehistogrammer og finner bilder med lik fargepalett selv om
+/// motivet er flyttet. Threshold er da maks avstand i prosent (0-100, anbefalt 10-15).
+/// `hash_size`: 8 (standard, 64 bit) eller 16 (256 bit, færre falske treff i store samlinger).
+/// Terskelen er en Hamming-distanse og skaleres med antall bits: 16x16 trenger 4x terskel.
+/// Uten `threshold` brukes anbefalt verdi for modus og størrelse (5 for 8x8, 20 for 16x16).
+/// Optimalisert for store bildesamlinger med parallell prosessering.
+/// `max_threads` begrenser antall tråder for hashingen (standard: antall fysiske kjerner,
+/// 0 = alle logiske kjerner). Lavere verdi hjelper på mekaniske disker.
+/// Kan avbrytes med `cancel_operation`; grupperingen gjøres da på bildene som rakk å bli hashet.
+/// Bildene hashes i bolker og kun hashene beholdes, så minnebruken er omtrent stien pluss
+/// 150 byte per bilde med 8x8-hash (se `duplicates::HashSetGrouper`).
+/// `resize_filter`: filter for nedskaleringen før hashing, "triangle" (standard), "nearest"
+/// (raskest, men kan bomme på kopier med fine detaljer) eller "lanczos3" (skarpest, tregest).
+/// `group_page_size` begrenser hver gruppe i svaret til så mange bilder; `imageCount` gir
+/// hele størrelsen, og resten hentes med `get_duplicate_group_page`. Uten den kuttes gruppene
+/// til 50 bilder når resultatet har over 2000 bilder totalt; 0 sender alltid alt.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn find_duplicates(
+    app: tauri::AppHandle,
+    cancel: State<'_, CancellationToken>,
+    store: State<'_, DuplicateStore>,
+    paths: Vec<String>,
+    threshold: Option<u32>,
+    hash_mode: Option<String>,
+    hash_size: Option<u32>,
+    resize_filter: Option<ResizeFilter>,
+    max_threads: Option<usize>,
+    group_page_size: Option<usize>,
+) -> Result<DuplicateResult, AppError> {
+    cancel.reset();
+    let settings = parse_hash_settings(hash_mode.as_deref(), hash_size, resize_filter)?;
+    let result = find_duplicates_in_paths(&app, &cancel, paths, threshold, settings, max_threads)?;
+    Ok(store_duplicates(result, &store, group_page_size))
+}
+
+/// Henter bildene `offset..offset + limit` i en gruppe fra siste duplikatsøk
+/// Gir `NotFound` hvis gruppen er fra et eldre søk.
+#[tauri::command]
+pub async fn get_duplicate_group_page(
+    store: State<'_, DuplicateStore>,
+    group_id: usize,
+    offset: usize,
+    limit: usize,
+) -> Result<DuplicateGroup, AppError> {
+    store
+        .page(group_id, offset, limit)
+        .ok_or_else(|| AppError::NotFound(format!("duplikatgruppe {}", group_id)))
+}
+
+/// Lagrer resultatet fra siste duplikatsøk som JSON eller CSV, med alle bildene i hver gruppe
+/// (også de som ikke er hentet med `get_duplicate_group_page`). `group_ids` begrenser
+/// eksporten til enkelte grupper. Filen skrives gruppe for gruppe, så store rapporter ikke
+/// bygges opp i minnet; en halvskrevet fil fjernes ved feil. Gir feil før første søk.
+#[tauri::command]
+pub async fn export_duplicates(
+    store: State<'_, DuplicateStore>,
+    format: ExportFormat,
+    output_path: String,
+    group_ids: Option<Vec<usize>>,
+) -> Result<DuplicateExport, AppError> {
+    let output_path = path_codec::decode(&output_path);
+    let group_ids: Option<HashSet<usize>> = group_ids.map(|ids| ids.into_iter().collect());
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(&output_path)?);
+    let result = store.export(format, group_ids.as_ref(), &mut writer);
+    drop(writer);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&output_path);
+    }
+    result
+}
+
+/// Som `find_duplicates`, men for alle bildene i en mappe
+/// Bruker resultatet fra siste `scan_folder` av samme mappe hvis det finnes, ellers skannes
+/// mappen (rekursivt, kun bilder). Sparer frontend for å sende alle stiene tilbake.
+/// `group_page_size` som i `find_duplicates`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn find_duplicates_in_folder(
+    app: tauri::AppHandle,
+    cancel: State<'_, CancellationToken>,
+    scan_cache: State<'_, ScanCache>,
+    store: State<'_, DuplicateStore>,
+    path: String,
+    threshold: Option<u32>,
+    hash_mode: Option<String>,
+    hash_size: Option<u32>,
+    resize_filter: Option<ResizeFilter>,
+    max_threads: Option<usize>,
+    group_page_size: Option<usize>,
+) -> Result<DuplicateResult, AppError> {
+    cancel.reset();
+    let settings = parse_hash_settings(hash_mode.as_deref(), hash_size, resize_filter)?;
+
+    let Some(paths) = folder_image_paths(&path, &cancel, &scan_cache)? else {
+        return Ok(DuplicateResult {
+            groups: Vec::new(),
+            total_duplicates: 0,
+            processed: 0,
+            errors: 0,
+            cancelled: true,
+        });
+    };
+
+    let result = find_duplicates_in_paths(&app, &cancel, paths, threshold, settings, max_threads)?;
+    Ok(store_duplicates(result, &store, group_page_size))
+}
+
+/// Bildene i en mappe, fra siste `scan_folder` av samme mappe med standardvalg hvis den finnes,
+/// ellers skannet med standardvalg. `None` hvis skanningen ble avbrutt.
+fn folder_image_paths(
+    path: &str,
+    cancel: &CancellationToken,
+    scan_cache: &ScanCache,
+) -> Result<Option<Vec<String>>, AppError> {
+    let mut options = ScanOptions::default();
+    exclude_app_dirs(&mut options);
+    if let Some(paths) = scan_cache.get(&path_codec::decode(path), &options) {
+        return Ok(Some(paths));
+    }
+    let outcome = scanner::scan_directory_cancellable(path, &options, cancel)?;
+    if outcome.cancelled {
+        return Ok(None);
+    }
+    let paths: Vec<String> = outcome.images.into_iter().map(|img| img.path).collect();
+    scan_cache.store(&path_codec::decode(path), &options, paths.clone());
+    Ok(Some(paths))
+}
+
+/// Antall bilder som hashes før hashene legges inn i grupperingen
+/// Holder hash-strengene for kun én bolk i minnet om gangen.
+const HASH_CHUNK_SIZE: usize = 2048;
+
+/// Tolker `hash_mode` fra frontend (standard "difference")
+fn parse_hash_mode(hash_mode: Option<&str>) -> Result<hashing::HashType, AppError> {
+    match hash_mode {
+        Some(mode) => hashing::HashType::from_mode(mode)
+            .ok_or_else(|| AppError::InvalidInput(format!("ukjent hash-modus {}", mode))),
+        None => Ok(hashing::HashType::Difference),
+    }
+}
+
+/// Validerer `hash_size` fra frontend (standard 8x8)
+fn parse_hash_size(hash_size: Option<u32>) -> Result<u32, AppError> {
+    let size = hash_size.unwrap_or(hashing::DEFAULT_HASH_SIZE);
+    if hashing::HASH_SIZES.contains(&size) {
+        Ok(size)
+    } else {
+        Err(AppError::InvalidInput(format!("ugyldig hash-størrelse {}, bruk 8 eller 16", size)))
+    }
+}
+
+/// Tolker hash-innstillingene fra frontend (se `find_duplicates`)
+fn parse_hash_settings(
+    hash_mode: Option<&str>,
+    hash_size: Option<u32>,
+    resize_filter: Option<ResizeFilter>,
+) -> Result<HashSettings, AppError> {
+    Ok(HashSettings {
+        hash_type: parse_hash_mode(hash_mode)?,
+        hash_size: parse_hash_size(hash_size)?,
+        filter: resize_filter.unwrap_or_default(),
+    })
+}
+
+/// Trådpool for hashing, adskilt fra rayons globale pool
+/// Standard er antall fysiske kjerner; færre tråder gir mindre I/O-kø på trege disker.
+/// 0 bruker alle logiske kjerner.
+fn hashing_pool(max_threads: Option<usize>) -> Result<rayon::ThreadPool, AppError> {
+    let threads = max_threads.unwrap_or_else(num_cpus::get_physical);
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .map_err(|e| AppError::Other(format!("Kunne ikke starte trådpool: {}", e)))
+}
+
+fn find_duplicates_in_paths(
+    app: &tauri::AppHandle,
+    cancel: &CancellationToken,
+    paths: Vec<String>,
+    threshold: Option<u32>,
+    settings: HashSettings,
+    max_threads: Option<usize>,
+) -> Result<DuplicateResult, AppError> {
+    use tauri::Emitter;
+
+    // Duplikatsøket gjelder foreløpig kun bilder; videoer fra en blandet skanning hoppes over
+    let paths: Vec<String> = paths
+        .into_iter()
+        .filter(|path| {
+            let ext = Path::new(path).extension().unwrap_or_default().to_string_lossy();
+            MediaType::from_extension(&ext) == MediaType::Image
+        })
+        .collect();
+    // Samme fil under flere navn skal hverken hashes flere ganger eller matche seg selv
+    let paths = duplicates::dedupe_paths(paths);
+
+    let HashSettings { hash_type, hash_size, .. } = settings;
+    let threshold = threshold.unwrap_or_else(|| hash_type.default_threshold(hash_size));
+
+    let pool = hashing_pool(max_threads)?;
+
+    if hash_type.is_content_hash() {
+        return Ok(pool.install(|| find_exact_duplicates(&paths, hash_type, cancel)));
+    }
+
+    // --------------- STAGE 1: EXACT DUPLICATES (Rask filtrering) ---------------
+    // Byte-identiske kopier finnes med størrelse, hash av start og slutt og til slutt full hash.
+
+    let app_handle = app.clone();
+    let paths_len = paths.len();
+
+    let exact = pool.install(|| duplicates::find_exact_duplicates(&paths, hashing::HashType::Blake3, cancel));
+    let exact_groups: Vec<Vec<ImageInfo>> = exact
+        .groups
+        .into_iter()
+        .map(|group| {
+            group
+                .into_iter()
+                .map(|(path_str, size_bytes)| ImageInfo::from_path(path_str, size_bytes))
+                .collect()
+        })
+        .collect();
+
+    // --------------- STAGE 2: VISUAL DUPLICATES (Perceptuell Hash) ---------------
+    // Hvis vi har 3 eksakte kopier av Bilde A, trenger vi bare å visuelt sjekke én av dem mot Bilde B.
+    // Kopiene legges tilbake i resultatet til slutt.
+    let in_exact_group: HashSet<&str> = exact_groups.iter().flatten().map(|img| img.path.as_str()).collect();
+    let mut files_to_visual_scan: Vec<String> = paths
+        .iter()
+        .filter(|path| !in_exact_group.contains(path.as_str()))
+        .cloned()
+        .collect();
+    files_to_visual_scan.extend(exact_groups.iter().filter_map(|group| group.first()).map(|img| img.path.clone()));
+    
+    // Last inn cache for visuell hash
+    let cache_dir = get_hash_cache_dir();
+    let cache = Arc::new(RwLock::new(HashCache::new(&cache_dir)));
+    
+    let total = files_to_visual_scan.len();
+    let processed = AtomicUsize::new(0);
+    let errors = AtomicUsize::new(0);
+    let throttle = ProgressThrottle::new();
+
+    // Hashing i bolker: hver bolk hashes parallelt og legges rett inn i BK-treet, så kun
+    // hashene (ikke strenger eller bildeinfo) for alle bildene ligger i minnet samtidig.
+    // Grupperingen er transitiv (Union-Find); fargehistogrammer sammenlignes parvis til slutt.
+    // Hvert bilde kan ha flere hasher (beskjæringsresistent modus), kommaseparert
+    let is_color = hash_type == hashing::HashType::Color;
+    let mut grouper = duplicates::HashSetGrouper::new(threshold);
+    let mut histograms: Vec<hashing::ColorHistogram> = Vec::new();
+    // Sti og størrelse per bilde i grupperingen; `ImageInfo` bygges kun for treffene
+    let mut hash_owners: Vec<(String, u64)> = Vec::new();
+
+    for chunk in files_to_visual_scan.chunks(HASH_CHUNK_SIZE) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let hashed: Vec<ImageWithHash> = pool.install(|| {
+            chunk
+                .par_iter()
+                .filter_map(|path_str| {
+                    if cancel.is_cancelled() {
+                        return None;
+                    }
+
+                    let hashed = hash_image_cached(path_str, &settings, &cache);
+                    if hashed.is_none() {
+                        errors.fetch_add(1, Ordering::Relaxed);
+                    }
+
+                    let done = processed.fetch_add(1, Ordering::Relaxed) + 1;
+                    if throttle.should_emit(done, total) {
+                        let _ = app_handle.emit("duplicate-progress", DuplicateProgress {
+                            processed: done,
+                            total,
+                            errors: errors.load(Ordering::Relaxed),
+                        });
+                    }
+
+                    hashed
+                })
+                .collect()
+        });
+
+        for img in hashed {
+            if is_color {
+                if let Some(histogram) = hashing::histogram_from_string(&img.hash) {
+                    histograms.push(histogram);
+                    hash_owners.push((img.info.path, img.info.size_bytes));
+                }
+            } else if let Some(hashes) = parse_hash_set(&img.hash) {
+                grouper.add(hashes);
+                hash_owners.push((img.info.path, img.info.size_bytes));
+            }
+        }
+    }
+
+    // Lagre cache (også ved avbrudd: hver oppføring er komplett, og lagringen er atomisk)
+    if let Ok(read_guard) = cache.read() {
+        let _ = read_guard.save();
+    }
+
+    let similar_groups = if is_color {
+        duplicates::group_similar_histograms(&histograms, threshold as f32 / 100.0)
+    } else {
+        grouper.groups()
+    };
+
+    // Likhet mellom representanten og et bilde i gruppen. Fargemodus måler i prosent.
+    let score = |rep: usize, idx: usize| {
+        if is_color {
+            let distance = hashing::histogram_distance(&histograms[rep], &histograms[idx]);
+            SimilarityScore::from_distance((distance * 100.0).round() as u32, 100)
+        } else {
+            let (rep_hashes, hashes) = (grouper.hashes(rep), grouper.hashes(idx));
+            let bits = rep_hashes.first().map(|h| h.0.as_bytes().len() as u32 * 8).unwrap_or(64);
+            SimilarityScore::from_distance(duplicates::hash_set_distance(rep_hashes, hashes), bits)
+        }
+    };
+
+    // Oppslag fra representant til dens eksakte kopier (filtrert ut i steg 1)
+    let mut exact_copies: HashMap<&str, &Vec<ImageInfo>> = HashMap::new();
+    for group in &exact_groups {
+        for member in group {
+            exact_copies.insert(member.path.as_str(), group);
+        }
+    }
+
+    let mut duplicate_groups: Vec<DuplicateGroup> = Vec::new();
+
+    for indices in similar_groups {
+        let mut group = DuplicateGroup::default();
+        // Første bilde i gruppen er representanten som alle scorer måles mot
+        let group_rep = indices[0];
+
+        for idx in indices {
+            let score = score(group_rep, idx);
+
+            // 1. Legg til den visuelle matchen (representanten)
+            let (path, size_bytes) = &hash_owners[idx];
+            group.images.push(ImageInfo::from_path(path.clone(), *size_bytes));
+            group.scores.push(score);
+
+            // 2. Legg til eventuelle eksakte kopier av representanten (samme score)
+            if let Some(copies) = exact_copies.get(path.as_str()) {
+                for member in copies.iter() {
+                    if member.path != *path {
+                        group.images.push(member.clone());
+                        group.scores.push(score);
+                    }
+                }
+            }
+        }
+
+        if group.images.len() > 1 {
+            duplicate_groups.push(group);
+        }
+    }
+
+    let total_duplicates: usize = duplicate_groups.iter().map(|g| g.images.len() - 1).sum();
+    let errors = errors.load(Ordering::Relaxed);
+
+    Ok(DuplicateResult {
+        groups: duplicate_groups,
+        total_duplicates,
+        processed: paths_len,
+        errors,
+        cancelled: cancel.is_cancelled(),
+    })
+}
+
+
+/// Finner bildene blant `candidate_paths` som ligner på `reference_path`
+/// Treff innenfor `threshold` returneres sortert med mest like først. `hash_mode` som i
+/// `find_duplicates`, men kun perceptuelle moduser. Referansebildet selv og videoer hoppes over.
+/// `hash_size`, `resize_filter` og standard `threshold` som i `find_duplicates`.
+#[tauri::command]
+pub async fn find_similar(
+    reference_path: String,
+    candidate_paths: Vec<String>,
+    threshold: Option<u32>,
+    hash_mode: Option<String>,
+    hash_size: Option<u32>,
+    resize_filter: Option<ResizeFilter>,
+) -> Result<Vec<SimilarImage>, AppError> {
+    let settings = parse_hash_settings(hash_mode.as_deref(), hash_size, resize_filter)?;
+    let HashSettings { hash_type, hash_size, .. } = settings;
+    if hash_type.is_content_hash() || hash_type == hashing::HashType::Color {
+        return Err(AppError::InvalidInput(format!(
+            "{} støttes ikke her, velg en perceptuell hash-modus",
+            hash_type.as_str()
+        )));
+    }
+    let threshold = threshold.unwrap_or_else(|| hash_type.default_threshold(hash_size));
+
+    let cache = RwLock::new(HashCache::new(&get_hash_cache_dir()));
+
+    let reference_file = path_codec::decode(&reference_path);
+    std::fs::metadata(&reference_file)?;
+    let reference = hash_image_cached(&reference_path, &settings, &cache)
+        .and_then(|img| parse_hash_set(&img.hash))
+        .ok_or_else(|| AppError::Decode(reference_path.clone()))?;
+
+    let candidates: Vec<(ImageInfo, Vec<ComparableHash>)> = candidate_paths
+        .par_iter()
+        .map(|path| (path, path_codec::decode(path)))
+        .filter(|(_, file)| *file != reference_file)
+        .filter(|(_, file)| {
+            let ext = file.extension().unwrap_or_default().to_string_lossy();
+            MediaType::from_extension(&ext) == MediaType::Image
+        })
+        .filter_map(|(path, _)| {
+            let img = hash_image_cached(path, &settings, &cache)?;
+            Some((img.info, parse_hash_set(&img.hash)?))
+        })
+        .collect();
+
+    if let Ok(read_guard) = cache.read() {
+        let _ = read_guard.save();
+    }
+
+    let bits = reference.first().map(|h| h.0.as_bytes().len() as u32 * 8).unwrap_or(64);
+    let hash_sets: Vec<Vec<ComparableHash>> = candidates.iter().map(|(_, hashes)| hashes.clone()).collect();
+
+    Ok(duplicates::rank_similar(&reference, &hash_sets, threshold)
+        .into_iter()
+        .map(|(idx, distance)| SimilarImage {
+            image: candidates[idx].0.clone(),
+            score: SimilarityScore::from_distance(distance, bits),
+        })
+        .collect())
+}
+
+/// Et bilde fra `find_already_imported` som allerede finnes i referansemappen
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportedMatch {
+    pub candidate: ImageInfo,
+    /// Nærmeste treff i referansemappen
+    pub reference: ImageInfo,
+    pub score: SimilarityScore,
+}
+
+/// Resultat fra `find_already_imported`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlreadyImportedResult {
+    /// Kandidatene med treff, i samme rekkefølge som `candidate_paths`
+    pub matches: Vec<ImportedMatch>,
+    pub cancelled: bool,
+}
+
+/// Finner hvilke av `candidate_paths` (f.eks. et minnekort) som allerede finnes i `reference_folder`
+/// Referansemappen hentes fra siste skanning eller skannes, hashes via hash-cachen og legges i
+/// et BK-tre. Bare kandidater med treff returneres; duplikater innad i referansen rapporteres
+/// ikke. `threshold`, `hash_mode`, `hash_size` og `resize_filter` som i `find_duplicates`, men
+/// fargehistogram støttes ikke. Kan avbrytes.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn find_already_imported(
+    cancel: State<'_, CancellationToken>,
+    scan_cache: State<'_, ScanCache>,
+    reference_folder: String,
+    candidate_paths: Vec<String>,
+    threshold: Option<u32>,
+    hash_mode: Option<String>,
+    hash_size: Option<u32>,
+    resize_filter: Option<ResizeFilter>,
+    max_threads: Option<usize>,
+) -> Result<AlreadyImportedResult, AppError> {
+    cancel.reset();
+    let settings = parse_hash_settings(hash_mode.as_deref(), hash_size, resize_filter)?;
+    let HashSettings { hash_type, hash_size, .. } = settings;
+    if hash_type == hashing::HashType::Color {
+        return Err(AppError::InvalidInput(format!("{} støttes ikke her", hash_type.as_str())));
+    }
+    let threshold = threshold.unwrap_or_else(|| hash_type.default_threshold(hash_size));
+
+    let Some(reference_paths) = folder_image_paths(&reference_folder, &cancel, &scan_cache)? else {
+        return Ok(AlreadyImportedResult { matches: Vec::new(), cancelled: true });
+    };
+    // Kandidater som selv ligger i referansemappen ville bare funnet seg selv
+    let in_reference: HashSet<&String> = reference_paths.iter().collect();
+    let candidate_paths: Vec<String> = candidate_paths
+        .into_iter()
+        .filter(|path| !in_reference.contains(path))
+        .collect();
+
+    let pool = hashing_pool(max_threads)?;
+    let cache = RwLock::new(HashCache::new(&get_hash_cache_dir()));
+    let hash_all = |paths: &[String]| -> Vec<ImageWithHash> {
+        pool.install(|| {
+            paths
+                .par_iter()
+                .filter(|path| {
+                    let ext = Path::new(path).extension().unwrap_or_default().to_string_lossy();
+                    MediaType::from_extension(&ext) == MediaType::Image
+                })
+                .filter(|_| !cancel.is_cancelled())
+                .filter_map(|path| hash_image_cached(path, &settings, &cache))
+                .collect()
+        })
+    };
+    let reference = hash_all(&reference_paths);
+    let candidates = hash_all(&candidate_paths);
+    if let Ok(read_guard) = cache.read() {
+        let _ = read_guard.save();
+    }
+    if cancel.is_cancelled() {
+        return Ok(AlreadyImportedResult { matches: Vec::new(), cancelled: true });
+    }
+
+    let matches = if hash_type.is_content_hash() {
+        let mut by_hash: HashMap<&str, usize> = HashMap::new();
+        for (idx, img) in reference.iter().enumerate() {
+            by_hash.entry(img.hash.as_str()).or_insert(idx);
+        }
+        candidates
+            .into_iter()
+            .filter_map(|img| {
+                let &idx = by_hash.get(img.hash.as_str())?;
+                Some(ImportedMatch {
+                    candidate: img.info,
+                    reference: reference[idx].info.clone(),
+                    score: SimilarityScore::IDENTICAL,
+                })
+            })
+            .collect()
+    } else {
+        let hash_sets: Vec<Vec<ComparableHash>> =
+            reference.iter().map(|img| parse_hash_set(&img.hash).unwrap_or_default()).collect();
+        let index = duplicates::ReferenceIndex::new(&hash_sets);
+        candidates
+            .into_iter()
+            .filter_map(|img| {
+                let hashes = parse_hash_set(&img.hash)?;
+                let (idx, distance) = index.nearest(&hashes, threshold)?;
+                let bits = hashes.first().map(|h| h.0.as_bytes().len() as u32 * 8).unwrap_or(64);
+                Some(ImportedMatch {
+                    candidate: img.info,
+                    reference: reference[idx].info.clone(),
+                    score: SimilarityScore::from_distance(distance, bits),
+                })
+            })
+            .collect()
+    };
+
+    Ok(AlreadyImportedResult { matches, cancelled: false })
+}
+
+/// Finner nedlastingskopier ut fra filnavn: `photo.jpg`, `photo (1).jpg`, `photo - Kopi.jpg` osv.
+/// i samme mappe. Ingen dekoding, så mye raskere enn `find_duplicates`.
+/// Med `verify` (standard) sjekkes innholdet med SHA-256, og hver gruppe får `contentsMatch`.
+#[tauri::command]
+pub async fn find_name_duplicates(
+    paths: Vec<String>,
+    verify: Option<bool>,
+) -> Result<Vec<duplicates::NameDuplicateGroup>, AppError> {
+    Ok(duplicates::find_name_duplicates(&paths, verify.unwrap_or(true)))
+}
+
+/// Resultat fra `hash_images`
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashImagesResult {
+    /// I samme rekkefølge som `paths`, uten filene som feilet
+    pub hashes: Vec<ImageWithHash>,
+    pub errors: Vec<ScanError>,
+    pub cancelled: bool,
+}
+
+/// Beregner hasher for bildene uten å gruppere dem, for egne verktøy eller ekstern caching
+/// `hash_mode`, `hash_size` og `resize_filter` som i `find_duplicates`; hashene har samme format som i
+/// hash-cachen (base64, kommaseparert for flere utsnitt). Videoer hoppes over.
+/// Hver bolk sendes også som `hash-chunk`-event, slik at frontend kan gruppere underveis.
+#[tauri::command]
+pub async fn hash_images(
+    app: tauri::AppHandle,
+    cancel: State<'_, CancellationToken>,
+    paths: Vec<String>,
+    hash_mode: Option<String>,
+    hash_size: Option<u32>,
+    resize_filter: Option<ResizeFilter>,
+    max_threads: Option<usize>,
+) -> Result<HashImagesResult, AppError> {
+    use tauri::Emitter;
+
+    cancel.reset();
+    let settings = parse_hash_settings(hash_mode.as_deref(), hash_size, resize_filter)?;
+    let pool = hashing_pool(max_threads)?;
+    let cache = RwLock::new(HashCache::new(&get_hash_cache_dir()));
+
+    let paths: Vec<String> = paths
+        .into_iter()
+        .filter(|path| {
+            let ext = Path::new(path).extension().unwrap_or_default().to_string_lossy();
+            MediaType::from_extension(&ext) == MediaType::Image
+        })
+        .collect();
+
+    let mut result = HashImagesResult { hashes: Vec::new(), errors: Vec::new(), cancelled: false };
+    for chunk in paths.chunks(HASH_CHUNK_SIZE) {
+        if cancel.is_cancelled() {
+            break;
+        }
+        let hashed: Vec<(&String, Option<ImageWithHash>)> = pool.install(|| {
+            chunk
+                .par_iter()
+                .filter(|_| !cancel.is_cancelled())
+                .map(|path| (path, hash_image_cached(path, &settings, &cache)))
+                .collect()
+        });
+
+        let mut chunk_hashes = Vec::new();
+        for (path, hashed) in hashed {
+            match hashed {
+                Some(img) => chunk_hashes.push(img),
+                None => result.errors.push(ScanError {
+                    path: path.clone(),
+                    message: "Kunne ikke lese eller dekode bildet".to_string(),
+                }),
+            }
+        }
+        let _ = app.emit("hash-chunk", &chunk_hashes);
+        result.hashes.extend(chunk_hashes);
+    }
+
+    if let Ok(read_guard) = cache.read() {
+        let _ = read_guard.save();
+    }
+    result.cancelled = cancel.is_cancelled();
+    Ok(result)
+}
+
+/// Finner duplikater på tvers av flere mapper (f.eks. et minnekort og arkivet)
+/// Hver mappe skannes rekursivt, og hvert bilde merkes med `source_folder`. Grupper som
+/// spenner over flere kildemapper rapporteres adskilt fra duplikater innen samme mappe.
+/// Ligger en fil i flere av mappene (nøstede mapper), tilhører den den første i listen.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn find_duplicates_across(
+    app: tauri::AppHandle,
+    cancel: State<'_, CancellationToken>,
+    store: State<'_, DuplicateStore>,
+    folders: Vec<String>,
+    threshold: Option<u32>,
+    hash_mode: Option<String>,
+    hash_size: Option<u32>,
+    resize_filter: Option<ResizeFilter>,
+    max_threads: Option<usize>,
+) -> Result<CrossFolderDuplicateResult, AppError> {
+    cancel.reset();
+    if folders.is_empty() {
+        return Err(AppError::InvalidInput("ingen mapper valgt".to_string()));
+    }
+    let settings = parse_hash_settings(hash_mode.as_deref(), hash_size, resize_filter)?;
+
+    let mut options = ScanOptions::default();
+    exclude_app_dirs(&mut options);
+
+    let mut source_of: HashMap<String, String> = HashMap::new();
+    let mut paths = Vec::new();
+    for folder in &folders {
+        let outcome = scanner::scan_directory_cancellable(folder, &options, &cancel)?;
+        for img in outcome.images {
+            if !source_of.contains_key(&img.path) {
+                source_of.insert(img.path.clone(), folder.clone());
+                paths.push(img.path);
+            }
+        }
+        if outcome.cancelled {
+            break;
+        }
+    }
+
+    let mut result = find_duplicates_in_paths(&app, &cancel, paths, threshold, settings, max_threads)?;
+    for group in &mut result.groups {
+        for image in &mut group.images {
+            image.source_folder = source_of.get(&image.path).cloned();
+        }
+    }
+    store.store(&mut result.groups);
+
+    let mut cross_folder = Vec::new();
+    let mut within_folder = Vec::new();
+    for group in result.groups {
+        let first_folder = &group.images[0].source_folder;
+        if group.images.iter().any(|image| image.source_folder != *first_folder) {
+            cross_folder.push(group);
+        } else {
+            within_folder.push(group);
+        }
+    }
+
+    Ok(CrossFolderDuplicateResult {
+        cross_folder,
+        within_folder,
+        total_duplicates: result.total_duplicates,
+        processed: result.processed,
+        errors: result.errors,
+        cancelled: result.cancelled,
+    })
+}
+
+/// Tolker en lagret hash-streng (flere hasher er kommaseparert)
+/// Lengden følger av base64-strengen, så 8x8- og 16x16-hasher tolkes likt.
+fn parse_hash_set(hash: &str) -> Option<Vec<ComparableHash>> {
+    hash.split(',')
+        .map(|h| img_hash::ImageHash::<Box<[u8]>>::from_base64(h).map(ComparableHash).ok())
+        .collect()
+}
+
+/// Beregner perceptuell hash for én fil, med oppslag i og oppdatering av hash-cachen
+/// Innholdshasher (exact/blake3) caches ikke.
+/// Returnerer `None` hvis filen ikke kan leses eller dekodes
+fn hash_image_cached(
+    path_str: &str,
+    settings: &HashSettings,
+    cache: &RwLock<HashCache>,
+) -> Option<ImageWithHash> {
+    let HashSettings { hash_type, hash_size, filter } = *settings;
+    let path = path_codec::decode(path_str);
+    let path = path.as_path();
+    let metadata = std::fs::metadata(path).ok()?;
+
+    let mtime = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+    let size_bytes = metadata.len();
+    let info = ImageInfo::from_path(path_str.to_string(), size_bytes);
+
+    if hash_type.is_content_hash() {
+        let hash = hashing::compute_content_hash(path, hash_type).ok()?;
+        return Some(ImageWithHash { info, hash });
+    }
+
+    // Sjekk cache
+    if let Some(hash) = cache.read().unwrap().get(path_str, mtime, size_bytes, settings) {
+        return Some(ImageWithHash { info, hash });
+    }
+
+    // Beregn hash. Beskjæringsresistent modus lagrer én hash per utsnitt, kommaseparert.
+    let img = hashing::load_image_with_filter(path, filter).ok()?;
+    let hash = if hash_type == hashing::HashType::Color {
+        hashing::histogram_to_string(&hashing::compute_color_histogram(&img))
+    } else if hash_type == hashing::HashType::CropResistant || hash_type == hashing::HashType::RotationInvariant {
+        let hashes = if hash_type == hashing::HashType::CropResistant {
+            hashing::compute_crop_resistant_hashes(&img, hash_size)
+        } else {
+            hashing::compute_rotation_invariant_hashes(&img, hash_size)
+        };
+        hashes
+            .ok()?
+            .iter()
+            .map(|h| h.to_base64())
+            .collect::<Vec<_>>()
+            .join(",")
+    } else {
+        hashing::compute_perceptual_hash_with_size(&img, hash_type, hash_size).ok()?.to_base64()
+    };
+    cache.write().unwrap().insert(path_str, mtime, size_bytes, settings, hash.clone());
+
+    Some(ImageWithHash { info, hash })
+}
+
+/// Tømmer den persistente hash-cachen
+/// Returnerer `true` hvis det fantes en cache som ble slettet
+#[tauri::command]
+pub async fn clear_hash_cache() -> Result<bool, AppError> {
+    Ok(HashCache::clear(&get_hash_cache_dir())?)
+}
+
+/// Sammenligner to bilder med alle hash-algoritmer, f.eks. for å forklare et treff
+/// eller finne riktig `threshold` for `find_duplicates`
+#[tauri::command]
+pub async fn compare_images(path_a: String, path_b: String) -> Result<duplicates::ComparisonReport, AppError> {
+    Ok(duplicates::compare_images(&path_codec::decode(&path_a), &path_codec::decode(&path_b))?)
+}
+
+/// Tømmer thumbnail-cachen og rapporterer hvor mye plass som ble frigjort
+#[tauri::command]
+pub async fn clear_thumbnail_cache() -> Result<thumbnail::CacheClearResult, AppError> {
+    Ok(thumbnail::clear_cache_with_stats(&get_thumbnail_cache_dir())?)
+}
+
+/// Foreslår hvilken fil som beholdes i hver duplikatgruppe
+/// `strategy`: "largest", "smallest", "newest", "oldest" eller "highest_resolution".
+/// Resultatet har samme rekkefølge som `groups`; tomme grupper hoppes over.
+/// Filer under `protected_prefixes` (f.eks. arkivmappen) beholdes alltid og foreslås
+/// aldri slettet. En gruppe der alle filene er beskyttet gir feil.
+#[tauri::command]
+pub async fn select_keepers(
+    groups: Vec<Vec<String>>,
+    strategy: String,
+    protected_prefixes: Option<Vec<String>>,
+) -> Result<Vec<duplicates::KeeperSuggestion>, AppError> {
+    let strategy = duplicates::KeepStrategy::from_name(&strategy)
+        .ok_or_else(|| AppError::InvalidInput(format!("ukjent strategi {}", strategy)))?;
+    let protected_prefixes = duplicates::protected_prefixes(&protected_prefixes.unwrap_or_default());
+
+    groups
+        .par_iter()
+        .filter_map(|paths| duplicates::select_keeper_protected(paths, strategy, &protected_prefixes).transpose())
+        .collect::<Result<_, _>>()
+        .map_err(AppError::InvalidInput)
+}
+
+/// Hvor mye plass som frigjøres ved å slette alt unntatt den beholdte filen i hver gruppe
+/// Bruker samme strategi og beskyttede mapper som `select_keepers`, og `sizeBytes` fra gruppene
+/// (en `ImageInfo` fra duplikatsøket kan sendes direkte).
+#[tauri::command]
+pub async fn duplicate_waste(
+    groups: Vec<Vec<duplicates::SizedPath>>,
+    strategy: String,
+    protected_prefixes: Option<Vec<String>>,
+) -> Result<duplicates::DuplicateWaste, AppError> {
+    let strategy = duplicates::KeepStrategy::from_name(&strategy)
+        .ok_or_else(|| AppError::InvalidInput(format!("ukjent strategi {}", strategy)))?;
+    let protected_prefixes = duplicates::protected_prefixes(&protected_prefixes.unwrap_or_default());
+    duplicates::duplicate_waste(&groups, strategy, &protected_prefixes).map_err(AppError::InvalidInput)
+}
+
+/// Grupperer bilder tatt tett i tid (serier/burst), i kronologisk rekkefølge
+/// Bilder innenfor `max_gap_seconds` av forrige bilde havner i samme gruppe;
+/// bilder uten lesbar dato blir egne grupper.
+#[tauri::command]
+pub async fn group_bursts(paths: Vec<String>, max_gap_seconds: f64) -> Result<Vec<Vec<String>>, AppError> {
+    if !max_gap_seconds.is_finite() || max_gap_seconds < 0.0 {
+        return Err(AppError::InvalidInput(format!("ugyldig tidsavstand {}", max_gap_seconds)));
+    }
+    let max_gap_ms = (max_gap_seconds * 1000.0).round() as i64;
+    Ok(duplicates::group_bursts(&paths, max_gap_ms))
+}
+
+/// Eksakt duplikatsøk (SHA-256 eller BLAKE3) uten bildedekoding
+fn find_exact_duplicates(paths: &[String], hash_type: hashing::HashType, cancel: &CancellationToken) -> DuplicateResult {
+    let exact = duplicates::find_exact_duplicates(paths, hash_type, cancel);
+
+    let groups: Vec<DuplicateGroup> = exact
+        .groups
+        .into_iter()
+        .map(|group| DuplicateGroup {
+            scores: vec![SimilarityScore::IDENTICAL; group.len()],
+            images: group
+                .into_iter()
+                .map(|(path_str, size_bytes)| ImageInfo::from_path(path_str, size_bytes))
+                .collect(),
+            ..Default::default()
+        })
+        .collect();
+
+    let total_duplicates: usize = groups.iter().map(|g| g.images.len() - 1).sum();
+
+    DuplicateResult {
+        groups,
+        total_duplicates,
+        processed: paths.len(),
+        errors: exact.errors,
+        cancelled: exact.cancelled,
+    }
+}
+
+/// Sorterer bilder basert på dato til en målsti (År/Måned)
+/// Med `options.flatten` havner alt rett i målmappen med tidspunktet foran filnavnet.
+/// Sender `sort-progress`-events underveis, strupet som for duplikatsøket.
+#[tauri::command]
+pub async fn sort_images_by_date(
+    window: tauri::Window,
+    paths: Vec<String>,
+    method: String, // "copy", "move", "hardlink" eller "symlink"
+    target_dir: String,
+    options: Option<SortConfig>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<OperationResult, AppError> {
+    use tauri::Emitter;
+
+    let method = SortMethod::from_name(&method).ok_or_else(|| {
+        AppError::InvalidInput(format!(
+            "ukjent sorteringsmetode {} (gyldige: copy, move, hardlink, symlink)",
+            method
+        ))
+    })?;
+    let config = options.unwrap_or_default();
+    let throttle = ProgressThrottle::new();
+
+    let mut result = sorter::sort_images_with_progress(paths, &target_dir, method, config, |processed, total, file| {
+        if throttle.should_emit(processed, total) {
+            let _ = window.emit("sort-progress", SortProgress {
+                processed,
+                total,
+                current_file: file.to_string(),
+            });
+        }
+    });
+    save_journal(&mut result);
+    scan_cache.clear();
+    Ok(result)
+}
+
+/// Sjekker at filene i et sorteringsmanifest fortsatt finnes med samme størrelse
+/// `verify_checksums` sammenligner også SHA-256 (krever `manifestChecksums` ved sortering).
+/// Gir feil hvis manifestet ikke kan leses eller ikke er et manifest.
+#[tauri::command]
+pub async fn verify_manifest(
+    manifest_path: String,
+    verify_checksums: Option<bool>,
+) -> Result<sorter::VerifyReport, AppError> {
+    let report = sorter::verify_manifest(&path_codec::decode(&manifest_path), verify_checksums.unwrap_or(false))?;
+    Ok(report)
+}
+
+/// Antall filer og samlet størrelse for en sletting, til bekreftelsesdialogen
+/// Leser kun metadata; stier som ikke finnes listes i `missing`.
+#[tauri::command]
+pub async fn summarize_deletion(paths: Vec<String>) -> Result<sorter::DeletionSummary, AppError> {
+    Ok(sorter::summarize_deletion(&paths))
+}
+
+/// Sletter bilder (flytter til papirkurv hvis mulig)
+/// `permanent` tillater permanent sletting når papirkurven ikke er tilgjengelig
+/// (standard: nei). Slike filer listes i `permanentlyDeleted`.
+#[tauri::command]
+pub async fn delete_images(
+    paths: Vec<String>,
+    permanent: Option<bool>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<OperationResult, AppError> {
+    let result = sorter::delete_images(paths, permanent.unwrap_or(false));
+    scan_cache.clear();
+    Ok(result)
+}
+
+/// Gjenoppretter slettede bilder fra papirkurven til opprinnelig sti
+/// Gir feil per fil på plattformer uten støtte (macOS).
+#[tauri::command]
+pub async fn restore_from_trash(
+    paths: Vec<String>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<OperationResult, AppError> {
+    let result = sorter::restore_from_trash(paths);
+    scan_cache.clear();
+    Ok(result)
+}
+
+/// Flytter bilder til valgt mappe (uten datosortering)
+/// `retry` som `SortConfig::retry` (standard 3 forsøk).
+#[tauri::command]
+pub async fn move_images(
+    paths: Vec<String>,
+    target_dir: String,
+    dry_run: Option<bool>,
+    collision: Option<CollisionStrategy>,
+    retry: Option<RetryPolicy>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<OperationResult, AppError> {
+    let mut result = sorter::move_images(
+        paths,
+        &target_dir,
+        dry_run.unwrap_or(false),
+        collision.unwrap_or_default(),
+        &retry.unwrap_or_default(),
+    );
+    save_journal(&mut result);
+    scan_cache.clear();
+    Ok(result)
+}
+
+/// Flytter de ekstra kopiene i hver duplikatgruppe til `review_dir` for gjennomgang
+/// Filen `strategy` velger (som i `select_keepers`) blir liggende, og filer under
+/// `protected_prefixes` flyttes aldri. `preserve_structure` beholder mappestrukturen under
+/// `review_dir`. Flyttingene kan angres med `operation.operationId`.
+#[tauri::command]
+pub async fn quarantine_duplicates(
+    groups: Vec<Vec<String>>,
+    strategy: String,
+    review_dir: String,
+    preserve_structure: Option<bool>,
+    dry_run: Option<bool>,
+    protected_prefixes: Option<Vec<String>>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<sorter::QuarantineResult, AppError> {
+    let strategy = duplicates::KeepStrategy::from_name(&strategy)
+        .ok_or_else(|| AppError::InvalidInput(format!("ukjent strategi {}", strategy)))?;
+    let mut result = sorter::quarantine_duplicates(
+        &groups,
+        strategy,
+        &review_dir,
+        preserve_structure.unwrap_or(false),
+        dry_run.unwrap_or(false),
+        &protected_prefixes.unwrap_or_default(),
+    );
+    save_journal(&mut result.operation);
+    scan_cache.clear();
+    Ok(result)
+}
+
+/// Slår sammen mappen `source` inn i `target`, med samme undermapper
+/// Filer som allerede finnes med likt innhold under `target` håndteres etter `on_duplicate`
+/// (standard: blir liggende); andre navnekollisjoner får nytt navn. Flyttingene kan angres.
+/// `retry` som i `move_images`.
+#[tauri::command]
+pub async fn merge_folders(
+    source: String,
+    target: String,
+    on_duplicate: Option<MergeDuplicateAction>,
+    retry: Option<RetryPolicy>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<OperationResult, AppError> {
+    let mut result = sorter::merge_folders(&source, &target, on_duplicate.unwrap_or_default(), &retry.unwrap_or_default());
+    save_journal(&mut result);
+    scan_cache.clear();
+    Ok(result)
+}
+
+/// Skriver inn datoen fra sidecar/video/endringsdato som EXIF i bilder uten EXIF-dato
+/// Kun JPEG og TIFF; andre formater gir feil per fil. Bilder med EXIF-dato hoppes over.
+#[tauri::command]
+pub async fn backfill_exif_dates(paths: Vec<String>) -> Result<OperationResult, AppError> {
+    Ok(metadata::backfill_exif_dates(&paths))
+}
+
+/// Gir bilder nye navn i mappen de ligger i, etter et mønster som `"{date}_{counter:04}"`
+/// `planned` i resultatet inneholder gammelt og nytt navn per fil. Kan angres som en flytting.
+#[tauri::command]
+pub async fn rename_images(
+    paths: Vec<String>,
+    pattern: String,
+    dry_run: Option<bool>,
+    collision: Option<CollisionStrategy>,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<OperationResult, AppError> {
+    let mut result = sorter::rename_images(paths, &pattern, collision.unwrap_or_default(), dry_run.unwrap_or(false));
+    save_journal(&mut result);
+    scan_cache.clear();
+    Ok(result)
+}
+
+/// Angrer en tidligere sortering/flytting
+/// Flyttede filer flyttes tilbake og kopier slettes; filer som er endret siden hoppes over.
+#[tauri::command]
+pub async fn undo_operation(
+    operation_id: String,
+    scan_cache: State<'_, ScanCache>,
+) -> Result<journal::UndoResult, AppError> {
+    let result = journal::undo(&get_journal_dir(), &operation_id)?;
+    scan_cache.clear();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(paths: &[&str], distance: u32) -> DuplicateGroup {
+        DuplicateGroup {
+            images: paths.iter().map(|path| ImageInfo::from_path(path.to_string(), 10)).collect(),
+            scores: vec![SimilarityScore { distance, similarity: 100.0 }; paths.len()],
+            ..Default::default()
+        }
+    }
+
+    fn export(store: &DuplicateStore, format: ExportFormat, group_ids: Option<&HashSet<usize>>) -> String {
+        let mut bytes = Vec::new();
+        store.export(format, group_ids, &mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    fn result(groups: Vec<DuplicateGroup>) -> DuplicateResult {
+        DuplicateResult { groups, total_duplicates: 0, processed: 0, errors: 0, cancelled: false }
+    }
+
+    #[test]
+    fn test_store_and_page_duplicates() {
+        let store = DuplicateStore::default();
+        assert!(store.page(0, 0, 10).is_none());
+
+        let first = store_duplicates(result(vec![group(&["/a.jpg", "/b.jpg", "/c.jpg"], 0)]), &store, Some(2));
+        assert_eq!(first.groups[0].images.len(), 2);
+        assert_eq!(first.groups[0].image_count, 3);
+
+        let page = store.page(0, 2, 10).unwrap();
+        assert_eq!(page.images.len(), 1);
+        assert_eq!(page.images[0].path, "/c.jpg");
+        assert!(store.page(0, 10, 10).unwrap().images.is_empty());
+        assert!(store.page(0, usize::MAX, usize::MAX).unwrap().images.is_empty());
+        assert!(store.page(1, 0, 10).is_none());
+
+        // Nytt søk erstatter det forrige, med nye id-er
+        let second = store_duplicates(result(vec![group(&["/d.jpg", "/e.jpg"], 0)]), &store, None);
+        assert_eq!(second.groups[0].id, 1);
+        assert!(store.page(0, 0, 10).is_none());
+        assert_eq!(store.page(1, 0, 10).unwrap().images.len(), 2);
+    }
+
+    #[test]
+    fn test_large_results_default_to_summary() {
+        let store = DuplicateStore::default();
+        let paths: Vec<String> = (0..=SUMMARY_MIN_IMAGES).map(|i| format!("/{}.jpg", i)).collect();
+        let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+
+        let summary = store_duplicates(result(vec![group(&paths, 0)]), &store, None);
+        assert_eq!(summary.groups[0].images.len(), DEFAULT_GROUP_PAGE_SIZE);
+        assert_eq!(summary.groups[0].image_count, paths.len());
+
+        let everything = store_duplicates(result(vec![group(&paths, 0)]), &store, Some(0));
+        assert_eq!(everything.groups[0].images.len(), paths.len());
+    }
+
+    #[test]
+    fn test_export_duplicates() {
+        let store = DuplicateStore::default();
+        assert!(store.export(ExportFormat::Json, None, &mut Vec::new()).is_err());
+
+        store.store(&mut [group(&["/a.jpg", "/b, kopi.jpg"], 0), group(&["/c.jpg", "/d.jpg", "/e.jpg"], 3)]);
+
+        let json: serde_json::Value = serde_json::from_str(&export(&store, ExportFormat::Json, None)).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+        assert_eq!(json[0]["id"], 0);
+        assert_eq!(json[0]["files"][1]["path"], "/b, kopi.jpg");
+        assert_eq!(json[1]["files"][2]["sizeBytes"], 10);
+        assert_eq!(json[1]["files"][0]["distance"], 3);
+
+        let csv = export(&store, ExportFormat::Csv, None);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "group_id,path,size_bytes,distance,similarity");
+        assert_eq!(lines[2], "0,\"/b, kopi.jpg\",10,0,100");
+        assert_eq!(lines.len(), 6);
+
+        let only_second: HashSet<usize> = [1].into_iter().collect();
+        let csv = export(&store, ExportFormat::Csv, Some(&only_second));
+        assert_eq!(csv.lines().skip(1).map(|line| &line[..2]).collect::<Vec<_>>(), vec!["1,"; 3]);
+    }
 }
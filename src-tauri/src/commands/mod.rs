@@ -1,4 +1,5 @@
 //! Tauri-kommandoer som kalles fra frontend
 
+pub mod convert;
 pub mod folder;
 
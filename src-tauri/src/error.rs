@@ -0,0 +1,126 @@
+//! Felles feiltype for Tauri-kommandoene
+//!
+//! Serialiseres som `{ "code": "not_found", "message": "..." }`, slik at frontend
+//! kan skille feiltypene med `code` og vise `message` direkte til brukeren.
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("Finnes ikke: {0}")]
+    NotFound(String),
+    #[error("Ingen tilgang: {0}")]
+    PermissionDenied(String),
+    #[error("Formatet støttes ikke: {0}")]
+    UnsupportedFormat(String),
+    #[error("Ugyldig verdi: {0}")]
+    InvalidInput(String),
+    #[error("Filfeil: {0}")]
+    Io(String),
+    #[error("Kunne ikke dekode bildet: {0}")]
+    Decode(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl AppError {
+    /// Stabil kode som frontend kan switche på
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::PermissionDenied(_) => "permission_denied",
+            AppError::UnsupportedFormat(_) => "unsupported_format",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::Io(_) => "io",
+            AppError::Decode(_) => "decode",
+            AppError::Other(_) => "other",
+        }
+    }
+
+    /// Klassifiserer en feil fra tjenestelaget. Kjente feiltyper (I/O, bildedekoding)
+    /// får egen kode; resten blir `Other` med original melding.
+    fn from_dyn(error: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(e) = error.downcast_ref::<io::Error>() {
+            return Self::from_io(e);
+        }
+        if let Some(e) = error.downcast_ref::<image::ImageError>() {
+            return Self::from_image(e);
+        }
+        AppError::Other(error.to_string())
+    }
+
+    fn from_io(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::NotFound => AppError::NotFound(error.to_string()),
+            io::ErrorKind::PermissionDenied => AppError::PermissionDenied(error.to_string()),
+            _ => AppError::Io(error.to_string()),
+        }
+    }
+
+    fn from_image(error: &image::ImageError) -> Self {
+        match error {
+            image::ImageError::Unsupported(e) => AppError::UnsupportedFormat(e.to_string()),
+            image::ImageError::IoError(e) => Self::from_io(e),
+            other => AppError::Decode(other.to_string()),
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(error: io::Error) -> Self {
+        Self::from_io(&error)
+    }
+}
+
+impl From<image::ImageError> for AppError {
+    fn from(error: image::ImageError) -> Self {
+        Self::from_image(&error)
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for AppError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        Self::from_dyn(error.as_ref())
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for AppError {
+    fn from(error: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        Self::from_dyn(error.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialized_shape() {
+        let json = serde_json::to_value(AppError::NotFound("/bilder".to_string())).unwrap();
+        assert_eq!(json["code"], "not_found");
+        assert_eq!(json["message"], "Finnes ikke: /bilder");
+    }
+
+    #[test]
+    fn test_classifies_service_errors() {
+        let io_error: Box<dyn std::error::Error> =
+            Box::new(io::Error::new(io::ErrorKind::PermissionDenied, "nektet"));
+        assert_eq!(AppError::from(io_error).code(), "permission_denied");
+
+        let message: Box<dyn std::error::Error> = "noe gikk galt".into();
+        let error = AppError::from(message);
+        assert_eq!(error.code(), "other");
+        assert_eq!(error.to_string(), "noe gikk galt");
+    }
+}
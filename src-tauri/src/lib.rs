@@ -3,6 +3,7 @@
 //! Denne modulen eksponerer alle kommandoer og tjenester for Tauri-applikasjonen.
 
 pub mod commands;
+pub mod error;
 pub mod services;
 
 pub use commands::*;
@@ -2,6 +2,7 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod error;
 mod services;
 
 fn main() {
@@ -9,14 +10,54 @@ fn main() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
+        .manage(services::cancellation::CancellationToken::new())
+        .manage(services::scan_cache::ScanCache::new())
+        .manage(commands::folder::DuplicateStore::default())
         .invoke_handler(tauri::generate_handler![
             commands::folder::scan_folder,
+            commands::folder::count_images,
+            commands::folder::scan_folder_streaming,
+            commands::folder::folder_stats,
             commands::folder::find_duplicates,
+            commands::folder::get_duplicate_group_page,
+            commands::folder::export_duplicates,
+            commands::folder::find_duplicates_in_folder,
+            commands::folder::find_duplicates_across,
+            commands::folder::find_similar,
+            commands::folder::find_already_imported,
+            commands::folder::find_name_duplicates,
+            commands::folder::hash_images,
+            commands::folder::compare_images,
+            commands::folder::clear_hash_cache,
+            commands::folder::clear_thumbnail_cache,
+            commands::folder::select_keepers,
+            commands::folder::duplicate_waste,
+            commands::folder::group_bursts,
+            commands::folder::cancel_operation,
             commands::folder::get_thumbnail,
+            commands::folder::get_thumbnail_data_url,
+            commands::folder::get_blurhash,
+            commands::folder::get_thumbnails,
+            commands::folder::prewarm_thumbnails,
+            commands::folder::get_image_metadata,
+            commands::folder::find_problem_images,
+            commands::folder::audit_dates,
+            commands::folder::group_by_orientation,
             commands::folder::open_image,
+            commands::folder::open_images,
+            commands::folder::reveal_in_file_manager,
             commands::folder::sort_images_by_date,
+            commands::folder::summarize_deletion,
+            commands::folder::verify_manifest,
             commands::folder::delete_images,
-            commands::folder::move_images
+            commands::folder::restore_from_trash,
+            commands::folder::move_images,
+            commands::folder::merge_folders,
+            commands::folder::quarantine_duplicates,
+            commands::folder::rename_images,
+            commands::folder::backfill_exif_dates,
+            commands::folder::undo_operation,
+            commands::convert::convert_images
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,113 @@
+//! Liggende, stående og kvadratiske bilder, ut fra sideforholdet
+//!
+//! Dimensjonene leses fra filens header (`scanner::read_dimensions`), uten dekoding.
+//! Bilder som er lagret liggende men skal vises stående (EXIF-orientering 5-8) regnes
+//! etter hvordan de vises.
+
+use crate::services::{metadata, path_codec, scanner};
+use rayon::prelude::*;
+use serde::Serialize;
+
+/// Bilder med forhold mellom bredde og høyde innenfor 1 ± dette regnes som kvadratiske
+pub const SQUARE_TOLERANCE: f64 = 0.05;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Orientation {
+    Landscape,
+    Portrait,
+    Square,
+}
+
+impl Orientation {
+    /// Ut fra visningsdimensjonene; `None` hvis en av sidene er 0
+    pub fn from_dimensions(width: u32, height: u32) -> Option<Self> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let ratio = width as f64 / height as f64;
+        Some(if (ratio - 1.0).abs() <= SQUARE_TOLERANCE {
+            Orientation::Square
+        } else if ratio > 1.0 {
+            Orientation::Landscape
+        } else {
+            Orientation::Portrait
+        })
+    }
+
+    /// Mappenavn for `{orientation}` i mappemaler
+    pub fn folder_name(&self) -> &'static str {
+        match self {
+            Orientation::Landscape => "Liggende",
+            Orientation::Portrait => "Stående",
+            Orientation::Square => "Kvadratisk",
+        }
+    }
+}
+
+/// Retningen til bildet slik det vises, `None` hvis dimensjonene ikke kan leses
+pub fn read_orientation(path: &std::path::Path) -> Option<Orientation> {
+    let (width, height) = scanner::read_dimensions(path)?;
+    // Orientering 5-8 roterer bildet 90 grader
+    let (width, height) = match metadata::read_orientation(path) {
+        Some(5..=8) => (height, width),
+        _ => (width, height),
+    };
+    Orientation::from_dimensions(width, height)
+}
+
+/// Resultat fra `group_by_orientation`, i samme rekkefølge som stiene ble gitt
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OrientationGroups {
+    pub landscape: Vec<String>,
+    pub portrait: Vec<String>,
+    pub square: Vec<String>,
+    /// Filer der dimensjonene ikke kunne leses (ukjent format, ødelagt eller borte)
+    pub unreadable: Vec<String>,
+}
+
+/// Deler bildene i liggende, stående og kvadratiske. Leser kun filheader og EXIF.
+pub fn group_by_orientation(paths: &[String]) -> OrientationGroups {
+    let orientations: Vec<Option<Orientation>> =
+        paths.par_iter().map(|path| read_orientation(&path_codec::decode(path))).collect();
+
+    let mut groups = OrientationGroups::default();
+    for (path, orientation) in paths.iter().zip(orientations) {
+        let group = match orientation {
+            Some(Orientation::Landscape) => &mut groups.landscape,
+            Some(Orientation::Portrait) => &mut groups.portrait,
+            Some(Orientation::Square) => &mut groups.square,
+            None => &mut groups.unreadable,
+        };
+        group.push(path.clone());
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_group_by_orientation() {
+        let dir = tempdir().unwrap();
+        let mut paths = Vec::new();
+        for (name, width, height) in [("bred.png", 300, 200), ("hoy.png", 200, 300), ("nesten_kvadrat.png", 204, 200)] {
+            let path = dir.path().join(name);
+            image::RgbImage::new(width, height).save(&path).unwrap();
+            paths.push(path.to_string_lossy().to_string());
+        }
+        let broken = dir.path().join("odelagt.jpg");
+        std::fs::write(&broken, b"ikke et bilde").unwrap();
+        paths.push(broken.to_string_lossy().to_string());
+
+        let groups = group_by_orientation(&paths);
+        assert_eq!(groups.landscape, vec![paths[0].clone()]);
+        assert_eq!(groups.portrait, vec![paths[1].clone()]);
+        assert_eq!(groups.square, vec![paths[2].clone()]);
+        assert_eq!(groups.unreadable, vec![paths[3].clone()]);
+        assert_eq!(Orientation::from_dimensions(0, 10), None);
+    }
+}
@@ -1,13 +1,26 @@
+//! Persistent cache for perceptuelle hasher
+//!
+//! Lagres som JSON slik at en ny skanning av samme mappe slipper å dekode bildene på nytt.
+//! En oppføring er gyldig så lenge filens mtime og størrelse er uendret.
+
+use crate::services::hashing::{HashSettings, ResizeFilter, DEFAULT_HASH_SIZE};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// Filnavn for cachen i cache-mappen
+const CACHE_FILE_NAME: &str = "hash_cache.json";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CachedHash {
     pub hash: String,
     pub mtime: u64,
+    #[serde(default)]
+    pub size_bytes: u64,
+    #[serde(default)]
+    pub hash_type: String,
 }
 
 pub struct HashCache {
@@ -17,7 +30,7 @@ pub struct HashCache {
 
 impl HashCache {
     pub fn new(cache_dir: &Path) -> Self {
-        let file_path = cache_dir.join("hash_cache.json");
+        let file_path = cache_dir.join(CACHE_FILE_NAME);
         let entries = if file_path.exists() {
             Self::load_cache(&file_path).unwrap_or_else(|e| {
                 println!("Kunne ikke laste cache: {}", e);
@@ -43,31 +56,153 @@ impl HashCache {
         if let Some(parent) = self.file_path.parent() {
             fs::create_dir_all(parent)?;
         }
+        // Skriv til en midlertidig fil og bytt inn atomisk, slik at et avbrudd
+        // midt i skrivingen aldri etterlater en halvskrevet cache
         let content = serde_json::to_string(&self.entries)?;
-        fs::write(&self.file_path, content)?;
+        let tmp_path = self.file_path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &self.file_path)?;
         Ok(())
     }
 
-    pub fn get(&self, path: &str, current_mtime: SystemTime) -> Option<String> {
-        if let Some(entry) = self.entries.get(path) {
-            if let Ok(mtime_secs) = current_mtime.duration_since(UNIX_EPOCH) {
-                if entry.mtime == mtime_secs.as_secs() {
-                    return Some(entry.hash.clone());
-                }
-            }
+    /// Sletter cache-filen i gitt mappe. Returnerer `true` hvis en fil ble slettet.
+    pub fn clear(cache_dir: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+        let file_path = cache_dir.join(CACHE_FILE_NAME);
+        if !file_path.exists() {
+            return Ok(false);
+        }
+        fs::remove_file(file_path)?;
+        Ok(true)
+    }
+
+    /// Hash-innstillingene slik de lagres, f.eks. "difference_triangle" eller "difference_16_triangle"
+    /// Standardstørrelsen og `Nearest` (eneste filter før det ble valgbart) har ingen suffiks,
+    /// så eldre cacher fortsatt er gyldige for de innstillingene.
+    fn mode(settings: &HashSettings) -> String {
+        let mut mode = settings.hash_type.as_str().to_string();
+        if settings.hash_size != DEFAULT_HASH_SIZE {
+            mode = format!("{}_{}", mode, settings.hash_size);
+        }
+        if settings.filter != ResizeFilter::Nearest {
+            mode = format!("{}_{}", mode, settings.filter.as_str());
+        }
+        mode
+    }
+
+    /// Nøkkel per (absolutt sti, innstillinger), slik at ulike moduser ikke overskriver hverandre
+    fn cache_key(path: &str, settings: &HashSettings) -> String {
+        let absolute = std::path::absolute(path)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| path.to_string());
+        format!("{}|{}", Self::mode(settings), absolute)
+    }
+
+    /// Henter cachet hash hvis filens mtime og størrelse er uendret
+    pub fn get(
+        &self,
+        path: &str,
+        current_mtime: SystemTime,
+        size_bytes: u64,
+        settings: &HashSettings,
+    ) -> Option<String> {
+        let entry = self.entries.get(&Self::cache_key(path, settings))?;
+        let mtime_secs = current_mtime.duration_since(UNIX_EPOCH).ok()?.as_secs();
+
+        if entry.mtime == mtime_secs && entry.size_bytes == size_bytes {
+            Some(entry.hash.clone())
+        } else {
+            None
         }
-        None
     }
 
-    pub fn insert(&mut self, path: String, mtime: SystemTime, hash: String) {
+    pub fn insert(
+        &mut self,
+        path: &str,
+        mtime: SystemTime,
+        size_bytes: u64,
+        settings: &HashSettings,
+        hash: String,
+    ) {
         if let Ok(mtime_secs) = mtime.duration_since(UNIX_EPOCH) {
             self.entries.insert(
-                path,
+                Self::cache_key(path, settings),
                 CachedHash {
                     hash,
                     mtime: mtime_secs.as_secs(),
+                    size_bytes,
+                    hash_type: Self::mode(settings),
                 },
             );
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::hashing::HashType;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    fn settings(hash_type: HashType, hash_size: u32) -> HashSettings {
+        HashSettings { hash_type, hash_size, filter: ResizeFilter::default() }
+    }
+
+    #[test]
+    fn test_cache_roundtrip_through_disk() {
+        let dir = tempdir().unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let mut cache = HashCache::new(dir.path());
+        cache.insert("/bilder/a.jpg", mtime, 100, &settings(HashType::Difference, DEFAULT_HASH_SIZE), "abc".to_string());
+        cache.save().unwrap();
+
+        let reloaded = HashCache::new(dir.path());
+        assert_eq!(
+            reloaded.get("/bilder/a.jpg", mtime, 100, &settings(HashType::Difference, DEFAULT_HASH_SIZE)),
+            Some("abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_cache_invalidated_by_mtime_or_size() {
+        let dir = tempdir().unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let later = mtime + Duration::from_secs(60);
+
+        let mut cache = HashCache::new(dir.path());
+        cache.insert("/bilder/a.jpg", mtime, 100, &settings(HashType::Difference, DEFAULT_HASH_SIZE), "abc".to_string());
+
+        assert!(cache.get("/bilder/a.jpg", later, 100, &settings(HashType::Difference, DEFAULT_HASH_SIZE)).is_none());
+        assert!(cache.get("/bilder/a.jpg", mtime, 101, &settings(HashType::Difference, DEFAULT_HASH_SIZE)).is_none());
+    }
+
+    #[test]
+    fn test_cache_separates_hash_types() {
+        let dir = tempdir().unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let mut cache = HashCache::new(dir.path());
+        cache.insert("/bilder/a.jpg", mtime, 100, &settings(HashType::Difference, DEFAULT_HASH_SIZE), "dhash".to_string());
+
+        assert!(cache.get("/bilder/a.jpg", mtime, 100, &settings(HashType::Average, DEFAULT_HASH_SIZE)).is_none());
+        assert!(cache.get("/bilder/a.jpg", mtime, 100, &settings(HashType::Difference, 16)).is_none());
+        let nearest = HashSettings { filter: ResizeFilter::Nearest, ..settings(HashType::Difference, DEFAULT_HASH_SIZE) };
+        assert!(cache.get("/bilder/a.jpg", mtime, 100, &nearest).is_none());
+        assert_eq!(HashCache::mode(&nearest), "difference");
+    }
+
+    #[test]
+    fn test_clear_cache() {
+        let dir = tempdir().unwrap();
+        let mut cache = HashCache::new(dir.path());
+        cache.insert("/bilder/a.jpg", SystemTime::now(), 1, &settings(HashType::Difference, DEFAULT_HASH_SIZE), "abc".to_string());
+        cache.save().unwrap();
+
+        assert!(HashCache::clear(dir.path()).unwrap());
+        assert!(!HashCache::clear(dir.path()).unwrap());
+        assert!(HashCache::new(dir.path())
+            .get("/bilder/a.jpg", SystemTime::now(), 1, &settings(HashType::Difference, DEFAULT_HASH_SIZE))
+            .is_none());
+    }
+}
@@ -0,0 +1,312 @@
+//! Konvertering av bilder mellom formater
+//!
+//! Dekoder med `image`-craten og koder på nytt i valgt format. EXIF kopieres over
+//! når både kilde og mål er JPEG; ellers roteres pikslene etter EXIF-orienteringen
+//! slik at bildet fortsatt vises riktig vei. Filens mtime settes alltid til
+//! opprettelsesdatoen, så datosortering gir samme resultat for konverterte filer.
+
+use crate::services::sorter::{unique_destination, OperationResult};
+use crate::services::jpeg::{self, ChromaSubsampling};
+use crate::services::{heic, metadata, path_codec, raw, thumbnail};
+use image::{DynamicImage, ImageOutputFormat};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Standard JPEG-kvalitet ved konvertering
+pub const DEFAULT_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetFormat {
+    Jpeg,
+    Png,
+    Bmp,
+    Gif,
+}
+
+impl TargetFormat {
+    /// Tolker målformat fra frontend: "jpeg"/"jpg", "png", "bmp" eller "gif"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().trim_start_matches('.').to_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(TargetFormat::Jpeg),
+            "png" => Some(TargetFormat::Png),
+            "bmp" => Some(TargetFormat::Bmp),
+            "gif" => Some(TargetFormat::Gif),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TargetFormat::Jpeg => "jpg",
+            TargetFormat::Png => "png",
+            TargetFormat::Bmp => "bmp",
+            TargetFormat::Gif => "gif",
+        }
+    }
+
+    /// Koder bildet; JPEG går via `jpeg::encode` for å kunne velge subsampling
+    fn encode(&self, img: &DynamicImage, quality: u8, subsampling: ChromaSubsampling) -> Result<Vec<u8>, String> {
+        let output_format = match self {
+            TargetFormat::Jpeg => return jpeg::encode(img, quality, subsampling),
+            TargetFormat::Png => ImageOutputFormat::Png,
+            TargetFormat::Bmp => ImageOutputFormat::Bmp,
+            TargetFormat::Gif => ImageOutputFormat::Gif,
+        };
+        let mut encoded = Vec::new();
+        img.write_to(&mut encoded, output_format).map_err(|e| e.to_string())?;
+        Ok(encoded)
+    }
+}
+
+/// Konverterer bilder til `format`
+/// Uten `target_dir` legges den konverterte filen ved siden av originalen.
+/// Originalene røres aldri; navnekollisjoner løses med `_1`, `_2` osv.
+pub fn convert_images(
+    paths: Vec<String>,
+    format: TargetFormat,
+    quality: u8,
+    subsampling: ChromaSubsampling,
+    target_dir: Option<&Path>,
+) -> OperationResult {
+    let mut result = OperationResult::new();
+    result.processed = paths.len();
+
+    if let Some(dir) = target_dir {
+        if !dir.is_dir() {
+            result.add_error(format!("Målmappen finnes ikke: {}", dir.display()));
+            return result;
+        }
+    }
+
+    let mut reserved = HashSet::new();
+
+    for path_str in paths {
+        let source = path_codec::decode(&path_str);
+        let source = source.as_path();
+        let dest_dir = match target_dir.or_else(|| source.parent()) {
+            Some(dir) => dir,
+            None => {
+                result.add_error(format!("Ugyldig sti: {}", path_str));
+                continue;
+            }
+        };
+
+        match convert_one(source, dest_dir, format, quality, subsampling, &reserved) {
+            Ok(dest) => {
+                reserved.insert(dest);
+                result.add_success();
+            }
+            Err(e) => result.add_error(format!("Kunne ikke konvertere {}: {}", path_str, e)),
+        }
+    }
+
+    result
+}
+
+fn convert_one(
+    source: &Path,
+    dest_dir: &Path,
+    format: TargetFormat,
+    quality: u8,
+    subsampling: ChromaSubsampling,
+    reserved: &HashSet<PathBuf>,
+) -> Result<PathBuf, String> {
+    if !source.exists() {
+        return Err("filen finnes ikke".to_string());
+    }
+
+    let img = decode(source)?;
+
+    // EXIF kan bare bæres over JPEG -> JPEG. Ellers "baker" vi inn orienteringen.
+    let exif_segment = if format == TargetFormat::Jpeg {
+        fs::read(source).ok().and_then(|bytes| jpeg_exif_segment(&bytes))
+    } else {
+        None
+    };
+    let orientation = if heic::is_oriented_on_decode(source) {
+        None
+    } else {
+        metadata::read_orientation(source)
+    };
+    let img = match (exif_segment.is_some(), orientation) {
+        (false, Some(orientation)) => thumbnail::apply_orientation(img, orientation),
+        _ => img,
+    };
+
+    let mut encoded = format.encode(&img, quality, subsampling)?;
+    if let Some(segment) = exif_segment {
+        let pos = exif_insert_position(&encoded);
+        encoded.splice(pos..pos, segment);
+    }
+
+    let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = format!("{}.{}", stem, format.extension());
+    let dest = unique_destination(dest_dir, Path::new(&file_name), reserved);
+    fs::write(&dest, encoded).map_err(|e| e.to_string())?;
+
+    // Behold opprettelsesdatoen som mtime
+    if let Some(found) = metadata::read_creation_date(source) {
+        let _ = fs::File::options()
+            .write(true)
+            .open(&dest)
+            .and_then(|file| file.set_modified(SystemTime::from(found.date)));
+    }
+
+    Ok(dest)
+}
+
+fn decode(source: &Path) -> Result<DynamicImage, String> {
+    if raw::is_raw_file(source) {
+        return raw::extract_preview(source).map_err(|e| e.to_string());
+    }
+    if heic::is_heic_file(source) {
+        return heic::decode(source).map_err(|e| e.to_string());
+    }
+    image::io::Reader::open(source)
+        .map_err(|e| e.to_string())?
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?
+        .decode()
+        .map_err(|e| match e {
+            image::ImageError::Unsupported(_) => "formatet støttes ikke".to_string(),
+            other => other.to_string(),
+        })
+}
+
+/// Hvor APP1/EXIF skal settes inn i en nykodet JPEG
+/// Rett etter SOI, eller etter APP0 (JFIF) hvis koderen skrev en: JFIF krever at APP0 kommer først.
+fn exif_insert_position(bytes: &[u8]) -> usize {
+    match bytes.get(2..6) {
+        Some([0xFF, 0xE0, high, low]) => (4 + u16::from_be_bytes([*high, *low]) as usize).min(bytes.len()),
+        _ => 2,
+    }
+}
+
+/// Finner APP1/EXIF-segmentet (inkl. markør og lengde) i en JPEG-fil
+fn jpeg_exif_segment(bytes: &[u8]) -> Option<Vec<u8>> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+        let marker = bytes[pos + 1];
+        // Start of scan / end of image: ingen flere metadata-segmenter
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let end = pos + 2 + length;
+        let segment = bytes.get(pos..end)?;
+        if marker == 0xE1 && segment.get(4..10) == Some(b"Exif\0\0".as_slice()) {
+            return Some(segment.to_vec());
+        }
+        pos = end;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbImage};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_convert_png_to_jpeg() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("bilde.png");
+        RgbImage::new(30, 20).save(&source).unwrap();
+
+        let result = convert_images(
+            vec![source.to_string_lossy().to_string()],
+            TargetFormat::Jpeg,
+            DEFAULT_QUALITY,
+            ChromaSubsampling::default(),
+            None,
+        );
+
+        assert_eq!(result.success, 1);
+        let converted = dir.path().join("bilde.jpg");
+        assert_eq!(image::guess_format(&fs::read(&converted).unwrap()).unwrap(), image::ImageFormat::Jpeg);
+        assert_eq!(image::open(&converted).unwrap().dimensions(), (30, 20));
+        assert!(source.exists(), "Originalen skal ikke røres");
+    }
+
+    #[test]
+    fn test_convert_does_not_overwrite_existing() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("bilde.jpg");
+        RgbImage::new(8, 8).save(&source).unwrap();
+
+        let result = convert_images(
+            vec![source.to_string_lossy().to_string()],
+            TargetFormat::Jpeg,
+            DEFAULT_QUALITY,
+            ChromaSubsampling::default(),
+            None,
+        );
+
+        assert_eq!(result.success, 1);
+        assert!(dir.path().join("bilde_1.jpg").exists());
+    }
+
+    #[test]
+    fn test_convert_unsupported_source() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("bilde.heic");
+        fs::write(&source, b"ikke et bilde vi kan dekode").unwrap();
+
+        let result = convert_images(
+            vec![source.to_string_lossy().to_string(), "/finnes/ikke.png".to_string()],
+            TargetFormat::Png,
+            DEFAULT_QUALITY,
+            ChromaSubsampling::default(),
+            Some(dir.path()),
+        );
+
+        assert_eq!(result.success, 0);
+        assert_eq!(result.errors, 2);
+    }
+
+    #[test]
+    fn test_jpeg_exif_segment() {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // APP0 (JFIF) som skal hoppes over
+        jpeg.extend([0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00]);
+        let app1 = [0xFF, 0xE1, 0x00, 0x0A, b'E', b'x', b'i', b'f', 0, 0, b'I', b'I'];
+        jpeg.extend(app1);
+        jpeg.extend([0xFF, 0xDA, 0x00, 0x02]);
+
+        assert_eq!(jpeg_exif_segment(&jpeg), Some(app1.to_vec()));
+        assert_eq!(jpeg_exif_segment(b"not a jpeg"), None);
+    }
+
+    #[test]
+    fn test_exif_insert_position() {
+        let mut jfif = vec![0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00];
+        jfif.extend([0xFF, 0xDB, 0x00, 0x02]);
+        assert_eq!(exif_insert_position(&jfif), 8);
+        assert_eq!(exif_insert_position(&[0xFF, 0xD8, 0xFF, 0xDB, 0x00, 0x02]), 2);
+
+        // Nykodet JPEG fra koderen: EXIF havner etter JFIF og kan leses tilbake
+        let img = DynamicImage::ImageRgb8(RgbImage::new(8, 8));
+        let mut encoded = TargetFormat::Jpeg.encode(&img, DEFAULT_QUALITY, ChromaSubsampling::default()).unwrap();
+        let app1 = vec![0xFF, 0xE1, 0x00, 0x0A, b'E', b'x', b'i', b'f', 0, 0, b'I', b'I'];
+        assert_eq!(encoded[2..4], [0xFF, 0xE0]);
+        let pos = exif_insert_position(&encoded);
+        assert!(pos > 2);
+        encoded.splice(pos..pos, app1.clone());
+        assert_eq!(encoded[2..4], [0xFF, 0xE0]);
+        assert_eq!(jpeg_exif_segment(&encoded), Some(app1));
+    }
+
+    #[test]
+    fn test_target_format_from_name() {
+        assert_eq!(TargetFormat::from_name("JPG"), Some(TargetFormat::Jpeg));
+        assert_eq!(TargetFormat::from_name(".png"), Some(TargetFormat::Png));
+        assert_eq!(TargetFormat::from_name("heic"), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Finner bilder med åpenbart feil dato før en stor sortering
+//!
+//! Kameraer med tom klokke skriver ofte epoke-datoer (1970, 1980), og feil tidssone eller
+//! årstall gir datoer i fremtiden. Slike bilder havner i mapper som `1970/` eller `2099/`.
+
+use crate::services::metadata::{self, DateSource, FallbackPolicy};
+use crate::services::path_codec;
+use crate::services::scanner::MediaType;
+use chrono::{DateTime, Datelike, Duration, Local};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+
+/// Datoer før dette året regnes som mistenkelig gamle (`DateIssueKind::FarPast`)
+pub const DEFAULT_MIN_YEAR: i32 = 1990;
+
+/// Epoker som kameraer og filsystemer faller tilbake til: Unix, QuickTime og FAT/DOS
+const EPOCH_DATES: [(i32, u32, u32); 3] = [(1970, 1, 1), (1904, 1, 1), (1980, 1, 1)];
+
+/// Hva som er galt med datoen
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateIssueKind {
+    /// Ingen dato i EXIF, sidecar eller video; bildet havner i mappen for filer uten dato
+    NoExif,
+    /// Nøyaktig en epoke-dato (1970-01-01 osv.), typisk fra et kamera med nullstilt klokke
+    Epoch,
+    /// Mer enn et døgn frem i tid
+    Future,
+    /// Eldre enn `min_year`
+    FarPast,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DateIssue {
+    pub path: String,
+    pub issue: DateIssueKind,
+    /// Datoen som ble funnet (RFC 3339)
+    pub date: Option<String>,
+    pub date_source: Option<DateSource>,
+}
+
+/// Sjekker datoene parallelt og returnerer bildene med problemer, i samme rekkefølge som `paths`
+/// Kun lesing; videoer og filer som ikke finnes hoppes over.
+pub fn audit_dates(paths: &[String], min_year: i32) -> Vec<DateIssue> {
+    let now = Local::now();
+    paths
+        .par_iter()
+        .filter(|path| {
+            let ext = Path::new(path).extension().unwrap_or_default().to_string_lossy();
+            MediaType::from_extension(&ext) == MediaType::Image
+        })
+        .filter_map(|path| {
+            let file = path_codec::decode(path);
+            if !file.is_file() {
+                return None;
+            }
+            let found = metadata::read_creation_date_with_policy(&file, FallbackPolicy::None);
+            let issue = match found {
+                None => DateIssueKind::NoExif,
+                Some(found) => classify(found.date, now, min_year)?,
+            };
+            Some(DateIssue {
+                path: path.clone(),
+                issue,
+                date: found.map(|found| found.date.to_rfc3339()),
+                date_source: found.map(|found| found.source),
+            })
+        })
+        .collect()
+}
+
+fn classify(date: DateTime<Local>, now: DateTime<Local>, min_year: i32) -> Option<DateIssueKind> {
+    if EPOCH_DATES.contains(&(date.year(), date.month(), date.day())) {
+        Some(DateIssueKind::Epoch)
+    } else if date > now + Duration::days(1) {
+        Some(DateIssueKind::Future)
+    } else if date.year() < min_year {
+        Some(DateIssueKind::FarPast)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_audit_dates() {
+        let dir = tempdir().unwrap();
+        let mut paths = Vec::new();
+        let dates = [
+            ("ok.jpg", Some(Local.with_ymd_and_hms(2021, 6, 1, 12, 0, 0).unwrap())),
+            ("epoch.jpg", Some(Local.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap())),
+            ("future.jpg", Some(Local.with_ymd_and_hms(2099, 1, 1, 12, 0, 0).unwrap())),
+            ("old.jpg", Some(Local.with_ymd_and_hms(1985, 7, 1, 12, 0, 0).unwrap())),
+            ("none.jpg", None),
+        ];
+        for (name, date) in dates {
+            let path = dir.path().join(name);
+            image::RgbImage::new(8, 8).save(&path).unwrap();
+            if let Some(date) = date {
+                metadata::write_exif_date(&path, date).unwrap();
+            }
+            paths.push(path.to_string_lossy().to_string());
+        }
+        paths.push(dir.path().join("clip.mp4").to_string_lossy().to_string());
+
+        let issues = audit_dates(&paths, DEFAULT_MIN_YEAR);
+        let kinds: Vec<_> = issues.iter().map(|issue| issue.issue).collect();
+        assert_eq!(
+            kinds,
+            vec![DateIssueKind::Epoch, DateIssueKind::Future, DateIssueKind::FarPast, DateIssueKind::NoExif]
+        );
+        assert_eq!(issues[0].path, paths[1]);
+        assert_eq!(issues[0].date_source, Some(DateSource::ExifOriginal));
+        assert_eq!(issues[3].date, None);
+
+        // Med lavere grense er 1985 greit
+        assert_eq!(audit_dates(&paths[3..4], 1980), Vec::new());
+    }
+}
@@ -0,0 +1,1317 @@
+//! Gruppering av like bilder
+//!
+//! Visuelle duplikater: BK-Tree over perceptuelle hasher for å finne naboer innenfor
+//! en terskel, og Union-Find for å slå sammen treffene til transitive grupper.
+//! Eksakte duplikater: størrelse som forfilter, deretter SHA-256.
+
+use crate::services::cancellation::CancellationToken;
+use crate::services::hashing::{self, ComparableHash, PerceptualMetric};
+use crate::services::{metadata, path_codec, scanner};
+use bk_tree::BKTree;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+
+/// Resultat av et eksakt duplikatsøk
+#[derive(Debug, Default)]
+pub struct ExactDuplicates {
+    /// Grupper av (sti, størrelse) med byte-identisk innhold
+    pub groups: Vec<Vec<(String, u64)>>,
+    /// Antall filer som ikke kunne leses
+    pub errors: usize,
+    /// `true` hvis søket ble avbrutt før alle kandidater var hashet
+    pub cancelled: bool,
+}
+
+/// Hvilken kopi som skal beholdes i en duplikatgruppe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeepStrategy {
+    Largest,
+    Smallest,
+    Newest,
+    Oldest,
+    HighestResolution,
+}
+
+impl KeepStrategy {
+    /// Tolker strategi fra frontend: "largest", "smallest", "newest", "oldest" eller "highest_resolution"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "largest" => Some(KeepStrategy::Largest),
+            "smallest" => Some(KeepStrategy::Smallest),
+            "newest" => Some(KeepStrategy::Newest),
+            "oldest" => Some(KeepStrategy::Oldest),
+            "highest_resolution" => Some(KeepStrategy::HighestResolution),
+            _ => None,
+        }
+    }
+
+    /// Poeng for en fil der høyere er bedre. `None` hvis verdien ikke kan leses.
+    fn score(&self, path: &Path) -> Option<i64> {
+        match self {
+            KeepStrategy::Largest => Some(std::fs::metadata(path).ok()?.len() as i64),
+            KeepStrategy::Smallest => Some(-(std::fs::metadata(path).ok()?.len() as i64)),
+            KeepStrategy::Newest => Some(metadata::read_creation_date(path)?.date.timestamp()),
+            KeepStrategy::Oldest => Some(-metadata::read_creation_date(path)?.date.timestamp()),
+            KeepStrategy::HighestResolution => {
+                let (width, height) = image::image_dimensions(path).ok()?;
+                Some(width as i64 * height as i64)
+            }
+        }
+    }
+}
+
+/// Forslag til hvilken fil som beholdes og hvilke som kan slettes
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct KeeperSuggestion {
+    pub keeper: String,
+    pub delete: Vec<String>,
+    /// Andre filer under en beskyttet mappe (`select_keeper_protected`), som også beholdes
+    pub protected: Vec<String>,
+}
+
+/// Velger hvilken fil som skal beholdes i en gruppe
+///
+/// Filer der verdien ikke kan leses rangeres sist. Like verdier avgjøres av stien
+/// (alfabetisk først vinner), slik at gjentatte kjøringer gir samme svar.
+pub fn select_keeper(paths: &[String], strategy: KeepStrategy) -> Option<KeeperSuggestion> {
+    select_keeper_protected(paths, strategy, &[]).ok().flatten()
+}
+
+/// Som `select_keeper`, men filer under `protected_prefixes` (se `protected_prefixes`)
+/// foreslås aldri slettet. Finnes det beskyttede filer i gruppen, beholdes den beste av
+/// dem og alle ubeskyttede foreslås slettet. Gir feil hvis hele gruppen er beskyttet.
+pub fn select_keeper_protected(
+    paths: &[String],
+    strategy: KeepStrategy,
+    protected_prefixes: &[PathBuf],
+) -> Result<Option<KeeperSuggestion>, String> {
+    let mut ranked: Vec<(Option<i64>, &String)> = paths
+        .iter()
+        .map(|path| (strategy.score(&path_codec::decode(path)), path))
+        .collect();
+    // `Option` sorterer `None` først, så vi sorterer synkende på poeng og stigende på sti
+    ranked.sort_by(|(score_a, path_a), (score_b, path_b)| score_b.cmp(score_a).then_with(|| path_a.cmp(path_b)));
+
+    let (protected, unprotected): (Vec<String>, Vec<String>) = ranked
+        .into_iter()
+        .map(|(_, path)| path.clone())
+        .partition(|path| is_protected(path, protected_prefixes));
+    if !protected.is_empty() && unprotected.is_empty() {
+        return Err(format!("Alle filene i gruppen er beskyttet: {}", protected.join(", ")));
+    }
+
+    let suggestion = if protected.is_empty() {
+        let mut ranked = unprotected.into_iter();
+        ranked.next().map(|keeper| KeeperSuggestion { keeper, delete: ranked.collect(), protected: Vec::new() })
+    } else {
+        let mut protected = protected.into_iter();
+        protected.next().map(|keeper| KeeperSuggestion { keeper, delete: unprotected, protected: protected.collect() })
+    };
+    Ok(suggestion)
+}
+
+/// En fil i en duplikatgruppe fra frontend, med størrelsen fra skanningen
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SizedPath {
+    pub path: String,
+    pub size_bytes: u64,
+}
+
+/// Hvor mye plass som frigjøres ved å slette alt unntatt den beholdte filen i hver gruppe
+#[derive(Serialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateWaste {
+    pub reclaimable_bytes: u64,
+    pub files_removable: usize,
+}
+
+/// Summerer størrelsen på filene `select_keeper_protected` foreslår slettet
+///
+/// Hvilken fil som beholdes avhenger av strategien, så med f.eks. `Smallest` blir mer frigjort
+/// enn med `Largest`. Beskyttede filer telles aldri med. Gir feil hvis en gruppe er helt beskyttet.
+pub fn duplicate_waste(
+    groups: &[Vec<SizedPath>],
+    strategy: KeepStrategy,
+    protected_prefixes: &[PathBuf],
+) -> Result<DuplicateWaste, String> {
+    let suggestions = groups
+        .par_iter()
+        .map(|group| {
+            let paths: Vec<String> = group.iter().map(|file| file.path.clone()).collect();
+            Ok(select_keeper_protected(&paths, strategy, protected_prefixes)?.map(|suggestion| (group, suggestion)))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let mut waste = DuplicateWaste::default();
+    for (group, suggestion) in suggestions.into_iter().flatten() {
+        let sizes: HashMap<&str, u64> = group.iter().map(|file| (file.path.as_str(), file.size_bytes)).collect();
+        waste.files_removable += suggestion.delete.len();
+        waste.reclaimable_bytes += suggestion.delete.iter().map(|path| sizes[path.as_str()]).sum::<u64>();
+    }
+    Ok(waste)
+}
+
+/// Beskyttede mapper fra frontend, som kanoniske stier der de finnes. Tomme verdier ignoreres.
+pub fn protected_prefixes(prefixes: &[String]) -> Vec<PathBuf> {
+    prefixes
+        .iter()
+        .filter(|prefix| !prefix.trim().is_empty())
+        .map(|prefix| {
+            let prefix = path_codec::decode(prefix);
+            prefix.canonicalize().unwrap_or(prefix)
+        })
+        .collect()
+}
+
+/// Om filen ligger under en av de beskyttede mappene fra `protected_prefixes`
+pub fn is_protected(path: &str, protected_prefixes: &[PathBuf]) -> bool {
+    if protected_prefixes.is_empty() {
+        return false;
+    }
+    let path = path_codec::decode(path);
+    let path = path.canonicalize().unwrap_or(path);
+    protected_prefixes.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// Grupperer bilder tatt i serie (burst) ut fra opprettelsestidspunkt
+///
+/// Bildene sorteres kronologisk, og et nytt bilde havner i samme gruppe som forrige
+/// hvis avstanden er høyst `max_gap_ms` millisekunder. Gruppene returneres i
+/// kronologisk rekkefølge; filer uten lesbar dato blir egne grupper til slutt.
+pub fn group_bursts(paths: &[String], max_gap_ms: i64) -> Vec<Vec<String>> {
+    let dated: Vec<(String, Option<i64>)> = paths
+        .par_iter()
+        .map(|path| {
+            let timestamp = metadata::read_creation_date(&path_codec::decode(path)).map(|found| found.date.timestamp_millis());
+            (path.clone(), timestamp)
+        })
+        .collect();
+    group_by_time(dated, max_gap_ms)
+}
+
+fn group_by_time(mut items: Vec<(String, Option<i64>)>, max_gap_ms: i64) -> Vec<Vec<String>> {
+    // `None` sorteres først, så udaterte filer skilles ut før kjedingen
+    items.sort_by(|(path_a, time_a), (path_b, time_b)| time_a.cmp(time_b).then_with(|| path_a.cmp(path_b)));
+    let split = items.partition_point(|(_, time)| time.is_none());
+    let undated: Vec<Vec<String>> = items.drain(..split).map(|(path, _)| vec![path]).collect();
+
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut previous: Option<i64> = None;
+    for (path, time) in items {
+        let time = time.unwrap_or_default();
+        match (previous, groups.last_mut()) {
+            (Some(prev), Some(group)) if time - prev <= max_gap_ms => group.push(path),
+            _ => groups.push(vec![path]),
+        }
+        previous = Some(time);
+    }
+
+    groups.extend(undated);
+    groups
+}
+
+/// Union-Find (disjoint set) med stikomprimering og union by rank
+struct UnionFind {
+    parent: Vec<usize>,
+    rank: Vec<u8>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+            rank: vec![0; size],
+        }
+    }
+
+    /// Legger til et nytt sett med ett element og returnerer indeksen
+    fn push(&mut self) -> usize {
+        let idx = self.parent.len();
+        self.parent.push(idx);
+        self.rank.push(0);
+        idx
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        let mut root = x;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        // Stikomprimering
+        let mut current = x;
+        while self.parent[current] != root {
+            let next = self.parent[current];
+            self.parent[current] = root;
+            current = next;
+        }
+        root
+    }
+
+    /// Alle sett, sortert etter laveste indeks
+    fn groups(&mut self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut root_to_group: HashMap<usize, usize> = HashMap::new();
+
+        for idx in 0..self.parent.len() {
+            let root = self.find(idx);
+            let group_idx = *root_to_group.entry(root).or_insert_with(|| {
+                groups.push(Vec::new());
+                groups.len() - 1
+            });
+            groups[group_idx].push(idx);
+        }
+
+        groups
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a == root_b {
+            return;
+        }
+        match self.rank[root_a].cmp(&self.rank[root_b]) {
+            std::cmp::Ordering::Less => self.parent[root_a] = root_b,
+            std::cmp::Ordering::Greater => self.parent[root_b] = root_a,
+            std::cmp::Ordering::Equal => {
+                self.parent[root_b] = root_a;
+                self.rank[root_a] += 1;
+            }
+        }
+    }
+}
+
+/// Grupperer hasher som ligger innenfor `threshold` Hamming-distanse av hverandre
+///
+/// Matcher er transitive: hvis A~B og B~C havner alle tre i samme gruppe, selv om
+/// A og C ligger lenger fra hverandre enn terskelen.
+/// Returnerer indekser inn i `hashes`, én `Vec` per gruppe (også grupper med ett element),
+/// sortert etter laveste indeks slik at resultatet er deterministisk.
+pub fn group_similar_hashes(hashes: &[ComparableHash], threshold: u32) -> Vec<Vec<usize>> {
+    // Identiske hasher lagres kun én gang i treet, så vi holder oversikt over alle indeksene
+    let mut hash_to_indices: HashMap<&ComparableHash, Vec<usize>> = HashMap::new();
+    let mut tree = BKTree::new(PerceptualMetric);
+
+    for (idx, hash) in hashes.iter().enumerate() {
+        let indices = hash_to_indices.entry(hash).or_default();
+        if indices.is_empty() {
+            tree.add(hash.clone());
+        }
+        indices.push(idx);
+    }
+
+    let mut union_find = UnionFind::new(hashes.len());
+
+    for indices in hash_to_indices.values() {
+        let first = indices[0];
+        // Identiske hasher er alltid i samme gruppe
+        for &idx in &indices[1..] {
+            union_find.union(first, idx);
+        }
+
+        for (_dist, found_hash) in tree.find(&hashes[first], threshold) {
+            if let Some(found_indices) = hash_to_indices.get(found_hash) {
+                union_find.union(first, found_indices[0]);
+            }
+        }
+    }
+
+    union_find.groups()
+}
+
+/// Som `group_similar_hashes`, men hvert bilde har flere hasher (f.eks. ulike utsnitt)
+///
+/// To bilder havner i samme gruppe hvis et hvilket som helst par av hashene deres er
+/// innenfor `threshold`. Returnerer indekser inn i `hash_sets`.
+pub fn group_similar_hash_sets(hash_sets: &[Vec<ComparableHash>], threshold: u32) -> Vec<Vec<usize>> {
+    let mut grouper = HashSetGrouper::new(threshold);
+    for hashes in hash_sets {
+        grouper.add(hashes.clone());
+    }
+    grouper.groups()
+}
+
+/// Grupperer hash-sett etter hvert som de blir beregnet, som `group_similar_hash_sets`
+///
+/// Hvert sett slås opp i BK-treet og legges inn med én gang, så duplikatsøket kan hashe
+/// i bolker uten å holde alle hash-strengene i minnet samtidig. Per bilde lagres kun
+/// hashene (én kopi i treet og én for likhetsscore) og én Union-Find-node: rundt 150 byte
+/// for en 8x8-hash.
+pub struct HashSetGrouper {
+    threshold: u32,
+    tree: BKTree<ComparableHash, PerceptualMetric>,
+    /// Første bilde med hver unike hash; identiske hasher legges bare inn i treet én gang
+    first_owner: HashMap<ComparableHash, usize>,
+    sets: Vec<Vec<ComparableHash>>,
+    union_find: UnionFind,
+}
+
+impl HashSetGrouper {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold,
+            tree: BKTree::new(PerceptualMetric),
+            first_owner: HashMap::new(),
+            sets: Vec::new(),
+            union_find: UnionFind::new(0),
+        }
+    }
+
+    /// Legger til hashene for ett bilde og returnerer indeksen det får i `groups`
+    pub fn add(&mut self, hashes: Vec<ComparableHash>) -> usize {
+        let owner = self.union_find.push();
+        for hash in &hashes {
+            // Naboene til en kjent hash er allerede koblet til den første eieren
+            if let Some(&other) = self.first_owner.get(hash) {
+                self.union_find.union(owner, other);
+                continue;
+            }
+            let neighbours: Vec<usize> = self
+                .tree
+                .find(hash, self.threshold)
+                .filter_map(|(_, found)| self.first_owner.get(found).copied())
+                .collect();
+            for other in neighbours {
+                self.union_find.union(owner, other);
+            }
+            self.tree.add(hash.clone());
+            self.first_owner.insert(hash.clone(), owner);
+        }
+        self.sets.push(hashes);
+        owner
+    }
+
+    /// Hashene for bildet med indeks `owner`
+    pub fn hashes(&self, owner: usize) -> &[ComparableHash] {
+        &self.sets[owner]
+    }
+
+    /// Alle grupper (også med ett bilde), sortert etter laveste indeks
+    pub fn groups(&mut self) -> Vec<Vec<usize>> {
+        self.union_find.groups()
+    }
+}
+
+/// BK-tre over hashene til et fast referansesett (f.eks. biblioteket), for oppslag uten gruppering
+///
+/// Brukes til å finne hvilke nye bilder som allerede finnes i referansen. Bilder i
+/// referansen kobles ikke sammen med hverandre.
+pub struct ReferenceIndex {
+    tree: BKTree<ComparableHash, PerceptualMetric>,
+    /// Første referansebilde med hver unike hash
+    owners: HashMap<ComparableHash, usize>,
+}
+
+impl ReferenceIndex {
+    /// Bygger indeksen; indeksene i `nearest` peker inn i `hash_sets`
+    pub fn new(hash_sets: &[Vec<ComparableHash>]) -> Self {
+        let mut tree = BKTree::new(PerceptualMetric);
+        let mut owners = HashMap::new();
+        for (idx, hashes) in hash_sets.iter().enumerate() {
+            for hash in hashes {
+                if !owners.contains_key(hash) {
+                    tree.add(hash.clone());
+                    owners.insert(hash.clone(), idx);
+                }
+            }
+        }
+        Self { tree, owners }
+    }
+
+    /// Nærmeste referansebilde innenfor `threshold` for et bilde med `hashes`, som
+    /// `(indeks, distanse)`. Lik distanse: laveste indeks.
+    pub fn nearest(&self, hashes: &[ComparableHash], threshold: u32) -> Option<(usize, u32)> {
+        hashes
+            .iter()
+            .flat_map(|hash| self.tree.find(hash, threshold))
+            .filter_map(|(distance, found)| Some((*self.owners.get(found)?, distance)))
+            .min_by_key(|&(idx, distance)| (distance, idx))
+    }
+}
+
+/// Slakk i rutenettet for histogrammer, mot avrundingsfeil i f32-gjennomsnittene
+const HISTOGRAM_GRID_SLACK: f32 = 1e-4;
+
+/// Grupperer bilder med fargehistogrammer innenfor `max_distance` (0.0-1.0) av hverandre
+///
+/// Histogramavstanden er ikke en heltallsmetrikk, så BK-tre kan ikke brukes. I stedet legges
+/// bildene i et rutenett etter gjennomsnittlig fargenivå per kanal med cellestørrelse
+/// `max_distance`, og bare bilder i samme eller nabo-celler sammenlignes. Forskjellen i
+/// gjennomsnitt er aldri større enn histogramavstanden, så ingen treff går tapt.
+/// Transitivt og deterministisk som `group_similar_hashes`.
+pub fn group_similar_histograms(histograms: &[hashing::ColorHistogram], max_distance: f32) -> Vec<Vec<usize>> {
+    let cell_size = max_distance.max(0.0) + HISTOGRAM_GRID_SLACK;
+    let cells: Vec<[i32; 3]> = histograms
+        .iter()
+        .map(|histogram| hashing::histogram_channel_means(histogram).map(|mean| (mean / cell_size).floor() as i32))
+        .collect();
+    let mut grid: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+    for (idx, cell) in cells.iter().enumerate() {
+        grid.entry(*cell).or_default().push(idx);
+    }
+
+    let pairs: Vec<(usize, usize)> = (0..histograms.len())
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let [x, y, z] = cells[i];
+            let neighbours = (-1..=1).flat_map(move |dx| {
+                (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [x + dx, y + dy, z + dz]))
+            });
+            neighbours
+                .filter_map(|cell| grid.get(&cell))
+                .flatten()
+                .copied()
+                .filter(move |&j| j > i && hashing::histogram_distance(&histograms[i], &histograms[j]) <= max_distance)
+                .map(move |j| (i, j))
+        })
+        .collect();
+
+    let mut union_find = UnionFind::new(histograms.len());
+    for (i, j) in pairs {
+        union_find.union(i, j);
+    }
+    union_find.groups()
+}
+
+/// Minste Hamming-distanse mellom to sett med hasher (ett sett per bilde)
+pub fn hash_set_distance(a: &[ComparableHash], b: &[ComparableHash]) -> u32 {
+    a.iter()
+        .flat_map(|x| b.iter().map(move |y| x.0.dist(&y.0)))
+        .min()
+        .unwrap_or(u32::MAX)
+}
+
+/// Hvor like to bestemte bilder er etter hver hash-algoritme
+/// Brukes til å forklare et treff og til å kalibrere `threshold` i `find_duplicates`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonReport {
+    /// Hamming-distanse (0-64) for dHash, pHash og aHash
+    pub difference_distance: u32,
+    pub perceptual_distance: u32,
+    pub average_distance: u32,
+    /// Byte-identiske filer (SHA-256)
+    pub exact_match: bool,
+    /// Bredde/høyde fra filheaderen, `None` hvis formatet ikke kan leses
+    pub dimensions_a: Option<(u32, u32)>,
+    pub dimensions_b: Option<(u32, u32)>,
+    pub same_dimensions: bool,
+}
+
+/// Sammenligner to bilder med alle perceptuelle hash-algoritmer og eksakt filhash
+pub fn compare_images(path_a: &Path, path_b: &Path) -> Result<ComparisonReport, Box<dyn std::error::Error>> {
+    let image_a = hashing::load_image(path_a)?;
+    let image_b = hashing::load_image(path_b)?;
+    let distance = |hash_type| -> Result<u32, Box<dyn std::error::Error>> {
+        Ok(hashing::compare_hashes(
+            &hashing::compute_perceptual_hash(&image_a, hash_type)?,
+            &hashing::compute_perceptual_hash(&image_b, hash_type)?,
+        ))
+    };
+
+    let dimensions_a = scanner::read_dimensions(path_a);
+    let dimensions_b = scanner::read_dimensions(path_b);
+
+    Ok(ComparisonReport {
+        difference_distance: distance(hashing::HashType::Difference)?,
+        perceptual_distance: distance(hashing::HashType::Perceptual)?,
+        average_distance: distance(hashing::HashType::Average)?,
+        exact_match: hashing::compute_exact_hash(path_a)? == hashing::compute_exact_hash(path_b)?,
+        dimensions_a,
+        dimensions_b,
+        same_dimensions: dimensions_a.is_some() && dimensions_a == dimensions_b,
+    })
+}
+
+/// Rangerer kandidater etter likhet med ett referansebilde
+///
+/// Returnerer `(indeks, distanse)` for kandidatene innenfor `threshold`, sortert med
+/// nærmeste først (lik distanse: laveste indeks først).
+pub fn rank_similar(
+    reference: &[ComparableHash],
+    candidates: &[Vec<ComparableHash>],
+    threshold: u32,
+) -> Vec<(usize, u32)> {
+    let mut matches: Vec<(usize, u32)> = candidates
+        .iter()
+        .enumerate()
+        .map(|(idx, hashes)| (idx, hash_set_distance(reference, hashes)))
+        .filter(|(_, distance)| *distance <= threshold)
+        .collect();
+    matches.sort_by_key(|&(idx, distance)| (distance, idx));
+    matches
+}
+
+/// Likhet i prosent ut fra Hamming-distanse: `(1 - dist/bits) * 100`
+/// For 8x8-hasher er `bits` 64.
+pub fn similarity_percent(distance: u32, bits: u32) -> f64 {
+    if bits == 0 {
+        return 0.0;
+    }
+    (1.0 - distance.min(bits) as f64 / bits as f64) * 100.0
+}
+
+/// Fjerner stier som peker på samme fil (samme sti to ganger, `..`, symlenker)
+///
+/// Ellers hashes filen flere ganger og blir et "duplikat" av seg selv. Stiene sammenlignes
+/// kanonisert, men første forekomst returneres slik kalleren sendte den.
+/// Stier som ikke kan kanoniseres (f.eks. filer som mangler) sammenlignes med `lexical_key`.
+pub fn dedupe_paths(paths: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::with_capacity(paths.len());
+    paths
+        .into_iter()
+        .filter(|path| {
+            let path = path_codec::decode(path);
+            let key = path.canonicalize().unwrap_or_else(|_| lexical_key(path));
+            seen.insert(key)
+        })
+        .collect()
+}
+
+/// Absolutt sti med `.` og `..` fjernet uten å spørre filsystemet
+/// `..` forbi en symlenke kan da gi feil mappe, men dette brukes bare når kanonisering feiler.
+fn lexical_key(path: PathBuf) -> PathBuf {
+    let absolute = std::path::absolute(&path).unwrap_or(path);
+    let mut key = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                key.pop();
+            }
+            other => key.push(other),
+        }
+    }
+    key
+}
+
+/// Finner byte-identiske filer ved hjelp av SHA-256 (`HashType::Exact`) eller BLAKE3
+///
+/// Filer grupperes først på størrelse, og kun filer som deler størrelse med minst
+/// én annen fil blir hashet, etter forfiltrering på start og slutt av filen
+/// (se `find_exact_duplicates_with`). Ingen bildedekoding, så dette er mye raskere enn
+/// perceptuell hashing. Hvis `cancel` settes underveis returneres gruppene for filene
+/// som rakk å bli hashet.
+pub fn find_exact_duplicates(
+    paths: &[String],
+    hash_type: hashing::HashType,
+    cancel: &CancellationToken,
+) -> ExactDuplicates {
+    find_exact_duplicates_with(paths, hash_type, true, cancel)
+}
+
+/// Som `find_exact_duplicates`, med valg om forfiltrering på delvis hash
+///
+/// Med `prefilter` hashes først bare de første og siste `PREFILTER_CHUNK_SIZE` bytene av
+/// filer med samme størrelse, og kun filer som fortsatt har en make hashes i sin helhet.
+/// Filer med lik størrelse men ulikt innhold (typisk bilder fra samme kamera) skiller seg
+/// nesten alltid allerede i starten, så det meste av lesingen av store filer spares.
+/// Resultatet er det samme med og uten forfiltrering.
+pub fn find_exact_duplicates_with(
+    paths: &[String],
+    hash_type: hashing::HashType,
+    prefilter: bool,
+    cancel: &CancellationToken,
+) -> ExactDuplicates {
+    let mut result = ExactDuplicates::default();
+
+    let mut by_size: HashMap<u64, Vec<&String>> = HashMap::new();
+    for path in paths {
+        match std::fs::metadata(path_codec::decode(path)) {
+            Ok(metadata) => by_size.entry(metadata.len()).or_default().push(path),
+            Err(_) => result.errors += 1,
+        }
+    }
+
+    let candidates: Vec<(&String, u64)> = by_size
+        .iter()
+        .filter(|(_, files)| files.len() > 1)
+        .flat_map(|(size, files)| files.iter().map(move |path| (*path, *size)))
+        .collect();
+    let candidates = if prefilter {
+        prefilter_by_head_tail(candidates, cancel, &mut result)
+    } else {
+        candidates
+    };
+
+    // Ytre `None` = hoppet over pga. avbrudd, indre `None` = lesefeil
+    let hashed: Vec<Option<Option<(String, u64, String)>>> = candidates
+        .par_iter()
+        .map(|(path, size)| {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            Some(
+                hashing::compute_content_hash(&path_codec::decode(path), hash_type)
+                    .ok()
+                    .map(|hash| ((*path).clone(), *size, hash)),
+            )
+        })
+        .collect();
+
+    let mut by_hash: HashMap<(u64, String), Vec<(String, u64)>> = HashMap::new();
+    for entry in hashed {
+        match entry {
+            Some(Some((path, size, hash))) => by_hash.entry((size, hash)).or_default().push((path, size)),
+            Some(None) => result.errors += 1,
+            None => result.cancelled = true,
+        }
+    }
+
+    result.groups = by_hash
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect();
+    result.groups.sort();
+
+    result
+}
+
+/// Bytes fra start og slutt av filen som hashes i forfiltreringen til `find_exact_duplicates_with`
+pub const PREFILTER_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Beholder kandidatene som deler størrelse og hash av start og slutt med minst én annen fil
+/// Filer på opptil to biter leses uansett nesten helt, og sendes rett videre til full hashing.
+fn prefilter_by_head_tail<'a>(
+    candidates: Vec<(&'a String, u64)>,
+    cancel: &CancellationToken,
+    result: &mut ExactDuplicates,
+) -> Vec<(&'a String, u64)> {
+    let (small, large): (Vec<_>, Vec<_>) = candidates
+        .into_iter()
+        .partition(|(_, size)| *size <= 2 * PREFILTER_CHUNK_SIZE as u64);
+
+    // Ytre `None` = hoppet over pga. avbrudd, indre `None` = lesefeil
+    let hashed: Vec<_> = large
+        .par_iter()
+        .map(|(path, size)| {
+            if cancel.is_cancelled() {
+                return None;
+            }
+            Some(
+                hashing::compute_head_tail_hash(&path_codec::decode(path), PREFILTER_CHUNK_SIZE)
+                    .ok()
+                    .map(|hash| (*path, *size, hash)),
+            )
+        })
+        .collect();
+
+    let mut by_partial: HashMap<(u64, String), Vec<(&String, u64)>> = HashMap::new();
+    for entry in hashed {
+        match entry {
+            Some(Some((path, size, hash))) => by_partial.entry((size, hash)).or_default().push((path, size)),
+            Some(None) => result.errors += 1,
+            None => result.cancelled = true,
+        }
+    }
+
+    let mut survivors = small;
+    survivors.extend(by_partial.into_values().filter(|group| group.len() > 1).flatten());
+    survivors
+}
+
+/// Ord som nedlastinger og filutforskere legger til kopier ("bilde - Kopi", "photo copy")
+const COPY_WORDS: &[&str] = &["copy", "kopi", "kopia", "kopie", "copie"];
+
+/// Filer med samme navn bortsett fra kopi-suffikser, fra `find_name_duplicates`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct NameDuplicateGroup {
+    /// Normalisert navn gruppen deler, f.eks. `photo.jpg`
+    pub name: String,
+    /// Korteste navn (som regel originalen) først
+    pub paths: Vec<String>,
+    /// `Some(true)` hvis alle filene er byte-identiske (SHA-256), `None` hvis ikke sjekket
+    pub contents_match: Option<bool>,
+}
+
+/// Filnavn uten kopi-suffikser, med små bokstaver: `Photo (1)`, `photo - Kopi (2)`,
+/// `photo_copy2` og `photo copy 2` blir alle `photo`
+///
+/// Tall fjernes bare i parentes eller etter et kopi-ord, så nummererte serier som
+/// `Scan 1`/`Scan 2` og `DSC_01`/`DSC_02` ikke regnes som samme navn.
+pub fn normalize_stem(stem: &str) -> String {
+    let mut normalized = stem.trim().to_lowercase();
+    loop {
+        let stripped = strip_copy_suffix(&normalized).trim_end_matches([' ', '-', '_']);
+        if stripped.is_empty() || stripped.len() == normalized.len() {
+            return normalized;
+        }
+        normalized = stripped.to_string();
+    }
+}
+
+fn strip_copy_suffix(stem: &str) -> &str {
+    let is_number = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+
+    // "photo (1)"
+    if let Some(open) = stem.strip_suffix(')').and_then(|rest| rest.rfind('(')) {
+        if is_number(&stem[open + 1..stem.len() - 1]) {
+            return &stem[..open];
+        }
+    }
+    // "photo_copy", "photo - Kopi", "photo_copy2", "photo copy 2"
+    let without_number = stem.trim_end_matches(|c: char| c.is_ascii_digit()).trim_end_matches(' ');
+    for word in COPY_WORDS {
+        if let Some(rest) = without_number.strip_suffix(word) {
+            if rest.ends_with([' ', '-', '_']) {
+                return rest;
+            }
+        }
+    }
+    stem
+}
+
+/// Grupperer filer i samme mappe med samme normaliserte navn og filendelse (se `normalize_stem`)
+///
+/// Finner typiske nedlastingskopier uten å dekode noe. Med `verify` sjekkes innholdet med
+/// SHA-256 (kun når størrelsene er like), slik at gruppene kan merkes som ekte kopier
+/// eller bare navnelikhet.
+pub fn find_name_duplicates(paths: &[String], verify: bool) -> Vec<NameDuplicateGroup> {
+    let mut by_name: HashMap<(PathBuf, String), Vec<String>> = HashMap::new();
+    for path_str in paths {
+        let path = path_codec::decode(path_str);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(ext) => format!("{}.{}", normalize_stem(&stem), ext.to_string_lossy().to_lowercase()),
+            None => normalize_stem(&stem),
+        };
+        let folder = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        by_name.entry((folder, name)).or_default().push(path_str.clone());
+    }
+
+    let mut groups: Vec<NameDuplicateGroup> = by_name
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((_, name), mut paths)| {
+            paths.sort_by(|a, b| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+            NameDuplicateGroup { name, paths, contents_match: None }
+        })
+        .collect();
+
+    if verify {
+        groups.par_iter_mut().for_each(|group| group.contents_match = Some(same_contents(&group.paths)));
+    }
+    groups.sort_by(|a, b| a.paths[0].cmp(&b.paths[0]));
+    groups
+}
+
+/// `true` hvis alle filene kan leses og er byte-identiske
+fn same_contents(paths: &[String]) -> bool {
+    let sizes: Option<Vec<u64>> = paths
+        .iter()
+        .map(|path| std::fs::metadata(path_codec::decode(path)).ok().map(|m| m.len()))
+        .collect();
+    if !sizes.is_some_and(|sizes| sizes.windows(2).all(|pair| pair[0] == pair[1])) {
+        return false;
+    }
+    let hashes: Option<Vec<String>> = paths
+        .iter()
+        .map(|path| hashing::compute_exact_hash(&path_codec::decode(path)).ok())
+        .collect();
+    hashes.is_some_and(|hashes| hashes.windows(2).all(|pair| pair[0] == pair[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use img_hash::ImageHash;
+
+    /// Lager en 64-bit hash der de `flipped` første bitene er satt
+    fn hash_with_bits(flipped: u32) -> ComparableHash {
+        let mut bytes = [0u8; 8];
+        for bit in 0..flipped {
+            bytes[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+        ComparableHash(ImageHash::from_bytes(&bytes).unwrap())
+    }
+
+    #[test]
+    fn test_group_by_time_chains_bursts() {
+        let items = vec![
+            ("c.jpg".to_string(), Some(10_500)),
+            ("udatert.jpg".to_string(), None),
+            ("a.jpg".to_string(), Some(10_000)),
+            ("d.jpg".to_string(), Some(60_000)),
+            ("b.jpg".to_string(), Some(10_200)),
+        ];
+
+        let groups = group_by_time(items, 1_000);
+
+        assert_eq!(
+            groups,
+            vec![
+                vec!["a.jpg".to_string(), "b.jpg".to_string(), "c.jpg".to_string()],
+                vec!["d.jpg".to_string()],
+                vec!["udatert.jpg".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identical_hashes_grouped() {
+        let hashes = vec![hash_with_bits(0), hash_with_bits(0), hash_with_bits(40)];
+        let groups = group_similar_hashes(&hashes, 0);
+
+        assert_eq!(groups, vec![vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn test_reference_index_nearest() {
+        // Referansebilde 1 har to utsnitt; bilde 0 og 2 deler hash
+        let reference = vec![
+            vec![hash_with_bits(0)],
+            vec![hash_with_bits(30), hash_with_bits(10)],
+            vec![hash_with_bits(0)],
+        ];
+        let index = ReferenceIndex::new(&reference);
+
+        assert_eq!(index.nearest(&[hash_with_bits(2)], 5), Some((0, 2)));
+        assert_eq!(index.nearest(&[hash_with_bits(50), hash_with_bits(12)], 5), Some((1, 2)));
+        assert_eq!(index.nearest(&[hash_with_bits(20)], 5), None);
+    }
+
+    #[test]
+    fn test_transitive_matches_share_group() {
+        // A~B (distanse 4) og B~C (distanse 4), men A og C har distanse 8
+        let a = hash_with_bits(0);
+        let b = hash_with_bits(4);
+        let c = hash_with_bits(8);
+        assert_eq!(a.0.dist(&c.0), 8);
+
+        let groups = group_similar_hashes(&[a, b, c], 5);
+        assert_eq!(groups, vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn test_threshold_separates_distant_hashes() {
+        let hashes = vec![hash_with_bits(0), hash_with_bits(20), hash_with_bits(40)];
+        let groups = group_similar_hashes(&hashes, 5);
+
+        assert_eq!(groups.len(), 3);
+    }
+
+    #[test]
+    fn test_empty_input() {
+        assert!(group_similar_hashes(&[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_hash_sets_match_on_any_variant() {
+        // Bilde 0 og 1 deler kun én variant; bilde 2 er langt unna alt
+        let sets = vec![
+            vec![hash_with_bits(0), hash_with_bits(30)],
+            vec![hash_with_bits(50), hash_with_bits(31)],
+            vec![hash_with_bits(12)],
+        ];
+        let groups = group_similar_hash_sets(&sets, 2);
+
+        assert_eq!(groups, vec![vec![0, 1], vec![2]]);
+    }
+
+    #[test]
+    fn test_grouper_stress_retains_only_hashes() {
+        // Pseudotilfeldige hasher, der hvert 100. bilde er en nesten-kopi av forrige
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let count = 20_000;
+        let mut grouper = HashSetGrouper::new(2);
+        let mut previous = 0u64;
+        for i in 0..count {
+            let value = if i % 100 == 1 { previous ^ 0b11 } else { next() };
+            previous = value;
+            let hash = ComparableHash(ImageHash::from_bytes(&value.to_le_bytes()).unwrap());
+            assert_eq!(grouper.add(vec![hash]), i);
+        }
+
+        let groups = grouper.groups();
+        assert_eq!(groups.iter().map(Vec::len).sum::<usize>(), count);
+        let duplicate_groups = groups.iter().filter(|g| g.len() > 1).count();
+        assert!(duplicate_groups >= count / 100, "fant {} grupper", duplicate_groups);
+        // Kun hashene beholdes: ett sett per bilde, og hver unike hash én gang i oppslaget (og treet)
+        assert_eq!(grouper.sets.len(), count);
+        assert!(grouper.sets.iter().all(|set| set.len() == 1));
+        let unique: HashSet<&ComparableHash> = grouper.sets.iter().flatten().collect();
+        assert_eq!(grouper.first_owner.len(), unique.len());
+    }
+
+    #[test]
+    fn test_similarity_percent() {
+        assert_eq!(similarity_percent(0, 64), 100.0);
+        assert_eq!(similarity_percent(16, 64), 75.0);
+        assert_eq!(similarity_percent(64, 64), 0.0);
+        assert_eq!(similarity_percent(100, 64), 0.0);
+    }
+
+    #[test]
+    fn test_hash_set_distance_uses_closest_pair() {
+        let a = vec![hash_with_bits(0), hash_with_bits(40)];
+        let b = vec![hash_with_bits(20), hash_with_bits(42)];
+        assert_eq!(hash_set_distance(&a, &b), 2);
+    }
+
+    #[test]
+    fn test_select_keeper_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("a.jpg");
+        let large = dir.path().join("b.jpg");
+        std::fs::write(&small, b"liten").unwrap();
+        std::fs::write(&large, b"mye storre fil").unwrap();
+        let paths: Vec<String> = [&small, &large, &dir.path().join("mangler.jpg")]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+
+        let largest = select_keeper(&paths, KeepStrategy::Largest).unwrap();
+        assert_eq!(largest.keeper, paths[1]);
+        // Filer som ikke kan leses havner sist
+        assert_eq!(largest.delete, vec![paths[0].clone(), paths[2].clone()]);
+
+        let smallest = select_keeper(&paths, KeepStrategy::Smallest).unwrap();
+        assert_eq!(smallest.keeper, paths[0]);
+    }
+
+    #[test]
+    fn test_select_keeper_ties_break_by_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.png");
+        let b = dir.path().join("b.png");
+        let big = dir.path().join("c.png");
+        image::RgbImage::new(10, 10).save(&b).unwrap();
+        image::RgbImage::new(10, 10).save(&a).unwrap();
+        image::RgbImage::new(20, 10).save(&big).unwrap();
+        let path = |p: &std::path::PathBuf| p.to_string_lossy().to_string();
+
+        let result = select_keeper(&[path(&b), path(&a)], KeepStrategy::HighestResolution).unwrap();
+        assert_eq!(result.keeper, path(&a));
+
+        let result = select_keeper(&[path(&a), path(&big)], KeepStrategy::HighestResolution).unwrap();
+        assert_eq!(result.keeper, path(&big));
+    }
+
+    #[test]
+    fn test_select_keeper_protected() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("arkiv");
+        let inbox = dir.path().join("innboks");
+        std::fs::create_dir_all(&archive).unwrap();
+        std::fs::create_dir_all(&inbox).unwrap();
+        let path = |p: std::path::PathBuf| p.to_string_lossy().to_string();
+        let archived = path(archive.join("a.jpg"));
+        let archived_copy = path(archive.join("b.jpg"));
+        let large = path(inbox.join("stor.jpg"));
+        std::fs::write(&archived, b"liten").unwrap();
+        std::fs::write(&archived_copy, b"liten").unwrap();
+        std::fs::write(&large, b"mye storre fil").unwrap();
+        let prefixes = protected_prefixes(&[format!("{}/", archive.display()), String::new()]);
+        assert_eq!(prefixes.len(), 1);
+
+        // Den største filen ville vunnet, men den beskyttede beholdes
+        let group = vec![large.clone(), archived.clone(), archived_copy.clone()];
+        let result = select_keeper_protected(&group, KeepStrategy::Largest, &prefixes).unwrap().unwrap();
+        assert_eq!(result.keeper, archived);
+        assert_eq!(result.delete, vec![large.clone()]);
+        assert_eq!(result.protected, vec![archived_copy.clone()]);
+
+        assert!(select_keeper_protected(&[archived, archived_copy], KeepStrategy::Largest, &prefixes).is_err());
+        let unprotected = select_keeper_protected(std::slice::from_ref(&large), KeepStrategy::Largest, &prefixes).unwrap().unwrap();
+        assert_eq!(unprotected.keeper, large);
+    }
+
+    #[test]
+    fn test_duplicate_waste() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = |name: &str, content: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            SizedPath { path: path.to_string_lossy().to_string(), size_bytes: content.len() as u64 }
+        };
+        let groups = vec![
+            vec![file("a.jpg", &[0; 100]), file("a_kopi.jpg", &[0; 40]), file("a_liten.jpg", &[0; 10])],
+            vec![file("b.jpg", &[0; 30]), file("b_kopi.jpg", &[0; 30])],
+            vec![file("alene.jpg", &[0; 5])],
+        ];
+
+        let largest = duplicate_waste(&groups, KeepStrategy::Largest, &[]).unwrap();
+        assert_eq!(largest, DuplicateWaste { reclaimable_bytes: 40 + 10 + 30, files_removable: 3 });
+        // Beholdes den minste, frigjøres de store
+        let smallest = duplicate_waste(&groups, KeepStrategy::Smallest, &[]).unwrap();
+        assert_eq!(smallest.reclaimable_bytes, 100 + 40 + 30);
+        assert_eq!(duplicate_waste(&[], KeepStrategy::Largest, &[]).unwrap(), DuplicateWaste::default());
+    }
+
+    #[test]
+    fn test_keep_strategy_from_name() {
+        assert_eq!(KeepStrategy::from_name("highest_resolution"), Some(KeepStrategy::HighestResolution));
+        assert_eq!(KeepStrategy::from_name("Newest"), Some(KeepStrategy::Newest));
+        assert_eq!(KeepStrategy::from_name("random"), None);
+    }
+
+    #[test]
+    fn test_find_exact_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.jpg");
+        let b = dir.path().join("b.jpg");
+        let c = dir.path().join("c.jpg"); // Samme størrelse, annet innhold
+        let d = dir.path().join("d.jpg"); // Unik størrelse
+        std::fs::write(&a, b"identisk").unwrap();
+        std::fs::write(&b, b"identisk").unwrap();
+        std::fs::write(&c, b"forskjel").unwrap();
+        std::fs::write(&d, b"helt annen lengde").unwrap();
+
+        let paths: Vec<String> = [&a, &b, &c, &d]
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect();
+        let result = find_exact_duplicates(&paths, hashing::HashType::Exact, &CancellationToken::new());
+
+        assert_eq!(result.errors, 0);
+        assert_eq!(result.groups.len(), 1);
+        assert_eq!(result.groups[0], vec![(paths[0].clone(), 8), (paths[1].clone(), 8)]);
+    }
+
+    #[test]
+    fn test_exact_prefilter_matches_full_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = 3 * PREFILTER_CHUNK_SIZE;
+        let base: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+        let variant = |index: usize| {
+            let mut data = base.clone();
+            data[index] ^= 0xFF;
+            data
+        };
+        let files = [
+            ("original.raw", base.clone()),
+            ("kopi.raw", base.clone()),
+            ("ulik_start.raw", variant(10)),
+            // Ulikt i midten: likt start og slutt, så bare full hash skiller dem
+            ("ulik_midt.raw", variant(size / 2)),
+            ("ulik_midt_kopi.raw", variant(size / 2)),
+            ("ulik_slutt.raw", variant(size - 1)),
+            ("liten.jpg", b"liten fil".to_vec()),
+            ("liten_kopi.jpg", b"liten fil".to_vec()),
+        ];
+        let paths: Vec<String> = files
+            .iter()
+            .map(|(name, data)| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, data).unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect();
+
+        let token = CancellationToken::new();
+        let brute_force = find_exact_duplicates_with(&paths, hashing::HashType::Exact, false, &token);
+        let prefiltered = find_exact_duplicates_with(&paths, hashing::HashType::Exact, true, &token);
+        assert_eq!(prefiltered.groups, brute_force.groups);
+        assert_eq!(prefiltered.errors, 0);
+        assert_eq!(prefiltered.groups.len(), 3);
+    }
+
+    #[test]
+    fn test_find_exact_duplicates_counts_missing_files() {
+        let result = find_exact_duplicates(&["/nonexistent/bilde.jpg".to_string()], hashing::HashType::Blake3, &CancellationToken::new());
+        assert_eq!(result.errors, 1);
+        assert!(result.groups.is_empty());
+    }
+
+    #[test]
+    fn test_find_exact_duplicates_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.jpg");
+        let b = dir.path().join("b.jpg");
+        std::fs::write(&a, b"identisk").unwrap();
+        std::fs::write(&b, b"identisk").unwrap();
+        let paths = vec![a.to_string_lossy().to_string(), b.to_string_lossy().to_string()];
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let result = find_exact_duplicates(&paths, hashing::HashType::Exact, &token);
+
+        assert!(result.cancelled);
+        assert!(result.groups.is_empty());
+        assert_eq!(result.errors, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_dedupe_paths_keeps_first_spelling() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.jpg");
+        std::fs::write(&file, b"bilde").unwrap();
+        let link = dir.path().join("lenke.jpg");
+        std::os::unix::fs::symlink(&file, &link).unwrap();
+        let dotted = dir.path().join(".").join("a.jpg");
+
+        let path = |p: &Path| p.to_string_lossy().to_string();
+        let deduped = dedupe_paths(vec![
+            path(&dotted),
+            path(&file),
+            path(&link),
+            "mangler.jpg".to_string(),
+            "mangler.jpg".to_string(),
+        ]);
+        assert_eq!(deduped, vec![path(&dotted), "mangler.jpg".to_string()]);
+    }
+
+    #[test]
+    fn test_dedupe_missing_paths_lexically() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("mangler.jpg");
+        let dotted = dir.path().join(".").join("mangler.jpg");
+        let parent = dir.path().join("undermappe").join("..").join("mangler.jpg");
+        let other = dir.path().join("annen.jpg");
+
+        let path = |p: &Path| p.to_string_lossy().to_string();
+        let deduped = dedupe_paths(vec![path(&dotted), path(&missing), path(&parent), path(&other)]);
+        assert_eq!(deduped, vec![path(&dotted), path(&other)]);
+    }
+
+    #[test]
+    fn test_compare_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut pixels = image::RgbImage::new(64, 48);
+        for (x, y, pixel) in pixels.enumerate_pixels_mut() {
+            *pixel = image::Rgb([(x * 4) as u8, (y * 5) as u8, 128]);
+        }
+        let original = dir.path().join("original.png");
+        let copy = dir.path().join("kopi.png");
+        let smaller = dir.path().join("mindre.png");
+        pixels.save(&original).unwrap();
+        std::fs::copy(&original, &copy).unwrap();
+        image::DynamicImage::ImageRgb8(pixels).thumbnail(32, 24).save(&smaller).unwrap();
+
+        let identical = compare_images(&original, &copy).unwrap();
+        assert!(identical.exact_match && identical.same_dimensions);
+        assert_eq!(identical.difference_distance, 0);
+
+        let resized = compare_images(&original, &smaller).unwrap();
+        assert!(!resized.exact_match && !resized.same_dimensions);
+        assert_eq!(resized.dimensions_b, Some((32, 24)));
+        assert!(resized.difference_distance <= 4, "{:?}", resized);
+
+        assert!(compare_images(&original, &dir.path().join("mangler.png")).is_err());
+    }
+
+    #[test]
+    fn test_group_similar_histograms() {
+        let mut red = [0f32; hashing::HISTOGRAM_BINS];
+        red[0] = 1.0;
+        let mut mostly_red = [0f32; hashing::HISTOGRAM_BINS];
+        mostly_red[0] = 0.9;
+        mostly_red[1] = 0.1;
+        let mut blue = [0f32; hashing::HISTOGRAM_BINS];
+        blue[5] = 1.0;
+
+        let groups = group_similar_histograms(&[red, blue, mostly_red], 0.15);
+        assert_eq!(groups, vec![vec![0, 2], vec![1]]);
+    }
+
+    #[test]
+    fn test_group_similar_histograms_matches_all_pairs() {
+        // Pseudotilfeldige histogrammer med få dominerende bøtter, sammenlignet med alle par
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+        let histograms: Vec<hashing::ColorHistogram> = (0..300)
+            .map(|_| {
+                let mut histogram = [0f32; hashing::HISTOGRAM_BINS];
+                for _ in 0..3 {
+                    histogram[next() as usize % hashing::HISTOGRAM_BINS] += (next() % 100 + 1) as f32;
+                }
+                let total: f32 = histogram.iter().sum();
+                histogram.map(|bin| bin / total)
+            })
+            .collect();
+
+        for max_distance in [0.0, 0.1, 0.3, 0.6] {
+            let mut union_find = UnionFind::new(histograms.len());
+            for i in 0..histograms.len() {
+                for j in (i + 1)..histograms.len() {
+                    if hashing::histogram_distance(&histograms[i], &histograms[j]) <= max_distance {
+                        union_find.union(i, j);
+                    }
+                }
+            }
+            assert_eq!(group_similar_histograms(&histograms, max_distance), union_find.groups(), "{}", max_distance);
+        }
+    }
+
+    #[test]
+    fn test_rank_similar_sorts_by_distance() {
+        let reference = vec![hash_with_bits(0)];
+        let candidates = vec![
+            vec![hash_with_bits(6)],
+            vec![hash_with_bits(20)],
+            vec![hash_with_bits(30), hash_with_bits(2)],
+            vec![hash_with_bits(0)],
+        ];
+
+        assert_eq!(rank_similar(&reference, &candidates, 10), vec![(3, 0), (2, 2), (0, 6)]);
+        assert!(rank_similar(&reference, &[], 10).is_empty());
+    }
+
+    #[test]
+    fn test_find_name_duplicates() {
+        for (name, expected) in [
+            ("Photo (1)", "photo"),
+            ("photo - Kopi (2)", "photo"),
+            ("photo_copy", "photo"),
+            ("photo copy 2", "photo"),
+            ("photo_copy2", "photo"),
+            ("photo-1", "photo-1"),
+            ("Scan 2", "scan 2"),
+            ("DSC_01", "dsc_01"),
+            ("IMG_1234", "img_1234"),
+            ("2024-03-07", "2024-03-07"),
+            ("(1)", "(1)"),
+        ] {
+            assert_eq!(normalize_stem(name), expected, "{}", name);
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, content: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, content).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let paths = vec![
+            write("photo (1).jpg", b"samme"),
+            write("photo.jpg", b"samme"),
+            write("ferie (1).JPG", b"annen"),
+            write("ferie.jpg", b"bilde"),
+            write("IMG_1234.jpg", b"samme"),
+            write("IMG_1235.jpg", b"samme"),
+            // Nummererte serier er ikke kopier, selv med likt innhold
+            write("Scan 1.jpg", b"samme"),
+            write("Scan 2.jpg", b"samme"),
+            write("DSC_01.jpg", b"samme"),
+            write("DSC_02.jpg", b"samme"),
+        ];
+
+        let groups = find_name_duplicates(&paths, true);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].name, "ferie.jpg");
+        assert_eq!(groups[0].contents_match, Some(false));
+        assert_eq!(groups[1].paths, vec![paths[1].clone(), paths[0].clone()]);
+        assert_eq!(groups[1].contents_match, Some(true));
+        assert_eq!(find_name_duplicates(&paths, false)[1].contents_match, None);
+    }
+}
@@ -1,11 +1,16 @@
 //! Bildehashing for duplikatdeteksjon
 //!
-//! Støtter både eksakt hashing (SHA-256) og perceptuell hashing (pHash, dHash, aHash)
+//! Støtter både eksakt hashing (SHA-256, BLAKE3) og perceptuell hashing (pHash, dHash, aHash)
 //! Optimalisert for store bildesamlinger
 
-// use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use crate::services::{heic, raw};
+use base64::Engine;
 use image::{DynamicImage, GenericImageView};
+#[cfg(test)]
+use image::{Rgba, RgbaImage};
+use image::imageops::FilterType;
 use img_hash::{HashAlg, HasherConfig, ImageHash};
+use serde::Deserialize;
 use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::io::Read;
@@ -16,12 +21,71 @@ use std::path::Path;
 pub enum HashType {
     /// Eksakt filhash (SHA-256)
     Exact,
+    /// Eksakt filhash (BLAKE3) - som `Exact`, men vesentlig raskere på store filer
+    Blake3,
     /// Perceptuell hash (pHash) - god for å finne visuelt like bilder
     Perceptual,
     /// Difference hash (dHash) - rask og effektiv
     Difference,
     /// Average hash (aHash) - enkel men mindre nøyaktig
     Average,
+    /// dHash av hele bildet pluss sentrerte utsnitt, så beskårne kopier også matches.
+    /// Tregere enn `Difference` (én hash per utsnitt), og trenger litt høyere terskel (8-10).
+    CropResistant,
+    /// dHash av bildet rotert 0/90/180/270 grader, så roterte kopier også matches.
+    /// Omtrent 4x hashekostnaden til `Difference`.
+    RotationInvariant,
+    /// Fargehistogram - finner bilder med samme palett, f.eks. omfargede eller filtrerte
+    /// kopier som dHash bommer på. Terskelen er avstand i prosent (0-100).
+    Color,
+}
+
+impl HashType {
+    /// Tolker hash-modus fra frontend: "exact", "blake3", "difference", "perceptual",
+    /// "average", "crop_resistant", "rotation_invariant" eller "color"
+    pub fn from_mode(mode: &str) -> Option<Self> {
+        match mode.to_lowercase().as_str() {
+            "exact" => Some(HashType::Exact),
+            "blake3" => Some(HashType::Blake3),
+            "difference" => Some(HashType::Difference),
+            "perceptual" => Some(HashType::Perceptual),
+            "average" => Some(HashType::Average),
+            "crop_resistant" => Some(HashType::CropResistant),
+            "rotation_invariant" => Some(HashType::RotationInvariant),
+            "color" => Some(HashType::Color),
+            _ => None,
+        }
+    }
+
+    /// Navn som brukes i frontend og i hash-cachen
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashType::Exact => "exact",
+            HashType::Blake3 => "blake3",
+            HashType::Difference => "difference",
+            HashType::Perceptual => "perceptual",
+            HashType::Average => "average",
+            HashType::CropResistant => "crop_resistant",
+            HashType::RotationInvariant => "rotation_invariant",
+            HashType::Color => "color",
+        }
+    }
+
+    /// Hasher filinnholdet i stedet for bildet (ingen dekoding, kun byte-identiske treff)
+    pub fn is_content_hash(&self) -> bool {
+        matches!(self, HashType::Exact | HashType::Blake3)
+    }
+
+    /// Anbefalt terskel for modusen ved gitt hash-størrelse (se `scale_threshold`)
+    /// Fargemodus måler i prosent og påvirkes ikke av størrelsen.
+    pub fn default_threshold(&self, hash_size: u32) -> u32 {
+        match self {
+            HashType::Exact | HashType::Blake3 => 0,
+            HashType::Color => 12,
+            HashType::CropResistant => scale_threshold(9, hash_size),
+            _ => scale_threshold(DEFAULT_THRESHOLD, hash_size),
+        }
+    }
 }
 
 /// Resultat av en hashing-operasjon
@@ -31,39 +95,68 @@ pub struct HashResult {
     pub hash_type: String,
 }
 
+/// Bufferstørrelse når filer strømmes gjennom en hasher
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
 /// Beregn eksakt SHA-256 hash av en fil
+/// Leses i biter på 64 KiB, så minnebruken er konstant også for store RAW-/videofiler.
 pub fn compute_exact_hash(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
     let mut file = File::open(path)?;
-    let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
-
     let mut hasher = Sha256::new();
-    hasher.update(&buffer);
-    let result = hasher.finalize();
+    let mut buffer = vec![0; HASH_CHUNK_SIZE];
 
-    Ok(hex::encode(result))
+    loop {
+        let bytes_read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Beregn eksakt BLAKE3-hash av en fil
+/// Filen strømmes gjennom hasheren, så minnebruken er konstant uansett filstørrelse.
+pub fn compute_blake3_hash(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(file)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// Beregn innholds-hash (hex) med algoritmen for `hash_type`
+pub fn compute_content_hash(path: &Path, hash_type: HashType) -> Result<String, Box<dyn std::error::Error>> {
+    match hash_type {
+        HashType::Exact => compute_exact_hash(path),
+        HashType::Blake3 => compute_blake3_hash(path),
+        other => Err(format!("{} er ikke en innholds-hash", other.as_str()).into()),
+    }
 }
 
-/// Leser første 4KB og siste 4KB av filen for en rask "unikhetssjekk"
-/// Dette er mye raskere enn å lese hele filen eller dekode bildet
-pub fn compute_partial_hash(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+/// SHA-256 av de første og siste `chunk_size` bytene, pluss filstørrelsen
+/// Slutten tas bare med når filen er større enn to biter.
+pub fn compute_head_tail_hash(path: &Path, chunk_size: usize) -> Result<String, Box<dyn std::error::Error>> {
     let mut file = File::open(path)?;
     let len = file.metadata()?.len();
-    let chunk_size = 4096;
     
     let mut hasher = Sha256::new();
     
-    // Les start
-    let mut buffer = vec![0; chunk_size];
-    let bytes_read = file.read(&mut buffer)?;
-    hasher.update(&buffer[..bytes_read]);
+    // Les start. `take` + `read_to_end` gir hele biten selv om en enkelt `read` returnerer mindre,
+    // så like filer alltid får lik hash.
+    let mut buffer = Vec::with_capacity(chunk_size);
+    (&mut file).take(chunk_size as u64).read_to_end(&mut buffer)?;
+    hasher.update(&buffer);
     
     // Hvis filen er stor nok, les slutt
     if len > (chunk_size as u64) * 2 {
         use std::io::Seek;
         file.seek(std::io::SeekFrom::End(-(chunk_size as i64)))?;
-        let bytes_read_end = file.read(&mut buffer)?;
-        hasher.update(&buffer[..bytes_read_end]);
+        buffer.clear();
+        (&mut file).take(chunk_size as u64).read_to_end(&mut buffer)?;
+        hasher.update(&buffer);
     }
     
     // Legg til filstørrelse i hashen for sikkerhets skyld
@@ -81,9 +174,54 @@ fn read_embedded_thumbnail(_path: &Path) -> Option<DynamicImage> {
     None
 }
 
-/// Laster et bilde fra fil og skalerer ned for raskere hashing
-/// Optimalisert versjon: Prøver embedded thumbnail først!
+/// Filter for nedskaleringen til 512 px før hashing
+///
+/// `Nearest` er raskest, men gir aliasing i fine detaljer (stoff, løv, skrift) som kan flytte
+/// hashen nok til at kopier ikke blir funnet. `Triangle` koster litt mer og fjerner det meste
+/// av aliasingen. `Lanczos3` er skarpest, men er merkbart tregere på store bilder.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResizeFilter {
+    Nearest,
+    #[default]
+    Triangle,
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResizeFilter::Nearest => "nearest",
+            ResizeFilter::Triangle => "triangle",
+            ResizeFilter::Lanczos3 => "lanczos3",
+        }
+    }
+
+    fn filter_type(&self) -> FilterType {
+        match self {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// Alt som avgjør verdien av en perceptuell hash, og dermed nøkkelen i hash-cachen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HashSettings {
+    pub hash_type: HashType,
+    pub hash_size: u32,
+    pub filter: ResizeFilter,
+}
+
+/// Laster et bilde fra fil og skalerer ned for raskere hashing, med standardfilteret
 pub fn load_image(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    load_image_with_filter(path, ResizeFilter::default())
+}
+
+/// Som `load_image`, med valgfritt filter for nedskaleringen (se `ResizeFilter`)
+/// Optimalisert versjon: Prøver embedded thumbnail først!
+pub fn load_image_with_filter(path: &Path, filter: ResizeFilter) -> Result<DynamicImage, Box<dyn std::error::Error>> {
     // 1. Prøv "Fast Path" - Embedded Thumbnail
     // Dette kan spare 100-500ms per bilde for store filer (RAW/høyoppløselig JPEG)
     if let Some(thumb) = read_embedded_thumbnail(path) {
@@ -91,42 +229,82 @@ pub fn load_image(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error
         // Vi sjekker likevel størrelsen før evt. nedskalering
         let (width, height) = thumb.dimensions();
         if width > 512 || height > 512 {
-            return Ok(thumb.resize(512, 512, image::imageops::FilterType::Nearest));
+            return Ok(thumb.resize(512, 512, filter.filter_type()));
         }
         return Ok(thumb);
     }
 
     // 2. "Slow Path" - Full dekoding
-    // Fallback hvis ingen thumbnail finnes
-    let reader = image::io::Reader::open(path)?
-        .with_guessed_format()?;
-
-    let img = reader.decode()?;
+    // Fallback hvis ingen thumbnail finnes. RAW dekodes via innebygd forhåndsvisning.
+    let img = if raw::is_raw_file(path) {
+        raw::extract_preview(path).map_err(|e| e.to_string())?
+    } else if heic::is_heic_file(path) {
+        heic::decode(path).map_err(|e| e.to_string())?
+    } else {
+        image::io::Reader::open(path)?
+            .with_guessed_format()?
+            .decode()?
+    };
 
     // 3. Resize for hashing
     let (width, height) = img.dimensions();
     if width > 512 || height > 512 {
-        Ok(img.resize(512, 512, image::imageops::FilterType::Nearest))
+        Ok(img.resize(512, 512, filter.filter_type()))
     } else {
         Ok(img)
     }
 }
 
+/// Standard hash-størrelse: 8x8 = 64 bit
+pub const DEFAULT_HASH_SIZE: u32 = 8;
+
+/// Gyldige hash-størrelser. 16x16 (256 bit) gir færre falske treff i store, varierte
+/// samlinger, men tåler mindre endringer før to kopier skilles.
+pub const HASH_SIZES: &[u32] = &[8, 16];
+
+/// Standard terskel for 8x8-hasher, samme som `DUPLICATE_THRESHOLD` i frontend
+pub const DEFAULT_THRESHOLD: u32 = 5;
+
+/// Skalerer en terskel gitt for 8x8-hasher til `hash_size`
+///
+/// Terskelen er en Hamming-distanse, og antall bits vokser med kvadratet av størrelsen:
+/// 16x16 har 4x så mange bits som 8x8, så samme relative likhet krever 4x terskel (5 -> 20).
+pub fn scale_threshold(threshold: u32, hash_size: u32) -> u32 {
+    threshold * hash_size * hash_size / (DEFAULT_HASH_SIZE * DEFAULT_HASH_SIZE)
+}
+
 /// Beregner perceptuell hash av et bilde
 /// Bruker 8x8 hash for god balanse mellom hastighet og nøyaktighet
 pub fn compute_perceptual_hash(
     image: &DynamicImage,
     hash_type: HashType,
 ) -> Result<ImageHash, Box<dyn std::error::Error>> {
-    // 8x8 hash er raskere og gir 64-bit hash
+    compute_perceptual_hash_with_size(image, hash_type, DEFAULT_HASH_SIZE)
+}
+
+/// Som `compute_perceptual_hash`, men med `hash_size` x `hash_size` bits (se `HASH_SIZES`)
+pub fn compute_perceptual_hash_with_size(
+    image: &DynamicImage,
+    hash_type: HashType,
+    hash_size: u32,
+) -> Result<ImageHash, Box<dyn std::error::Error>> {
     let hasher = HasherConfig::new()
-        .hash_size(8, 8)
+        .hash_size(hash_size, hash_size)
         .hash_alg(match hash_type {
             HashType::Perceptual => HashAlg::DoubleGradient,
             HashType::Difference => HashAlg::Gradient,
             HashType::Average => HashAlg::Mean,
-            HashType::Exact => {
-                return Err("Bruk compute_exact_hash for eksakt hashing".into());
+            HashType::Exact | HashType::Blake3 => {
+                return Err("Bruk compute_content_hash for eksakt hashing".into());
+            }
+            HashType::CropResistant => {
+                return Err("Bruk compute_crop_resistant_hashes for beskjæringsresistent hashing".into());
+            }
+            HashType::RotationInvariant => {
+                return Err("Bruk compute_rotation_invariant_hashes for rotasjonsuavhengig hashing".into());
+            }
+            HashType::Color => {
+                return Err("Bruk compute_color_histogram for fargesammenligning".into());
             }
         })
         .to_hasher();
@@ -134,6 +312,124 @@ pub fn compute_perceptual_hash(
     Ok(hasher.hash_image(image))
 }
 
+/// Andel av bredde/høyde som beholdes i hvert sentrerte utsnitt (1.0 = hele bildet)
+pub const CROP_VARIANTS: &[f32] = &[1.0, 0.8, 0.6];
+
+/// Beregner dHash for hele bildet og for sentrerte utsnitt (se `CROP_VARIANTS`)
+/// To bilder regnes som like hvis et hvilket som helst par av variantene er innenfor terskelen.
+pub fn compute_crop_resistant_hashes(
+    image: &DynamicImage,
+    hash_size: u32,
+) -> Result<Vec<ImageHash>, Box<dyn std::error::Error>> {
+    let (width, height) = image.dimensions();
+    let mut hashes = Vec::with_capacity(CROP_VARIANTS.len());
+
+    for &fraction in CROP_VARIANTS {
+        let crop_width = ((width as f32 * fraction).round() as u32).max(1);
+        let crop_height = ((height as f32 * fraction).round() as u32).max(1);
+        let variant = image.crop_imm(
+            (width - crop_width) / 2,
+            (height - crop_height) / 2,
+            crop_width,
+            crop_height,
+        );
+        hashes.push(compute_perceptual_hash_with_size(&variant, HashType::Difference, hash_size)?);
+    }
+
+    Ok(hashes)
+}
+
+/// Beregner dHash for bildet rotert 0, 90, 180 og 270 grader
+/// Minste distanse mellom to sett gir da likheten uansett hvordan kopien er rotert.
+/// Roter bildet fra `load_image` (maks 512 px), ikke originalen: rotasjonene er da billige,
+/// men hashingen koster fortsatt omtrent 4x `Difference`.
+pub fn compute_rotation_invariant_hashes(
+    image: &DynamicImage,
+    hash_size: u32,
+) -> Result<Vec<ImageHash>, Box<dyn std::error::Error>> {
+    let hash = |image: &DynamicImage| compute_perceptual_hash_with_size(image, HashType::Difference, hash_size);
+    Ok(vec![
+        hash(image)?,
+        hash(&image.rotate90())?,
+        hash(&image.rotate180())?,
+        hash(&image.rotate270())?,
+    ])
+}
+
+/// Nivåer per fargekanal i histogrammet (4 x 4 x 4 = 64 bøtter)
+const HISTOGRAM_LEVELS: usize = 4;
+
+/// Antall bøtter i et fargehistogram
+pub const HISTOGRAM_BINS: usize = HISTOGRAM_LEVELS * HISTOGRAM_LEVELS * HISTOGRAM_LEVELS;
+
+/// Normalisert RGB-histogram (summen av bøttene er 1)
+pub type ColorHistogram = [f32; HISTOGRAM_BINS];
+
+/// Beregner et grovt RGB-histogram for bildet
+/// Grove bøtter gjør histogrammet robust mot små farge- og komprimeringsforskjeller.
+/// Bruk bildet fra `load_image`, som allerede er skalert ned til maks 512 px.
+pub fn compute_color_histogram(image: &DynamicImage) -> ColorHistogram {
+    let mut histogram = [0f32; HISTOGRAM_BINS];
+    let rgb = image.to_rgb8();
+    let bucket = |value: u8| value as usize * HISTOGRAM_LEVELS / 256;
+
+    for pixel in rgb.pixels() {
+        let [r, g, b] = pixel.0;
+        histogram[(bucket(r) * HISTOGRAM_LEVELS + bucket(g)) * HISTOGRAM_LEVELS + bucket(b)] += 1.0;
+    }
+
+    let total = (rgb.width() * rgb.height()).max(1) as f32;
+    for bin in histogram.iter_mut() {
+        *bin /= total;
+    }
+    histogram
+}
+
+/// Avstand mellom to histogrammer, 0.0 (identisk palett) til 1.0 (ingen felles farger)
+/// Halvparten av L1-avstanden, dvs. 1 minus histogram-snittet.
+pub fn histogram_distance(a: &ColorHistogram, b: &ColorHistogram) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y).abs()).sum::<f32>() / 2.0
+}
+
+/// Gjennomsnittlig fargenivå per kanal (R, G, B), 0.0-1.0
+/// Forskjellen i hver kanal er aldri større enn `histogram_distance`, så dette kan
+/// brukes som grov signatur for å utelukke par uten å regne ut hele avstanden.
+pub fn histogram_channel_means(histogram: &ColorHistogram) -> [f32; 3] {
+    let max_level = (HISTOGRAM_LEVELS - 1) as f32;
+    let mut means = [0f32; 3];
+    for (bin, weight) in histogram.iter().enumerate() {
+        // Samme indeksering som i `compute_color_histogram`
+        let levels = [
+            bin / (HISTOGRAM_LEVELS * HISTOGRAM_LEVELS),
+            bin / HISTOGRAM_LEVELS % HISTOGRAM_LEVELS,
+            bin % HISTOGRAM_LEVELS,
+        ];
+        for (mean, level) in means.iter_mut().zip(levels) {
+            *mean += weight * level as f32 / max_level;
+        }
+    }
+    means
+}
+
+/// Koder et histogram som streng for hash-cachen
+pub fn histogram_to_string(histogram: &ColorHistogram) -> String {
+    let bytes: Vec<u8> = histogram.iter().flat_map(|bin| bin.to_le_bytes()).collect();
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// Leser et histogram lagret med `histogram_to_string`
+pub fn histogram_from_string(value: &str) -> Option<ColorHistogram> {
+    let bytes = base64::engine::general_purpose::STANDARD.decode(value).ok()?;
+    if bytes.len() != HISTOGRAM_BINS * 4 {
+        return None;
+    }
+    let mut histogram = [0f32; HISTOGRAM_BINS];
+    for (bin, chunk) in histogram.iter_mut().zip(bytes.chunks_exact(4)) {
+        *bin = f32::from_le_bytes(chunk.try_into().ok()?);
+    }
+    Some(histogram)
+}
+
 /// Wrapper for ImageHash som implementerer bk_tree::Metric
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ComparableHash(pub ImageHash<Box<[u8]>>);
@@ -250,6 +546,44 @@ mod tests {
         assert!(compute_perceptual_hash(&img, HashType::Exact).is_err());
     }
 
+    #[test]
+    fn test_exact_hash_streams_multiple_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stor.bin");
+        // Litt over to biter, så både hele og delvise lesinger testes
+        let data: Vec<u8> = (0..HASH_CHUNK_SIZE * 2 + 123).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        assert_eq!(compute_exact_hash(&path).unwrap(), hex::encode(Sha256::digest(&data)));
+    }
+
+    #[test]
+    fn test_blake3_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fil.bin");
+        std::fs::write(&path, b"abc").unwrap();
+
+        // Kjent testvektor for BLAKE3("abc")
+        assert_eq!(
+            compute_blake3_hash(&path).unwrap(),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        assert_eq!(compute_content_hash(&path, HashType::Blake3).unwrap(), compute_blake3_hash(&path).unwrap());
+        assert!(compute_content_hash(&path, HashType::Difference).is_err());
+    }
+
+    #[test]
+    fn test_hash_type_from_mode() {
+        assert_eq!(HashType::from_mode("exact"), Some(HashType::Exact));
+        assert_eq!(HashType::from_mode("BLAKE3"), Some(HashType::Blake3));
+        assert_eq!(HashType::from_mode("Difference"), Some(HashType::Difference));
+        assert_eq!(HashType::from_mode("perceptual"), Some(HashType::Perceptual));
+        assert_eq!(HashType::from_mode("average"), Some(HashType::Average));
+        assert_eq!(HashType::from_mode("crop_resistant"), Some(HashType::CropResistant));
+        assert_eq!(HashType::from_mode("rotation_invariant"), Some(HashType::RotationInvariant));
+        assert_eq!(HashType::from_mode("md5"), None);
+    }
+
     #[test]
     fn test_hash_is_deterministic() {
         let img = create_gradient_image(100, 100, Rgba([100, 150, 200, 255]), Rgba([50, 100, 150, 255]));
@@ -315,4 +649,106 @@ mod tests {
         println!("Ensfargede bilder (rød vs blå) distanse: {}", distance);
         // Ikke assert på distanse - ensfargede bilder er edge case
     }
+
+    #[test]
+    fn test_crop_resistant_matches_center_crop() {
+        // Bilde med struktur i alle deler, slik at utsnitt faktisk endrer hashen
+        let mut pixels = RgbaImage::new(200, 200);
+        for (x, y, pixel) in pixels.enumerate_pixels_mut() {
+            let v = ((x * x / 90 + y * 3) % 256) as u8;
+            *pixel = Rgba([v, 255 - v, (x % 256) as u8, 255]);
+        }
+        let img = DynamicImage::ImageRgba8(pixels);
+        // Simuler en beskåret kopi: midterste 80 %
+        let cropped = img.crop_imm(20, 20, 160, 160);
+
+        let original_hashes = compute_crop_resistant_hashes(&img, DEFAULT_HASH_SIZE).unwrap();
+        let cropped_hashes = compute_crop_resistant_hashes(&cropped, DEFAULT_HASH_SIZE).unwrap();
+        assert_eq!(original_hashes.len(), CROP_VARIANTS.len());
+
+        let best = original_hashes
+            .iter()
+            .flat_map(|a| cropped_hashes.iter().map(move |b| a.dist(b)))
+            .min()
+            .unwrap();
+        assert!(best <= 2, "Beste variantpar skal matche, distanse var {}", best);
+    }
+
+    #[test]
+    fn test_rotation_invariant_matches_rotated_copy() {
+        let img = create_gradient_image(120, 80, Rgba([250, 40, 40, 255]), Rgba([20, 20, 200, 255]));
+        let rotated = img.rotate90();
+
+        let plain = compare_hashes(
+            &compute_perceptual_hash(&img, HashType::Difference).unwrap(),
+            &compute_perceptual_hash(&rotated, HashType::Difference).unwrap(),
+        );
+        let original_hashes = compute_rotation_invariant_hashes(&img, DEFAULT_HASH_SIZE).unwrap();
+        let rotated_hashes = compute_rotation_invariant_hashes(&rotated, DEFAULT_HASH_SIZE).unwrap();
+        let best = original_hashes
+            .iter()
+            .flat_map(|a| rotated_hashes.iter().map(move |b| a.dist(b)))
+            .min()
+            .unwrap();
+
+        assert!(plain > 10, "Vanlig dHash skal skille roterte kopier, distanse var {}", plain);
+        assert_eq!(best, 0, "Rotasjonsuavhengig hash skal matche rotert kopi");
+    }
+
+    #[test]
+    fn test_hash_size_16_round_trips_base64() {
+        let img = create_gradient_image(100, 100, Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let hash = compute_perceptual_hash_with_size(&img, HashType::Difference, 16).unwrap();
+        assert_eq!(hash.as_bytes().len() * 8, 256);
+
+        let decoded = ImageHash::<Box<[u8]>>::from_base64(&hash.to_base64()).unwrap();
+        assert_eq!(decoded.as_bytes(), hash.as_bytes());
+
+        assert_eq!(HashType::Difference.default_threshold(DEFAULT_HASH_SIZE), DEFAULT_THRESHOLD);
+        assert_eq!(HashType::Difference.default_threshold(16), 20);
+        assert_eq!(HashType::Color.default_threshold(16), 12);
+    }
+
+    #[test]
+    fn test_color_histogram_ignores_composition() {
+        // Samme farger i ulik layout: dHash skiller dem, histogrammet gjør det ikke
+        let left_red = RgbaImage::from_fn(64, 64, |x, _| if x < 32 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) });
+        let top_red = RgbaImage::from_fn(64, 64, |_, y| if y < 32 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 255]) });
+        let green = RgbaImage::from_pixel(64, 64, Rgba([0, 255, 0, 255]));
+
+        let a = compute_color_histogram(&DynamicImage::ImageRgba8(left_red));
+        let b = compute_color_histogram(&DynamicImage::ImageRgba8(top_red));
+        let c = compute_color_histogram(&DynamicImage::ImageRgba8(green));
+
+        assert!(histogram_distance(&a, &b) < 1e-6);
+        assert!((histogram_distance(&a, &c) - 1.0).abs() < 1e-6);
+        assert!((a.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert_eq!(histogram_from_string(&histogram_to_string(&a)), Some(a));
+        assert_eq!(histogram_from_string("ugyldig"), None);
+    }
+
+    #[test]
+    fn test_triangle_filter_reduces_aliasing() {
+        // Tette striper med en periode litt over 4 px gir moiré når hver fjerde piksel plukkes
+        // ut (Nearest ved 2048 -> 512). Triangle midler stripene bort, slik hashen av originalen gjør.
+        let original = DynamicImage::ImageLuma8(image::GrayImage::from_fn(2048, 512, |x, _| {
+            let brightness = x as f32 / 2048.0 * 255.0;
+            let stripe = (x as f32 / 4.02).fract() < 0.5;
+            image::Luma([if stripe { brightness as u8 } else { 0 }])
+        }));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("striper.png");
+        original.save(&path).unwrap();
+
+        let reference = compute_perceptual_hash(&original, HashType::Difference).unwrap();
+        let distance = |filter: ResizeFilter| {
+            let scaled = load_image_with_filter(&path, filter).unwrap();
+            assert_eq!(scaled.dimensions(), (512, 128));
+            compare_hashes(&reference, &compute_perceptual_hash(&scaled, HashType::Difference).unwrap())
+        };
+
+        let (nearest, triangle) = (distance(ResizeFilter::Nearest), distance(ResizeFilter::Triangle));
+        assert!(nearest > DEFAULT_THRESHOLD, "nearest {}", nearest);
+        assert!(triangle <= DEFAULT_THRESHOLD, "triangle {}", triangle);
+    }
 }
@@ -0,0 +1,103 @@
+//! Støtte for HEIC/HEIF-bilder (standardformatet på iPhone)
+//!
+//! `image`-craten kan ikke dekode HEVC. Med featuren `heic` brukes libheif via
+//! `libheif-rs`; uten den prøver vi den innebygde JPEG-forhåndsvisningen (f.eks.
+//! EXIF-thumbnailen) på samme måte som for RAW-filer, og gir ellers en tydelig feil.
+
+use image::DynamicImage;
+use std::path::Path;
+
+/// Filendelser for HEIC/HEIF
+pub const HEIC_EXTENSIONS: &[&str] = &["heic", "heif"];
+
+/// Sjekker om en sti har HEIC/HEIF-filendelse
+pub fn is_heic_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| HEIC_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// libheif roterer bildet etter HEIF-transformasjonene ved dekoding, så EXIF-orienteringen
+/// skal ikke brukes en gang til. Den innebygde forhåndsvisningen er ikke rotert.
+pub fn is_oriented_on_decode(path: &Path) -> bool {
+    cfg!(feature = "heic") && is_heic_file(path)
+}
+
+/// Dekoder hovedbildet i en HEIC/HEIF-fil
+#[cfg(feature = "heic")]
+pub fn decode(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| format!("Ugyldig sti: {}", path.display()))?;
+    let lib_heif = LibHeif::new();
+    let context = HeifContext::read_from_file(path_str)?;
+    let handle = context.primary_image_handle()?;
+    let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgb), None)?;
+
+    let plane = image
+        .planes()
+        .interleaved
+        .ok_or_else(|| format!("Uventet pikselformat i {}", path.display()))?;
+    let (width, height) = (plane.width, plane.height);
+    let row_len = width as usize * 3;
+
+    // Radene kan ha padding (stride > bredde * 3)
+    let mut pixels = Vec::with_capacity(row_len * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    let buffer = image::RgbImage::from_raw(width, height, pixels)
+        .ok_or_else(|| format!("Kunne ikke lese pikseldata i {}", path.display()))?;
+    Ok(DynamicImage::ImageRgb8(buffer))
+}
+
+/// Uten libheif: bruk innebygd JPEG-forhåndsvisning hvis filen har en
+#[cfg(not(feature = "heic"))]
+pub fn decode(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+    crate::services::raw::extract_preview(path).map_err(|_| {
+        format!(
+            "HEIC-støtte er ikke kompilert inn, og {} har ingen innebygd forhåndsvisning",
+            path.display()
+        )
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbImage};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_is_heic_file() {
+        assert!(is_heic_file(Path::new("IMG_0001.HEIC")));
+        assert!(is_heic_file(Path::new("bilde.heif")));
+        assert!(!is_heic_file(Path::new("bilde.jpg")));
+    }
+
+    #[cfg(not(feature = "heic"))]
+    #[test]
+    fn test_decode_falls_back_to_embedded_preview() {
+        let dir = tempdir().unwrap();
+
+        let mut preview = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(16, 12))
+            .write_to(&mut preview, image::ImageOutputFormat::Jpeg(80))
+            .unwrap();
+        let mut bytes = b"\0\0\0\x18ftypheic".to_vec();
+        bytes.extend(&preview);
+        let with_preview = dir.path().join("med.heic");
+        fs::write(&with_preview, bytes).unwrap();
+        assert_eq!(decode(&with_preview).unwrap().dimensions(), (16, 12));
+
+        let without_preview = dir.path().join("uten.heic");
+        fs::write(&without_preview, b"\0\0\0\x18ftypheic").unwrap();
+        let error = decode(&without_preview).unwrap_err().to_string();
+        assert!(error.contains("ikke kompilert inn"), "{}", error);
+    }
+}
@@ -0,0 +1,256 @@
+//! Angre-logg for flytte- og sorteringsoperasjoner
+//!
+//! Hver vellykket flytting/kopiering registreres i en journal som lagres som JSON
+//! (én fil per operasjon). `undo` leser journalen og reverserer stegene i motsatt
+//! rekkefølge: flyttinger flyttes tilbake, kopier og lenker slettes.
+
+use crate::services::{path_codec, sorter};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalAction {
+    Move,
+    Copy,
+    /// Hard- eller symlenke; angres ved å fjerne lenken
+    Link,
+}
+
+/// Ett utført steg: `source` ble flyttet/kopiert til `destination`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntry {
+    pub action: JournalAction,
+    pub source: String,
+    pub destination: String,
+    /// Størrelse og mtime på målfilen rett etter operasjonen,
+    /// brukes til å oppdage at filen er endret eller byttet ut siden
+    pub size_bytes: u64,
+    pub mtime: u64,
+}
+
+impl JournalEntry {
+    /// Lager en oppføring ut fra målfilen slik den ligger på disk nå
+    pub fn record(action: JournalAction, source: &Path, destination: &Path) -> Option<Self> {
+        let (size_bytes, mtime) = file_signature(destination)?;
+        Some(Self {
+            action,
+            source: path_codec::encode(source),
+            destination: path_codec::encode(destination),
+            size_bytes,
+            mtime,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Journal {
+    id: String,
+    created_at: String,
+    entries: Vec<JournalEntry>,
+}
+
+/// Resultat av en angre-operasjon
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct UndoResult {
+    pub restored: usize,
+    /// Filer som er flyttet eller endret siden operasjonen, og derfor ikke røres
+    pub skipped: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+fn file_signature(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let mtime = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    Some((metadata.len(), mtime))
+}
+
+/// Id-er brukes som filnavn, så kun trygge tegn tillates
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn journal_path(journal_dir: &Path, id: &str) -> Result<PathBuf, String> {
+    if !is_valid_id(id) {
+        return Err(format!("Ugyldig operasjons-id: {}", id));
+    }
+    Ok(journal_dir.join(format!("{}.json", id)))
+}
+
+/// Lagrer en journal og returnerer operasjons-id-en
+pub fn save(journal_dir: &Path, entries: &[JournalEntry]) -> Result<String, Box<dyn std::error::Error>> {
+    fs::create_dir_all(journal_dir)?;
+
+    let now = chrono::Local::now();
+    let base_id = format!(
+        "{}-{:08x}",
+        now.format("%Y%m%d-%H%M%S"),
+        now.timestamp_subsec_nanos()
+    );
+    // Svært usannsynlig, men to operasjoner i samme nanosekund skal ikke overskrive hverandre
+    let mut id = base_id.clone();
+    let mut counter = 1;
+    while journal_dir.join(format!("{}.json", id)).exists() {
+        id = format!("{}-{}", base_id, counter);
+        counter += 1;
+    }
+
+    let journal = Journal {
+        id: id.clone(),
+        created_at: now.to_rfc3339(),
+        entries: entries.to_vec(),
+    };
+    fs::write(journal_path(journal_dir, &id)?, serde_json::to_string(&journal)?)?;
+    Ok(id)
+}
+
+/// Reverserer en lagret operasjon
+///
+/// Steg der målfilen mangler, er endret, eller der kildestien er tatt i bruk igjen,
+/// hoppes over i stedet for å overskrive noe. Steg som feiler blir liggende i
+/// journalen slik at de kan prøves igjen; ellers slettes journalen.
+pub fn undo(journal_dir: &Path, id: &str) -> Result<UndoResult, Box<dyn std::error::Error>> {
+    let path = journal_path(journal_dir, id)?;
+    if !path.exists() {
+        return Err(format!("Fant ingen operasjon med id {}", id).into());
+    }
+    let mut journal: Journal = serde_json::from_str(&fs::read_to_string(&path)?)?;
+
+    let mut result = UndoResult::default();
+    let mut failed = Vec::new();
+
+    for entry in journal.entries.iter().rev() {
+        let (source, destination) = (path_codec::decode(&entry.source), path_codec::decode(&entry.destination));
+        let (source, destination) = (source.as_path(), destination.as_path());
+
+        if file_signature(destination) != Some((entry.size_bytes, entry.mtime)) {
+            result.skipped.push(format!("{} er flyttet eller endret etter operasjonen", entry.destination));
+            continue;
+        }
+
+        let outcome = match entry.action {
+            JournalAction::Move => {
+                if source.exists() {
+                    result.skipped.push(format!("{} finnes allerede, flyttes ikke tilbake", entry.source));
+                    continue;
+                }
+                if let Some(parent) = source.parent() {
+                    let _ = fs::create_dir_all(parent);
+                }
+                sorter::move_file(destination, source)
+            }
+            JournalAction::Copy | JournalAction::Link => fs::remove_file(destination),
+        };
+
+        match outcome {
+            Ok(_) => result.restored += 1,
+            Err(e) => {
+                result.errors.push(format!("Kunne ikke angre {}: {}", entry.destination, e));
+                failed.push(entry.clone());
+            }
+        }
+    }
+
+    if failed.is_empty() {
+        fs::remove_file(&path)?;
+    } else {
+        failed.reverse();
+        journal.entries = failed;
+        fs::write(&path, serde_json::to_string(&journal)?)?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_undo_move_and_copy() {
+        let dir = tempdir().unwrap();
+        let journal_dir = dir.path().join("journal");
+        let moved_src = dir.path().join("a.jpg");
+        let moved_dst = dir.path().join("a_sorted.jpg");
+        let copy_src = dir.path().join("b.jpg");
+        let copy_dst = dir.path().join("b_copy.jpg");
+
+        fs::write(&moved_dst, b"a").unwrap();
+        fs::write(&copy_src, b"b").unwrap();
+        fs::write(&copy_dst, b"b").unwrap();
+
+        let entries = vec![
+            JournalEntry::record(JournalAction::Move, &moved_src, &moved_dst).unwrap(),
+            JournalEntry::record(JournalAction::Copy, &copy_src, &copy_dst).unwrap(),
+        ];
+        let id = save(&journal_dir, &entries).unwrap();
+
+        let result = undo(&journal_dir, &id).unwrap();
+        assert_eq!(result.restored, 2);
+        assert!(result.skipped.is_empty());
+        assert!(moved_src.exists());
+        assert!(!moved_dst.exists());
+        assert!(copy_src.exists());
+        assert!(!copy_dst.exists());
+
+        // Journalen er brukt opp
+        assert!(undo(&journal_dir, &id).is_err());
+    }
+
+    #[test]
+    fn test_undo_skips_files_moved_again() {
+        let dir = tempdir().unwrap();
+        let journal_dir = dir.path().join("journal");
+        let src = dir.path().join("a.jpg");
+        let dst = dir.path().join("sorted.jpg");
+        fs::write(&dst, b"a").unwrap();
+
+        let entries = vec![JournalEntry::record(JournalAction::Move, &src, &dst).unwrap()];
+        let id = save(&journal_dir, &entries).unwrap();
+
+        // Brukeren flytter filen videre etter operasjonen
+        fs::rename(&dst, dir.path().join("elsewhere.jpg")).unwrap();
+
+        let result = undo(&journal_dir, &id).unwrap();
+        assert_eq!(result.restored, 0);
+        assert_eq!(result.skipped.len(), 1);
+        assert!(!src.exists());
+    }
+
+    #[test]
+    fn test_undo_does_not_clobber_source() {
+        let dir = tempdir().unwrap();
+        let journal_dir = dir.path().join("journal");
+        let src = dir.path().join("a.jpg");
+        let dst = dir.path().join("sorted.jpg");
+        fs::write(&dst, b"sortert").unwrap();
+
+        let entries = vec![JournalEntry::record(JournalAction::Move, &src, &dst).unwrap()];
+        let id = save(&journal_dir, &entries).unwrap();
+
+        fs::write(&src, b"ny fil").unwrap();
+
+        let result = undo(&journal_dir, &id).unwrap();
+        assert_eq!(result.skipped.len(), 1);
+        assert_eq!(fs::read(&src).unwrap(), b"ny fil");
+        assert!(dst.exists());
+    }
+
+    #[test]
+    fn test_rejects_unsafe_ids() {
+        let dir = tempdir().unwrap();
+        assert!(undo(dir.path(), "../hemmelig").is_err());
+        assert!(undo(dir.path(), "").is_err());
+    }
+}
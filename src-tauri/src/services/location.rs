@@ -0,0 +1,139 @@
+//! Sortering etter GPS-posisjon (`SortConfig::location`)
+//!
+//! Uten nett finnes ingen omvendt geokoding, så bildene legges i ruter på `bucket_degrees`
+//! grader, f.eks. `59.9N_10.7E`. Gir frontend en liste med kjente steder, brukes navnet på
+//! nærmeste sted innenfor radiusen i stedet.
+
+use crate::services::template::sanitize_component;
+use serde::Deserialize;
+
+/// Mappe for filer uten GPS-posisjon
+pub const UNKNOWN_LOCATION_FOLDER: &str = "Ukjent sted";
+
+/// Standard rutestørrelse: 0.1 grader er ca. 11 km nord-sør
+pub const DEFAULT_BUCKET_DEGREES: f64 = 0.1;
+
+/// Standard radius for et navngitt sted
+pub const DEFAULT_PLACE_RADIUS_KM: f64 = 10.0;
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LocationConfig {
+    /// Størrelse på rutene i grader (breddegrad og lengdegrad)
+    pub bucket_degrees: f64,
+    /// Valgfri offline stedsliste; bilder innenfor et steds radius havner i en mappe med navnet
+    pub places: Vec<Place>,
+}
+
+impl Default for LocationConfig {
+    fn default() -> Self {
+        Self {
+            bucket_degrees: DEFAULT_BUCKET_DEGREES,
+            places: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Place {
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// `None` gir `DEFAULT_PLACE_RADIUS_KM`
+    pub radius_km: Option<f64>,
+}
+
+impl LocationConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.bucket_degrees > 0.0 && self.bucket_degrees <= 90.0) {
+            return Err(format!("Ugyldig rutestørrelse for GPS-sortering: {}", self.bucket_degrees));
+        }
+        match self.places.iter().find(|place| sanitize_component(&place.name).is_empty()) {
+            Some(place) => Err(format!("Ugyldig stedsnavn: {:?}", place.name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Mappenavn for en posisjon (breddegrad, lengdegrad) fra `metadata::read_gps`
+    pub fn folder_for(&self, gps: Option<(f64, f64)>) -> String {
+        let Some((latitude, longitude)) = gps else {
+            return UNKNOWN_LOCATION_FOLDER.to_string();
+        };
+        let nearest = self
+            .places
+            .iter()
+            .map(|place| (place, distance_km((latitude, longitude), (place.latitude, place.longitude))))
+            .filter(|(place, distance)| *distance <= place.radius_km.unwrap_or(DEFAULT_PLACE_RADIUS_KM))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        match nearest {
+            Some((place, _)) => sanitize_component(&place.name),
+            None => self.bucket_folder(latitude, longitude),
+        }
+    }
+
+    /// Ruten posisjonen ligger i, navngitt etter hjørnet nærmest ekvator og nullmeridianen
+    /// sett fra sør-vest, f.eks. `59.9N_10.7E` for Oslo sentrum
+    fn bucket_folder(&self, latitude: f64, longitude: f64) -> String {
+        let step = self.bucket_degrees;
+        let decimals = (-step.log10()).ceil().clamp(0.0, 6.0) as usize;
+        let bucket = |value: f64| (value / step).floor() * step;
+        let (lat, lon) = (bucket(latitude), bucket(longitude));
+        format!(
+            "{:.*}{}_{:.*}{}",
+            decimals,
+            lat.abs(),
+            if lat < 0.0 { 'S' } else { 'N' },
+            decimals,
+            lon.abs(),
+            if lon < 0.0 { 'W' } else { 'E' },
+        )
+    }
+}
+
+/// Storsirkelavstand (haversine) mellom to posisjoner i km
+fn distance_km(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat_a, lat_b) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * h.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_folder_for_position() {
+        let mut config = LocationConfig::default();
+        assert_eq!(config.folder_for(None), UNKNOWN_LOCATION_FOLDER);
+        assert_eq!(config.folder_for(Some((59.9139, 10.7522))), "59.9N_10.7E");
+        assert_eq!(config.folder_for(Some((-33.8568, 151.2153))), "33.9S_151.2E");
+        assert_eq!(config.folder_for(Some((40.6892, -74.0445))), "40.6N_74.1W");
+
+        config.places.push(Place {
+            name: "Oslo".to_string(),
+            latitude: 59.91,
+            longitude: 10.75,
+            radius_km: None,
+        });
+        config.places.push(Place {
+            name: "Hytta: Geilo".to_string(),
+            latitude: 60.53,
+            longitude: 8.21,
+            radius_km: Some(2.0),
+        });
+        assert_eq!(config.folder_for(Some((59.95, 10.70))), "Oslo");
+        assert_eq!(config.folder_for(Some((60.534, 8.205))), "Hytta_ Geilo");
+        assert_eq!(config.folder_for(Some((60.60, 8.21))), "60.6N_8.2E");
+        assert!(config.validate().is_ok());
+
+        config.bucket_degrees = 1.0;
+        assert_eq!(config.folder_for(Some((60.60, 8.21))), "60N_8E");
+        config.bucket_degrees = 0.0;
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,35 +1,167 @@
 //! Tjeneste for å lese metadata fra bilder (EXIF)
 
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use crate::services::{mp4, path_codec, raw};
+use crate::services::sorter::OperationResult;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::BufReader;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// GPS-posisjon i desimalgrader (negativ for sør/vest)
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsCoordinates {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Samlet metadata for visning i frontend
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageMetadata {
+    /// Opprettelsesdato i RFC 3339-format
+    pub date: Option<String>,
+    pub date_source: Option<DateSource>,
+    pub gps: Option<GpsCoordinates>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens: Option<String>,
+    pub exposure: ExposureInfo,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Stjernemerking (0-5) fra XMP-sidecar (`xmp:Rating`)
+    pub rating: Option<u8>,
+}
+
+/// Kamera og objektiv fra EXIF (`Make`, `Model`, `LensModel`)
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CameraInfo {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens: Option<String>,
+}
+
+/// Eksponeringsinnstillinger fra EXIF, formatert for visning
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExposureInfo {
+    pub iso: Option<u32>,
+    /// Blender, f.eks. "f/2.8"
+    pub f_number: Option<String>,
+    /// Lukkertid, f.eks. "1/250" eller "2s"
+    pub exposure_time: Option<String>,
+    /// Brennvidde, f.eks. "50 mm"
+    pub focal_length: Option<String>,
+}
+
+/// Opprettelsesdato og hvor den ble hentet fra
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CreationDate {
+    pub date: DateTime<Local>,
+    pub source: DateSource,
+}
+
+/// Hvor opprettelsesdatoen ble hentet fra
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DateSource {
+    /// XMP-sidecar (Lightroom/darktable)
+    Sidecar,
+    /// EXIF DateTimeOriginal: når bildet ble tatt
+    ExifOriginal,
+    /// EXIF DateTimeDigitized: når bildet ble digitalisert (f.eks. skannet)
+    ExifDigitized,
+    /// EXIF DateTime: sist endret, ofte satt av redigeringsprogrammer
+    ExifDateTime,
+    /// Opprettelsesdato fra videocontaineren
+    Video,
+    /// Filsystemets endringsdato; kan være helt feil etter kopiering
+    FileMtime,
+    /// Filsystemets opprettelsesdato (`FallbackPolicy::Ctime`); settes ofte ved kopiering
+    FileCtime,
+}
+
+/// Hvilken filsystemdato som brukes når filen mangler dato i metadata
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FallbackPolicy {
+    /// Endringsdato (standard)
+    #[default]
+    Mtime,
+    /// Opprettelsesdato. Der filsystemet ikke har den, brukes endringsdatoen
+    /// (kilden blir da `FileMtime`).
+    Ctime,
+    /// Ingen fallback: filer uten dato i metadata får ingen dato
+    None,
+}
+
+impl DateSource {
+    /// Grov kategori til manifestet: "sidecar", "exif", "video", "mtime" eller "ctime"
+    pub fn category(&self) -> &'static str {
+        match self {
+            DateSource::Sidecar => "sidecar",
+            DateSource::ExifOriginal | DateSource::ExifDigitized | DateSource::ExifDateTime => "exif",
+            DateSource::Video => "video",
+            DateSource::FileMtime => "mtime",
+            DateSource::FileCtime => "ctime",
+        }
+    }
+
+    /// Datoer fra filsystemet er upålitelige og bør markeres i UI
+    pub fn is_unreliable(&self) -> bool {
+        matches!(self, DateSource::FileMtime | DateSource::FileCtime)
+    }
+}
 
 /// prøver å lese opprettelsesdato fra bildet
-/// 1. Sjekker EXIF (DateTimeOriginal)
-/// 2. Faller tilbake til filsystemets endringsdato (mtime)
-pub fn read_creation_date(path: &Path) -> Option<DateTime<Local>> {
+/// 1. Sjekker XMP-sidecar (Lightroom/darktable)
+/// 2. Sjekker EXIF (DateTimeOriginal, DateTimeDigitized, DateTime)
+/// 3. Sjekker videocontaineren (MP4/MOV direkte, ellers FFprobe)
+/// 4. Faller tilbake til filsystemets endringsdato (mtime)
+pub fn read_creation_date(path: &Path) -> Option<CreationDate> {
     read_creation_date_with_fallback(path, true)
 }
 
 /// Leser opprettelsesdato med konfigurerbar fallback
-pub fn read_creation_date_with_fallback(path: &Path, use_fallback: bool) -> Option<DateTime<Local>> {
+/// `use_fallback` tilsvarer `FallbackPolicy::Mtime`, ellers `FallbackPolicy::None`.
+pub fn read_creation_date_with_fallback(path: &Path, use_fallback: bool) -> Option<CreationDate> {
+    let policy = if use_fallback { FallbackPolicy::Mtime } else { FallbackPolicy::None };
+    read_creation_date_with_policy(path, policy)
+}
+
+/// Leser opprettelsesdato, med `policy` for filer uten dato i sidecar, EXIF eller video
+pub fn read_creation_date_with_policy(path: &Path, policy: FallbackPolicy) -> Option<CreationDate> {
+    let found = |date, source| Some(CreationDate { date, source });
+
+    // 0. XMP-sidecar vinner: i RAW-arbeidsflyter er det der den korrigerte datoen ligger
+    if let Some(date) = read_sidecar_date(path) {
+        return found(date, DateSource::Sidecar);
+    }
+
     // 1. Prøv å lese EXIF (Bilder)
-    if let Some(date) = read_exif_date(path) {
-        return Some(Local.from_local_datetime(&date).unwrap());
+    if let Some((date, source)) = read_exif_date(path) {
+        return found(date, source);
     }
 
-    // 2. Prøv å lese Videometadata (FFprobe)
+    // 2. Videocontaineren: MP4/MOV leses direkte, andre formater via FFprobe
+    if let Some(date) = mp4::read_creation_date(path) {
+        return found(date, DateSource::Video);
+    }
     if let Some(date) = read_video_date(path) {
-        return Some(Local.from_local_datetime(&date).unwrap());
+        return found(Local.from_local_datetime(&date).unwrap(), DateSource::Video);
     }
     
-    if !use_fallback {
-        return None;
+    // 3. Fallback til filsystemets datoer
+    match policy {
+        FallbackPolicy::None => None,
+        FallbackPolicy::Mtime => found(read_file_mtime(path)?, DateSource::FileMtime),
+        FallbackPolicy::Ctime => match read_file_ctime(path) {
+            Some(date) => found(date, DateSource::FileCtime),
+            None => found(read_file_mtime(path)?, DateSource::FileMtime),
+        },
     }
-
-    // 3. Fallback til filsystem mtime
-    read_file_mtime(path)
 }
 
 /// Leser opprettelsesdato fra video ved hjelp av FFprobe
@@ -86,31 +218,28 @@ fn parse_ffmpeg_json(output: &[u8]) -> Option<NaiveDateTime> {
     NaiveDateTime::parse_from_str(clean_date, "%Y-%m-%dT%H:%M:%S").ok()
 }
 
-fn read_exif_date(path: &Path) -> Option<NaiveDateTime> {
-    let file = File::open(path).ok()?;
-    let mut bufreader = BufReader::new(&file);
-    let exifreader = exif::Reader::new();
-    let exif = exifreader.read_from_container(&mut bufreader).ok()?;
+/// Leser opprettelsesdato fra EXIF, med brøkdelssekunder og tidssone når de finnes
+fn read_exif_date(path: &Path) -> Option<(DateTime<Local>, DateSource)> {
+    let exif = read_exif(path)?;
 
-    // Prøv forskjellige datofelt i prioritert rekkefølge
+    // Prøv forskjellige datofelt i prioritert rekkefølge, hver med sine SubSec-/Offset-felt
     let date_fields = [
-        exif::Tag::DateTimeOriginal,
-        exif::Tag::DateTimeDigitized,
-        exif::Tag::DateTime,
+        (exif::Tag::DateTimeOriginal, exif::Tag::SubSecTimeOriginal, exif::Tag::OffsetTimeOriginal, DateSource::ExifOriginal),
+        (exif::Tag::DateTimeDigitized, exif::Tag::SubSecTimeDigitized, exif::Tag::OffsetTimeDigitized, DateSource::ExifDigitized),
+        (exif::Tag::DateTime, exif::Tag::SubSecTime, exif::Tag::OffsetTime, DateSource::ExifDateTime),
     ];
 
-    for tag in date_fields {
-        if let Some(field) = exif.get_field(tag, exif::In::PRIMARY) {
-            if let exif::Value::Ascii(ref vec) = field.value {
-                if !vec.is_empty() {
-                    let s = std::str::from_utf8(&vec[0]).ok()?;
-                    // EXIF datoformat: "YYYY:MM:DD HH:MM:SS"
-                    // Vi erstatter første to : med - for å matche ISO 8601 delvis
-                    // Eller bruke chrono sitt format direkte
-                    if let Ok(date) = NaiveDateTime::parse_from_str(s, "%Y:%m:%d %H:%M:%S") {
-                        return Some(date);
-                    }
-                }
+    let ascii_field = |tag| {
+        exif.get_field(tag, exif::In::PRIMARY)
+            .and_then(|f| ascii_value(&f.value))
+    };
+
+    for (date_tag, subsec_tag, offset_tag, source) in date_fields {
+        if let Some(datetime) = ascii_field(date_tag) {
+            let subsec = ascii_field(subsec_tag);
+            let offset = ascii_field(offset_tag);
+            if let Some(date) = parse_exif_datetime(&datetime, subsec.as_deref(), offset.as_deref()) {
+                return Some((date, source));
             }
         }
     }
@@ -118,6 +247,313 @@ fn read_exif_date(path: &Path) -> Option<NaiveDateTime> {
     None
 }
 
+/// Bygger et tidspunkt fra EXIF-felt
+/// `datetime` er "YYYY:MM:DD HH:MM:SS", `subsec` er sifrene etter desimalpunktet ("042" = 42 ms)
+/// og `offset` er "+HH:MM"/"-HH:MM". Uten gyldig offset tolkes tiden som lokal tid.
+fn parse_exif_datetime(datetime: &str, subsec: Option<&str>, offset: Option<&str>) -> Option<DateTime<Local>> {
+    let mut naive = NaiveDateTime::parse_from_str(datetime.trim(), "%Y:%m:%d %H:%M:%S").ok()?;
+
+    if let Some(nanos) = subsec.and_then(parse_subsec_nanos) {
+        naive = naive.with_nanosecond(nanos)?;
+    }
+
+    match offset.and_then(parse_exif_offset) {
+        Some(offset) => Some(offset.from_local_datetime(&naive).single()?.with_timezone(&Local)),
+        None => Local.from_local_datetime(&naive).earliest(),
+    }
+}
+
+/// Tolker EXIF SubSec-sifre som nanosekunder. Ugyldige verdier ignoreres.
+fn parse_subsec_nanos(subsec: &str) -> Option<u32> {
+    let digits = subsec.trim();
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    // Høyst ni sifre (nanosekunder), fylt ut med nuller til høyre
+    let digits: String = digits.chars().take(9).collect();
+    format!("{:0<9}", digits).parse().ok()
+}
+
+/// Tolker EXIF-offset på formen "+HH:MM" eller "-HH:MM"
+fn parse_exif_offset(offset: &str) -> Option<FixedOffset> {
+    let offset = offset.trim();
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':')?;
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Finner XMP-sidecar for et bilde: `bilde.xmp` eller `bilde.cr2.xmp` (også med `.XMP`)
+fn find_xmp_sidecar(path: &Path) -> Option<PathBuf> {
+    let parent = path.parent()?;
+    let stem = path.file_stem()?.to_string_lossy();
+    let filename = path.file_name()?.to_string_lossy();
+
+    [stem.as_ref(), filename.as_ref()]
+        .iter()
+        .flat_map(|base| ["xmp", "XMP"].map(|ext| parent.join(format!("{}.{}", base, ext))))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Leser opprettelsesdato fra en XMP-sidecar ved siden av bildet
+/// Bruker `exif:DateTimeOriginal`, deretter `xmp:CreateDate`. `None` hvis sidecar mangler.
+pub fn read_sidecar_date(path: &Path) -> Option<DateTime<Local>> {
+    let xmp = std::fs::read_to_string(find_xmp_sidecar(path)?).ok()?;
+    ["exif:DateTimeOriginal", "xmp:CreateDate"]
+        .iter()
+        .filter_map(|property| xmp_property(&xmp, property))
+        .find_map(|value| parse_xmp_date(&value))
+}
+
+/// Leser stjernemerking (`xmp:Rating`) fra XMP-sidecar. -1 (avvist) gir `None`.
+pub fn read_sidecar_rating(path: &Path) -> Option<u8> {
+    let xmp = std::fs::read_to_string(find_xmp_sidecar(path)?).ok()?;
+    let rating: i32 = xmp_property(&xmp, "xmp:Rating")?.parse().ok()?;
+    u8::try_from(rating).ok().filter(|r| *r <= 5)
+}
+
+/// Henter en enkel XMP-egenskap, enten som attributt (`xmp:Rating="3"`)
+/// eller som element (`<xmp:Rating>3</xmp:Rating>`)
+fn xmp_property(xmp: &str, name: &str) -> Option<String> {
+    let attribute = format!("{}=", name);
+    if let Some(start) = xmp.find(&attribute) {
+        let rest = &xmp[start + attribute.len()..];
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &rest[1..];
+        return value.find(quote).map(|end| value[..end].trim().to_string());
+    }
+
+    let open = format!("<{}>", name);
+    let start = xmp.find(&open)? + open.len();
+    let end = xmp[start..].find(&format!("</{}>", name))?;
+    Some(xmp[start..start + end].trim().to_string())
+}
+
+/// Tolker XMP-dato (ISO 8601), med eller uten sekunder, brøkdeler og tidssone
+fn parse_xmp_date(value: &str) -> Option<DateTime<Local>> {
+    let value = value.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Local));
+    }
+    if let Ok(date) = DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z") {
+        return Some(date.with_timezone(&Local));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+}
+
+/// Leser EXIF-blokken fra et bilde
+/// TIFF-baserte RAW-filer (CR2, NEF, ARW, DNG) leses direkte; for andre RAW-formater
+/// (f.eks. RAF, CR3) letes det etter en innebygd EXIF-blokk i filinnholdet.
+fn read_exif(path: &Path) -> Option<exif::Exif> {
+    let file = File::open(path).ok()?;
+    let mut bufreader = BufReader::new(&file);
+    let reader = exif::Reader::new();
+    match reader.read_from_container(&mut bufreader) {
+        Ok(exif) => Some(exif),
+        Err(_) if raw::is_raw_file(path) => {
+            let bytes = std::fs::read(path).ok()?;
+            reader.read_raw(raw::find_exif_block(&bytes)?).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+/// Leser EXIF-orientering (1-8) fra bildet
+/// Returnerer `None` hvis bildet mangler EXIF eller har en ugyldig verdi
+pub fn read_orientation(path: &Path) -> Option<u32> {
+    let exif = read_exif(path)?;
+
+    let field = exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?;
+    match field.value.get_uint(0)? {
+        orientation @ 1..=8 => Some(orientation),
+        _ => None,
+    }
+}
+
+/// Henter den innebygde JPEG-thumbnailen (IFD1) fra EXIF, som rå JPEG-data
+/// Thumbnailen er lagret uten rotasjon, så EXIF-orienteringen til hovedbildet gjelder også for den.
+pub fn read_exif_thumbnail(path: &Path) -> Option<Vec<u8>> {
+    let exif = read_exif(path)?;
+    let offset = exif.get_field(exif::Tag::JPEGInterchangeFormat, exif::In::THUMBNAIL)?.value.get_uint(0)?;
+    let length = exif
+        .get_field(exif::Tag::JPEGInterchangeFormatLength, exif::In::THUMBNAIL)?
+        .value
+        .get_uint(0)?;
+    // Offset er relativ til starten av TIFF-dataene, som er det `buf()` inneholder
+    let start = offset as usize;
+    let end = start.checked_add(length as usize)?;
+    exif.buf().get(start..end).map(|bytes| bytes.to_vec())
+}
+
+/// Leser GPS-posisjon fra EXIF som (breddegrad, lengdegrad) i desimalgrader
+/// Returnerer `None` hvis GPS-feltene mangler eller er ugyldige
+pub fn read_gps(path: &Path) -> Option<(f64, f64)> {
+    let exif = read_exif(path)?;
+    gps_from_exif(&exif)
+}
+
+fn gps_from_exif(exif: &exif::Exif) -> Option<(f64, f64)> {
+    let coordinate = |value_tag, ref_tag| {
+        let value = &exif.get_field(value_tag, exif::In::PRIMARY)?.value;
+        let reference = exif
+            .get_field(ref_tag, exif::In::PRIMARY)
+            .and_then(|f| ascii_value(&f.value));
+        dms_to_decimal(value, reference.as_deref())
+    };
+
+    let latitude = coordinate(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef)?;
+    let longitude = coordinate(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef)?;
+
+    if latitude.abs() > 90.0 || longitude.abs() > 180.0 {
+        return None;
+    }
+    Some((latitude, longitude))
+}
+
+/// Konverterer grader/minutter/sekunder til desimalgrader
+/// Referansen "S" eller "W" gir negativt fortegn
+fn dms_to_decimal(value: &exif::Value, reference: Option<&str>) -> Option<f64> {
+    let exif::Value::Rational(ref parts) = *value else {
+        return None;
+    };
+    let part = |i: usize| parts.get(i).map(|r| r.to_f64()).unwrap_or(0.0);
+    let degrees = parts.first()?.to_f64() + part(1) / 60.0 + part(2) / 3600.0;
+    if !degrees.is_finite() {
+        return None;
+    }
+
+    match reference.map(|r| r.trim().to_uppercase()) {
+        Some(r) if r == "S" || r == "W" => Some(-degrees),
+        _ => Some(degrees),
+    }
+}
+
+/// Leser ISO, blender, lukkertid og brennvidde. Felt som mangler blir `None`.
+pub fn read_exposure(path: &Path) -> ExposureInfo {
+    let Some(exif) = read_exif(path) else {
+        return ExposureInfo::default();
+    };
+    let rational_field = |tag| rational_value(&exif.get_field(tag, exif::In::PRIMARY)?.value);
+    ExposureInfo {
+        // `PhotographicSensitivity` er det nyere navnet på `ISOSpeedRatings`
+        iso: exif
+            .get_field(exif::Tag::PhotographicSensitivity, exif::In::PRIMARY)
+            .and_then(|f| f.value.get_uint(0))
+            .filter(|&iso| iso > 0),
+        f_number: rational_field(exif::Tag::FNumber).map(|f| format!("f/{}", format_decimal(f))),
+        exposure_time: rational_field(exif::Tag::ExposureTime).map(format_exposure_time),
+        focal_length: rational_field(exif::Tag::FocalLength).map(|mm| format!("{} mm", format_decimal(mm))),
+    }
+}
+
+/// Første rasjonale verdi i et EXIF-felt, `None` for 0/0 og negative verdier
+fn rational_value(value: &exif::Value) -> Option<f64> {
+    let exif::Value::Rational(ref parts) = *value else {
+        return None;
+    };
+    let value = parts.first()?.to_f64();
+    (value.is_finite() && value > 0.0).then_some(value)
+}
+
+/// Korte tider som brøk ("1/250"), lange i sekunder ("2s", "1.5s")
+fn format_exposure_time(seconds: f64) -> String {
+    if seconds < 1.0 {
+        format!("1/{}", (1.0 / seconds).round())
+    } else {
+        format!("{}s", format_decimal(seconds))
+    }
+}
+
+/// Én desimal, uten ".0" for hele tall
+fn format_decimal(value: f64) -> String {
+    let formatted = format!("{:.1}", value);
+    formatted.strip_suffix(".0").unwrap_or(&formatted).to_string()
+}
+
+/// Henter første ASCII-verdi fra et EXIF-felt, uten avsluttende NUL og mellomrom
+fn ascii_value(value: &exif::Value) -> Option<String> {
+    let exif::Value::Ascii(ref vec) = *value else {
+        return None;
+    };
+    let s = std::str::from_utf8(vec.first()?).ok()?;
+    let s = s.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+/// Leser kameraprodusent, -modell og objektiv. Felt som mangler blir `None`.
+pub fn read_camera_info(path: &Path) -> CameraInfo {
+    read_exif(path).map(|exif| camera_info_from_exif(&exif)).unwrap_or_default()
+}
+
+fn camera_info_from_exif(exif: &exif::Exif) -> CameraInfo {
+    let ascii_field = |tag| {
+        exif.get_field(tag, exif::In::PRIMARY)
+            .and_then(|f| ascii_value(&f.value))
+    };
+    CameraInfo {
+        make: ascii_field(exif::Tag::Make),
+        model: ascii_field(exif::Tag::Model),
+        lens: ascii_field(exif::Tag::LensModel),
+    }
+}
+
+/// Leser dato, GPS, kamera og dimensjoner for ett bilde
+/// Manglende felt blir `None`; kun en fil som ikke finnes gir feil.
+pub fn read_image_metadata(path: &Path) -> Result<ImageMetadata, Box<dyn std::error::Error>> {
+    if !path.exists() {
+        let message = format!("Filen finnes ikke: {}", path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, message).into());
+    }
+
+    let creation_date = read_creation_date(path);
+    let mut metadata = ImageMetadata {
+        date: creation_date.map(|d| d.date.to_rfc3339()),
+        date_source: creation_date.map(|d| d.source),
+        rating: read_sidecar_rating(path),
+        exposure: read_exposure(path),
+        ..Default::default()
+    };
+
+    if let Ok((width, height)) = image::image_dimensions(path) {
+        metadata.width = Some(width);
+        metadata.height = Some(height);
+    }
+
+    if let Some(exif) = read_exif(path) {
+        let camera = camera_info_from_exif(&exif);
+        metadata.camera_make = camera.make;
+        metadata.camera_model = camera.model;
+        metadata.lens = camera.lens;
+        metadata.gps = gps_from_exif(&exif).map(|(latitude, longitude)| GpsCoordinates {
+            latitude,
+            longitude,
+        });
+    }
+
+    Ok(metadata)
+}
+
+/// Opprettelsesdato fra filsystemet. `None` der plattformen eller filsystemet ikke har den.
+fn read_file_ctime(path: &Path) -> Option<DateTime<Local>> {
+    let created = std::fs::metadata(path).ok()?.created().ok()?;
+    Some(created.into())
+}
+
 fn read_file_mtime(path: &Path) -> Option<DateTime<Local>> {
     let metadata = std::fs::metadata(path).ok()?;
     let modified = metadata.modified().ok()?;
@@ -125,10 +561,74 @@ fn read_file_mtime(path: &Path) -> Option<DateTime<Local>> {
     Some(datetime)
 }
 
+/// Filtyper `write_exif_date` kan skrive til
+const EXIF_WRITABLE: &[&str] = &["jpg", "jpeg", "tif", "tiff"];
+
+/// Skriver `date` som EXIF DateTimeOriginal (med OffsetTimeOriginal) i bildet
+/// Andre EXIF-felt beholdes, og filens mtime settes tilbake etterpå slik at
+/// skrivingen ikke endrer datoen andre verktøy ser. Kun JPEG og TIFF støttes.
+pub fn write_exif_date(path: &Path, date: DateTime<Local>) -> Result<(), Box<dyn std::error::Error>> {
+    use little_exif::exif_tag::ExifTag;
+    use little_exif::metadata::Metadata;
+
+    let ext = path.extension().unwrap_or_default().to_string_lossy().to_lowercase();
+    if !EXIF_WRITABLE.contains(&ext.as_str()) {
+        return Err(format!("Kan ikke skrive EXIF til .{}-filer (kun JPEG og TIFF): {}", ext, path.display()).into());
+    }
+
+    let mtime = filetime::FileTime::from_last_modification_time(&std::fs::metadata(path)?);
+
+    // Bare filer helt uten EXIF starter med tom metadata; en EXIF-blokk som ikke kan leses
+    // gir feil, ellers ville kamera, GPS, orientering osv. blitt skrevet over
+    let mut exif = if has_exif_segment(path)? { Metadata::new_from_path(path)? } else { Metadata::new() };
+    exif.set_tag(ExifTag::DateTimeOriginal(date.format("%Y:%m:%d %H:%M:%S").to_string()));
+    exif.set_tag(ExifTag::OffsetTimeOriginal(date.format("%:z").to_string()));
+    exif.write_to_file(path)?;
+
+    filetime::set_file_mtime(path, mtime)?;
+    Ok(())
+}
+
+/// `true` hvis filen har en EXIF-blokk, feil hvis den ikke kan leses
+fn has_exif_segment(path: &Path) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    match exif::Reader::new().read_from_container(&mut reader) {
+        Ok(_) => Ok(true),
+        Err(exif::Error::NotFound(_)) => Ok(false),
+        Err(e) => Err(format!("Ugyldig EXIF i {}: {}", path.display(), e).into()),
+    }
+}
+
+/// Skriver inn datoen sorteringen ville brukt (sidecar, video eller mtime) som EXIF
+/// i filer som mangler EXIF-dato, slik at de sorteres likt på andre maskiner.
+/// Filer som allerede har en EXIF-dato hoppes over (`skipped`), også når en sidecar har
+/// en annen dato.
+pub fn backfill_exif_dates(paths: &[String]) -> OperationResult {
+    let mut result = OperationResult::new();
+    result.processed = paths.len();
+
+    for path_str in paths {
+        let path = path_codec::decode(path_str);
+        let path = path.as_path();
+        if read_exif_date(path).is_some() {
+            result.add_skipped(path_str);
+            continue;
+        }
+        match read_creation_date(path) {
+            Some(found) => match write_exif_date(path, found.date) {
+                Ok(()) => result.add_success(),
+                Err(e) => result.add_error(format!("Kunne ikke skrive dato til {}: {}", path_str, e)),
+            },
+            None => result.add_error(format!("Fant ingen dato for {}", path_str)),
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
+    use std::fs::{self, File};
     use std::io::Write;
     use tempfile::tempdir;
 
@@ -143,7 +643,229 @@ mod tests {
         
         // Sjekk at datoen er nylig (innenfor siste minutt)
         let now = Local::now();
-        let diff = now.signed_duration_since(date.unwrap());
+        let diff = now.signed_duration_since(date.unwrap().date);
         assert!(diff.num_seconds().abs() < 60);
     }
+
+    #[test]
+    fn test_read_orientation_without_exif() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("no_exif.jpg");
+        File::create(&file_path).unwrap().write_all(b"ikke et bilde").unwrap();
+
+        assert_eq!(read_orientation(&file_path), None);
+    }
+
+    #[test]
+    fn test_parse_exif_datetime_with_subsec_and_offset() {
+        let date = parse_exif_datetime("2023:12:31 23:30:00", Some("250"), Some("+09:00")).unwrap();
+        let expected = FixedOffset::east_opt(9 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2023, 12, 31, 23, 30, 0)
+            .unwrap();
+        assert_eq!(date.timestamp(), expected.timestamp());
+        assert_eq!(date.timestamp_subsec_millis(), 250);
+    }
+
+    #[test]
+    fn test_parse_exif_datetime_burst_order() {
+        let first = parse_exif_datetime("2024:05:01 10:00:00", Some("05"), None).unwrap();
+        let second = parse_exif_datetime("2024:05:01 10:00:00", Some("5"), None).unwrap();
+        assert!(first < second, "0.05 s skal komme før 0.5 s");
+    }
+
+    #[test]
+    fn test_parse_exif_datetime_falls_back_to_local() {
+        let date = parse_exif_datetime("2024:05:01 10:00:00", Some("abc"), Some("ukjent")).unwrap();
+        let naive = NaiveDateTime::parse_from_str("2024:05:01 10:00:00", "%Y:%m:%d %H:%M:%S").unwrap();
+        assert_eq!(date.naive_local(), naive);
+        assert!(parse_exif_datetime("ikke en dato", None, None).is_none());
+    }
+
+    #[test]
+    fn test_sidecar_date_preferred() {
+        let dir = tempdir().unwrap();
+        let image = dir.path().join("IMG_0001.CR2");
+        File::create(&image).unwrap();
+        assert!(read_sidecar_date(&image).is_none());
+
+        fs::write(
+            dir.path().join("IMG_0001.CR2.xmp"),
+            r#"<rdf:Description xmp:Rating="4" xmp:CreateDate="2020-01-01T00:00:00">
+                 <exif:DateTimeOriginal>2019-07-14T18:05:12.50+02:00</exif:DateTimeOriginal>
+               </rdf:Description>"#,
+        )
+        .unwrap();
+
+        let expected = FixedOffset::east_opt(2 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2019, 7, 14, 18, 5, 12)
+            .unwrap();
+        let date = read_creation_date(&image).unwrap();
+        assert_eq!(date.date.timestamp(), expected.timestamp());
+        assert_eq!(date.source, DateSource::Sidecar);
+        assert_eq!(read_sidecar_rating(&image), Some(4));
+    }
+
+    #[test]
+    fn test_sidecar_create_date_fallback() {
+        let dir = tempdir().unwrap();
+        let image = dir.path().join("photo.nef");
+        File::create(&image).unwrap();
+        fs::write(dir.path().join("photo.xmp"), r#"<x xmp:CreateDate='2018-03-02T10:20'/>"#).unwrap();
+
+        let date = read_sidecar_date(&image).unwrap();
+        assert_eq!(date.naive_local().to_string(), "2018-03-02 10:20:00");
+    }
+
+    #[test]
+    fn test_date_source() {
+        let dir = tempdir().unwrap();
+        let image = dir.path().join("photo.jpg");
+        File::create(&image).unwrap();
+
+        let date = read_creation_date_with_fallback(&image, true).unwrap();
+        assert_eq!(date.source, DateSource::FileMtime);
+        assert!(date.source.is_unreliable());
+        assert!(read_creation_date_with_fallback(&image, false).is_none());
+
+        fs::write(dir.path().join("photo.xmp"), r#"<x xmp:CreateDate='2018-03-02T10:20'/>"#).unwrap();
+        let date = read_creation_date_with_fallback(&image, false).unwrap();
+        assert_eq!(date.source, DateSource::Sidecar);
+        assert_eq!(date.source.category(), "sidecar");
+    }
+
+    #[test]
+    fn test_fallback_policy() {
+        let dir = tempdir().unwrap();
+        let image = dir.path().join("photo.jpg");
+        File::create(&image).unwrap();
+
+        assert!(read_creation_date_with_policy(&image, FallbackPolicy::None).is_none());
+        let mtime = read_creation_date_with_policy(&image, FallbackPolicy::Mtime).unwrap();
+        assert_eq!(mtime.source, DateSource::FileMtime);
+
+        // Uten støtte for opprettelsesdato faller Ctime tilbake til endringsdatoen
+        let ctime = read_creation_date_with_policy(&image, FallbackPolicy::Ctime).unwrap();
+        let expected = if fs::metadata(&image).unwrap().created().is_ok() {
+            DateSource::FileCtime
+        } else {
+            DateSource::FileMtime
+        };
+        assert_eq!(ctime.source, expected);
+        assert!(ctime.source.is_unreliable());
+    }
+
+    #[test]
+    fn test_dms_to_decimal() {
+        let value = exif::Value::Rational(vec![(59, 1).into(), (54, 1).into(), (3600, 100).into()]);
+
+        let north = dms_to_decimal(&value, Some("N")).unwrap();
+        assert!((north - 59.91).abs() < 1e-9);
+
+        let south = dms_to_decimal(&value, Some("S")).unwrap();
+        assert!((south + 59.91).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_dms_to_decimal_invalid() {
+        assert_eq!(dms_to_decimal(&exif::Value::Rational(vec![]), Some("N")), None);
+        assert_eq!(dms_to_decimal(&exif::Value::Rational(vec![(1, 0).into()]), None), None);
+        assert_eq!(dms_to_decimal(&exif::Value::Short(vec![59]), None), None);
+    }
+
+    #[test]
+    fn test_ascii_value_trims_padding() {
+        let value = exif::Value::Ascii(vec![b"Canon EOS R5 \0\0".to_vec()]);
+        assert_eq!(ascii_value(&value), Some("Canon EOS R5".to_string()));
+
+        let blank = exif::Value::Ascii(vec![b"   \0".to_vec()]);
+        assert_eq!(ascii_value(&blank), None);
+    }
+
+    #[test]
+    fn test_read_camera_info_without_exif() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("no_exif.txt");
+        File::create(&file_path).unwrap().write_all(b"test").unwrap();
+
+        assert_eq!(read_camera_info(&file_path), CameraInfo::default());
+    }
+
+    #[test]
+    fn test_exposure_formatting() {
+        let rational = |num, denom| exif::Value::Rational(vec![exif::Rational { num, denom }]);
+        assert_eq!(rational_value(&rational(28, 10)).map(format_decimal), Some("2.8".to_string()));
+        assert_eq!(rational_value(&rational(0, 0)), None);
+
+        assert_eq!(format_exposure_time(1.0 / 250.0), "1/250");
+        assert_eq!(format_exposure_time(10.0 / 3000.0), "1/300");
+        assert_eq!(format_exposure_time(2.0), "2s");
+        assert_eq!(format_exposure_time(1.5), "1.5s");
+        assert_eq!(format_decimal(50.0), "50");
+    }
+
+    #[test]
+    fn test_read_gps_without_exif() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("no_exif.jpg");
+        File::create(&file_path).unwrap().write_all(b"ikke et bilde").unwrap();
+
+        assert_eq!(read_gps(&file_path), None);
+    }
+
+    #[test]
+    fn test_write_exif_date() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("scan.jpg");
+        image::RgbImage::new(16, 16).save(&file_path).unwrap();
+        let mtime = filetime::FileTime::from_unix_time(1_500_000_000, 0);
+        filetime::set_file_mtime(&file_path, mtime).unwrap();
+
+        let result = backfill_exif_dates(&[file_path.to_string_lossy().to_string()]);
+        assert_eq!(result.success, 1, "{:?}", result.error_messages);
+
+        let found = read_creation_date(&file_path).unwrap();
+        assert_eq!(found.source, DateSource::ExifOriginal);
+        assert_eq!(found.date.timestamp(), 1_500_000_000);
+        let after = filetime::FileTime::from_last_modification_time(&fs::metadata(&file_path).unwrap());
+        assert_eq!(after, mtime);
+
+        // Andre gang har filen EXIF-dato og hoppes over, også når sidecaren har en annen dato
+        fs::write(dir.path().join("scan.xmp"), r#"<x exif:DateTimeOriginal="2010-05-01T10:00:00"/>"#).unwrap();
+        assert_eq!(read_creation_date(&file_path).unwrap().source, DateSource::Sidecar);
+        assert_eq!(backfill_exif_dates(&[file_path.to_string_lossy().to_string()]).skipped.len(), 1);
+        assert_eq!(read_exif_date(&file_path).unwrap().0.timestamp(), 1_500_000_000);
+
+        let png = dir.path().join("skjerm.png");
+        image::RgbImage::new(4, 4).save(&png).unwrap();
+        assert!(write_exif_date(&png, Local::now()).is_err());
+
+        // En EXIF-blokk som ikke kan leses skal ikke erstattes med en tom en
+        let broken = dir.path().join("ødelagt.jpg");
+        let jpeg = fs::read(&file_path).unwrap();
+        let mut bytes = jpeg[..2].to_vec();
+        bytes.extend_from_slice(&[0xFF, 0xE1, 0x00, 0x10]);
+        bytes.extend_from_slice(b"Exif\0\0XXXXXXXX");
+        bytes.extend_from_slice(&jpeg[2..]);
+        fs::write(&broken, &bytes).unwrap();
+        assert!(write_exif_date(&broken, Local::now()).is_err());
+        assert_eq!(fs::read(&broken).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_read_image_metadata_dimensions() {
+        let dir = tempdir().unwrap();
+        let file_path = dir.path().join("bilde.png");
+        image::RgbImage::new(40, 30).save(&file_path).unwrap();
+
+        let metadata = read_image_metadata(&file_path).unwrap();
+        assert_eq!((metadata.width, metadata.height), (Some(40), Some(30)));
+        assert!(metadata.gps.is_none());
+        assert!(metadata.camera_make.is_none());
+        assert_eq!(metadata.exposure, ExposureInfo::default());
+        assert!(metadata.date.is_some());
+
+        assert!(read_image_metadata(&dir.path().join("mangler.png")).is_err());
+    }
 }
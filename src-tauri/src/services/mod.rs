@@ -1,5 +1,8 @@
 //! Backend-tjenester for bildebehandling
 
+pub mod cancellation;
+pub mod scan_cache;
+pub mod duplicates;
 pub mod hashing;
 pub mod scanner;
 pub mod thumbnail;
@@ -7,4 +10,16 @@ pub mod metadata;
 pub mod sorter;
 pub mod cache;
 pub mod sidecar;
-
+pub mod template;
+pub mod journal;
+pub mod raw;
+pub mod heic;
+pub mod jpeg;
+pub mod converter;
+pub mod stats;
+pub mod problems;
+pub mod path_codec;
+pub mod location;
+pub mod mp4;
+pub mod date_audit;
+pub mod aspect;
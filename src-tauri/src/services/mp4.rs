@@ -0,0 +1,241 @@
+//! Opprettelsesdato fra MP4/MOV-containere uten ffprobe
+//!
+//! Leser kun `moov`-boksen. iPhone og mange kameraer skriver opptakstidspunktet med
+//! tidssone i `com.apple.quicktime.creationdate` (under `moov/meta`), som foretrekkes.
+//! Ellers brukes `creation_time` i `mvhd`, som er UTC og 0 når den ikke er satt.
+
+use chrono::{DateTime, FixedOffset, Local};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Filendelser med ISO BMFF/QuickTime-container
+pub const MP4_EXTENSIONS: &[&str] = &["mp4", "mov", "m4v", "3gp"];
+
+/// Større `moov` enn dette leses ikke (normalt noen hundre kB, også for lange videoer)
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+const APPLE_CREATION_DATE_KEY: &[u8] = b"com.apple.quicktime.creationdate";
+
+/// Sekunder fra QuickTime-epoken (1904-01-01) til Unix-epoken
+const QUICKTIME_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Sjekker om en sti har MP4/MOV-filendelse
+pub fn is_mp4_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| MP4_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Opptakstidspunktet fra containeren, `None` for andre formater eller når det mangler
+pub fn read_creation_date(path: &Path) -> Option<DateTime<Local>> {
+    if !is_mp4_file(path) {
+        return None;
+    }
+    let moov = read_moov(path)?;
+    apple_creation_date(&moov).or_else(|| mvhd_creation_date(&moov))
+}
+
+/// Innholdet i `moov`, som kan ligge før eller etter `mdat`
+/// Bokser som er større enn resten av filen regnes som ødelagte og avslutter lesingen.
+fn read_moov(path: &Path) -> Option<Vec<u8>> {
+    let mut file = File::open(path).ok()?;
+    let file_len = file.metadata().ok()?.len();
+    let mut offset: u64 = 0;
+    while file_len.checked_sub(offset)? >= 8 {
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8]).ok()?;
+        let (size, header_len) = match u32::from_be_bytes(header[0..4].try_into().ok()?) {
+            0 => (file_len - offset, 8),
+            1 => {
+                file.read_exact(&mut header[8..16]).ok()?;
+                (u64::from_be_bytes(header[8..16].try_into().ok()?), 16)
+            }
+            size => (size as u64, 8),
+        };
+        if size < header_len || size > file_len - offset {
+            return None;
+        }
+        if &header[4..8] == b"moov" {
+            let body_len = size - header_len;
+            if body_len > MAX_MOOV_SIZE {
+                return None;
+            }
+            let mut body = vec![0; body_len as usize];
+            file.read_exact(&mut body).ok()?;
+            return Some(body);
+        }
+        offset = offset.checked_add(size)?;
+    }
+    None
+}
+
+/// Barneboksene i `data` som (type, innhold). Stopper ved første ugyldige boks.
+fn child_boxes(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let (size, header_len) = match read_u32(data, pos) {
+            Some(0) => (data.len() - pos, 8),
+            Some(1) => match data.get(pos + 8..pos + 16) {
+                Some(large) => (u64::from_be_bytes(large.try_into().unwrap()) as usize, 16),
+                None => break,
+            },
+            Some(size) => (size as usize, 8),
+            None => break,
+        };
+        if size < header_len || size > data.len() - pos {
+            break;
+        }
+        boxes.push((&data[pos + 4..pos + 8], &data[pos + header_len..pos + size]));
+        pos += size;
+    }
+    boxes
+}
+
+fn find_box<'a>(data: &'a [u8], kind: &[u8]) -> Option<&'a [u8]> {
+    child_boxes(data).into_iter().find(|(k, _)| *k == kind).map(|(_, body)| body)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+/// `creation_time` i `mvhd`: sekunder siden 1904 i UTC, 32 eller 64 bit etter versjon
+fn mvhd_creation_date(moov: &[u8]) -> Option<DateTime<Local>> {
+    let mvhd = find_box(moov, b"mvhd")?;
+    let seconds = match mvhd.first()? {
+        0 => read_u32(mvhd, 4)? as i64,
+        1 => i64::try_from(u64::from_be_bytes(mvhd.get(4..12)?.try_into().ok()?)).ok()?,
+        _ => return None,
+    };
+    if seconds == 0 {
+        return None;
+    }
+    DateTime::from_timestamp(seconds - QUICKTIME_EPOCH_OFFSET, 0).map(|date| date.with_timezone(&Local))
+}
+
+/// `com.apple.quicktime.creationdate` fra `moov/meta` (nøkler i `keys`, verdier i `ilst`)
+fn apple_creation_date(moov: &[u8]) -> Option<DateTime<Local>> {
+    let meta = find_box(moov, b"meta")?;
+    // QuickTime-varianten har ikke versjon og flagg foran barneboksene, ISO-varianten har det
+    let meta = if find_box(meta, b"keys").is_some() { meta } else { meta.get(4..)? };
+    let keys = find_box(meta, b"keys")?;
+    let ilst = find_box(meta, b"ilst")?;
+
+    let count = read_u32(keys, 4)?;
+    let mut pos = 8;
+    let mut index = None;
+    for key_index in 1..=count {
+        let size = read_u32(keys, pos)? as usize;
+        if size < 8 {
+            return None;
+        }
+        if keys.get(pos + 8..pos + size)? == APPLE_CREATION_DATE_KEY {
+            index = Some(key_index);
+            break;
+        }
+        pos += size;
+    }
+    let index = index?.to_be_bytes();
+
+    let item = find_box(ilst, &index)?;
+    let data = find_box(item, b"data")?;
+    // Typeindikator (1 = UTF-8) og locale før selve verdien
+    if read_u32(data, 0)? != 1 {
+        return None;
+    }
+    parse_creation_date(std::str::from_utf8(data.get(8..)?).ok()?)
+}
+
+/// Apple skriver `2023-06-01T12:34:56+0200`; RFC 3339 godtas også
+fn parse_creation_date(value: &str) -> Option<DateTime<Local>> {
+    let value = value.trim_end_matches('\0').trim();
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::<FixedOffset>::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%z"))
+        .ok()
+        .map(|date| date.with_timezone(&Local))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::metadata::{self, DateSource};
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    fn mp4_box(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut data = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn mvhd(creation_time: u32) -> Vec<u8> {
+        let mut body = vec![0u8; 100];
+        body[4..8].copy_from_slice(&creation_time.to_be_bytes());
+        mp4_box(b"mvhd", &body)
+    }
+
+    #[test]
+    fn test_corrupt_box_sizes() {
+        let dir = tempdir().unwrap();
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\0\0isom");
+        let video = dir.path().join("ødelagt.mp4");
+
+        // 64-bit størrelse nær u64::MAX skal ikke gi overflyt eller evig løkke
+        let mut huge = 1u32.to_be_bytes().to_vec();
+        huge.extend_from_slice(b"free");
+        huge.extend_from_slice(&(u64::MAX - 4).to_be_bytes());
+        std::fs::write(&video, [ftyp.clone(), huge].concat()).unwrap();
+        assert_eq!(read_moov(&video), None);
+
+        // Boks som er lengre enn filen
+        let mut truncated = mp4_box(b"moov", &mvhd(1));
+        truncated.truncate(40);
+        std::fs::write(&video, [ftyp, truncated].concat()).unwrap();
+        assert_eq!(read_moov(&video), None);
+    }
+
+    #[test]
+    fn test_read_creation_date_from_container() {
+        let dir = tempdir().unwrap();
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\0\0isom");
+        let mdat = mp4_box(b"mdat", &[0u8; 64]);
+        let utc = chrono::Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+
+        // mvhd alene, med moov etter mdat
+        let mvhd_time = (utc.timestamp() + QUICKTIME_EPOCH_OFFSET) as u32;
+        let video = dir.path().join("clip.mp4");
+        std::fs::write(&video, [ftyp.clone(), mdat.clone(), mp4_box(b"moov", &mvhd(mvhd_time))].concat()).unwrap();
+        assert_eq!(read_creation_date(&video), Some(utc.with_timezone(&Local)));
+        let found = metadata::read_creation_date(&video).unwrap();
+        assert_eq!(found.source, DateSource::Video);
+
+        // Apple-nøkkelen vinner over mvhd
+        let mut keys = vec![0u8; 4];
+        keys.extend_from_slice(&1u32.to_be_bytes());
+        keys.extend_from_slice(&((APPLE_CREATION_DATE_KEY.len() + 8) as u32).to_be_bytes());
+        keys.extend_from_slice(b"mdta");
+        keys.extend_from_slice(APPLE_CREATION_DATE_KEY);
+        let mut data = 1u32.to_be_bytes().to_vec();
+        data.extend_from_slice(&[0u8; 4]);
+        data.extend_from_slice(b"2023-06-01T12:34:56+0200");
+        let ilst = mp4_box(b"ilst", &mp4_box(&1u32.to_be_bytes(), &mp4_box(b"data", &data)));
+        let meta = mp4_box(b"meta", &[mp4_box(b"keys", &keys), ilst].concat());
+        let moov = mp4_box(b"moov", &[mvhd(mvhd_time), meta].concat());
+        let mov = dir.path().join("IMG_0001.MOV");
+        std::fs::write(&mov, [ftyp, moov, mdat].concat()).unwrap();
+        let expected = FixedOffset::east_opt(2 * 3600).unwrap().with_ymd_and_hms(2023, 6, 1, 12, 34, 56).unwrap();
+        assert_eq!(read_creation_date(&mov), Some(expected.with_timezone(&Local)));
+
+        // Uten moov, eller med mvhd lik 0, finnes ingen dato
+        let empty = dir.path().join("empty.mp4");
+        std::fs::write(&empty, mp4_box(b"moov", &mvhd(0))).unwrap();
+        assert_eq!(read_creation_date(&empty), None);
+        std::fs::write(&empty, b"ikke en video").unwrap();
+        assert_eq!(read_creation_date(&empty), None);
+    }
+}
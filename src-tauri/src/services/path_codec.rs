@@ -0,0 +1,116 @@
+//! Tapsfri konvertering mellom filstier og strenger for frontend
+//!
+//! Filnavn kan inneholde vilkårlige byte på Linux (og uparede surrogater på Windows), som
+//! ikke kan sendes som JSON. `to_string_lossy` bytter dem ut med U+FFFD, og stien peker da
+//! ikke lenger på filen. `encode` legger i stedet hver ugyldig byte inn som et tegn i det
+//! private området øverst i Unicode, og `decode` gjør det om igjen.
+//!
+//! Gyldige UTF-8-stier er uendret. Et ekte filnavn med tegn i escape-området (fra
+//! `ESCAPE_BASE` og oppover: U+10FF00 på Unix, U+10F800 på Windows) vil bli tolket feil,
+//! men disse tegnene brukes ikke i praksis.
+
+use std::path::{Path, PathBuf};
+
+/// Første tegn i escape-området: ugyldig byte `b` (alltid >= 0x80) blir `ESCAPE_BASE + b`
+#[cfg(unix)]
+const ESCAPE_BASE: u32 = 0x10FF00;
+
+/// Første tegn i escape-området: uparet surrogat `s` (0xD800-0xDFFF) blir `ESCAPE_BASE + s - 0xD800`
+#[cfg(windows)]
+const ESCAPE_BASE: u32 = 0x10F800;
+
+/// Sti til streng for frontend, se modulkommentaren
+#[cfg(unix)]
+pub fn encode(path: &Path) -> String {
+    use std::os::unix::ffi::OsStrExt;
+
+    let bytes = path.as_os_str().as_bytes();
+    if let Ok(valid) = std::str::from_utf8(bytes) {
+        return valid.to_string();
+    }
+    let mut encoded = String::with_capacity(bytes.len() + 8);
+    for chunk in bytes.utf8_chunks() {
+        encoded.push_str(chunk.valid());
+        for &byte in chunk.invalid() {
+            encoded.extend(char::from_u32(ESCAPE_BASE + byte as u32));
+        }
+    }
+    encoded
+}
+
+/// Streng fra `encode` (eller vanlig sti fra frontend) tilbake til sti
+#[cfg(unix)]
+pub fn decode(value: &str) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    if !value.chars().any(|c| c as u32 >= ESCAPE_BASE) {
+        return PathBuf::from(value);
+    }
+    let mut bytes = Vec::with_capacity(value.len());
+    for c in value.chars() {
+        if c as u32 >= ESCAPE_BASE {
+            bytes.push((c as u32 - ESCAPE_BASE) as u8);
+        } else {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+    }
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+#[cfg(windows)]
+pub fn encode(path: &Path) -> String {
+    use std::os::windows::ffi::OsStrExt;
+
+    char::decode_utf16(path.as_os_str().encode_wide())
+        .map(|c| match c {
+            Ok(c) => c,
+            Err(e) => char::from_u32(ESCAPE_BASE + e.unpaired_surrogate() as u32 - 0xD800).unwrap_or('\u{FFFD}'),
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+pub fn decode(value: &str) -> PathBuf {
+    use std::os::windows::ffi::OsStringExt;
+
+    if !value.chars().any(|c| c as u32 >= ESCAPE_BASE) {
+        return PathBuf::from(value);
+    }
+    let mut wide = Vec::with_capacity(value.len());
+    for c in value.chars() {
+        if c as u32 >= ESCAPE_BASE {
+            wide.push((c as u32 - ESCAPE_BASE + 0xD800) as u16);
+        } else {
+            wide.extend_from_slice(c.encode_utf16(&mut [0; 2]));
+        }
+    }
+    PathBuf::from(std::ffi::OsString::from_wide(&wide))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn encode(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn decode(value: &str) -> PathBuf {
+    PathBuf::from(value)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[test]
+    fn test_roundtrip_invalid_utf8() {
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"/bilder/ferie_\xff\xfe_\xc3.jpg"));
+        let encoded = encode(path);
+        assert!(encoded.starts_with("/bilder/ferie_") && encoded.ends_with(".jpg"));
+        assert!(!encoded.contains('\u{FFFD}'));
+        assert_eq!(decode(&encoded), path);
+
+        assert_eq!(encode(Path::new("/bilder/blåbær.jpg")), "/bilder/blåbær.jpg");
+        assert_eq!(decode("/bilder/blåbær.jpg"), Path::new("/bilder/blåbær.jpg"));
+    }
+}
@@ -0,0 +1,142 @@
+//! Finner ødelagte og ubrukelige bilder: tomme filer, avbrutte nedlastinger og
+//! miniatyrer som ikke er verdt å sortere.
+//!
+//! Hver fil sjekkes først kun via headeren (`imagesize`). Full dekoding gjøres bare
+//! når headeren mangler eller filen er mistenkelig liten for dimensjonene.
+
+use crate::services::scanner::{self, MediaType};
+use crate::services::{heic, path_codec, raw};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+
+/// Standard minste bredde/høyde før et bilde regnes som for lite
+pub const DEFAULT_MIN_DIMENSION: u32 = 32;
+
+/// Flere piksler per byte enn dette tyder på at filen er avkortet
+/// Selv ensfargede JPEG-er ligger godt over, så bare ødelagte filer dekodes i praksis.
+const SUSPICIOUS_PIXELS_PER_BYTE: u64 = 100;
+
+/// Hvorfor et bilde er markert som problematisk
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ProblemReason {
+    /// Tom fil
+    ZeroBytes,
+    /// Headeren eller bildedataene kan ikke leses
+    DecodeFailed { message: String },
+    /// Mindre enn `min_dimension` i bredde eller høyde
+    TooSmall { width: u32, height: u32 },
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ProblemImage {
+    pub path: String,
+    pub reason: ProblemReason,
+}
+
+/// Sjekker bildene parallelt og returnerer de problematiske, i samme rekkefølge som `paths`
+/// Videoer og filer som ikke finnes hoppes over.
+pub fn find_problem_images(paths: &[String], min_dimension: u32) -> Vec<ProblemImage> {
+    paths
+        .par_iter()
+        .filter(|path| {
+            let ext = Path::new(path).extension().unwrap_or_default().to_string_lossy();
+            MediaType::from_extension(&ext) == MediaType::Image
+        })
+        .filter_map(|path| {
+            let reason = check_image(&path_codec::decode(path), min_dimension)?;
+            Some(ProblemImage { path: path.clone(), reason })
+        })
+        .collect()
+}
+
+fn check_image(path: &Path, min_dimension: u32) -> Option<ProblemReason> {
+    let size_bytes = std::fs::metadata(path).ok()?.len();
+    if size_bytes == 0 {
+        return Some(ProblemReason::ZeroBytes);
+    }
+
+    let dimensions = scanner::read_dimensions(path);
+    let suspicious = match dimensions {
+        Some((width, height)) => {
+            width == 0 || height == 0 || size_bytes < width as u64 * height as u64 / SUSPICIOUS_PIXELS_PER_BYTE
+        }
+        None => true,
+    };
+    if suspicious {
+        if let Err(message) = full_decode(path) {
+            return Some(ProblemReason::DecodeFailed { message });
+        }
+    }
+
+    match dimensions {
+        Some((width, height)) if width < min_dimension || height < min_dimension => {
+            Some(ProblemReason::TooSmall { width, height })
+        }
+        _ => None,
+    }
+}
+
+/// Dekoder hele bildet (RAW via innebygd forhåndsvisning)
+fn full_decode(path: &Path) -> Result<(), String> {
+    if raw::is_raw_file(path) {
+        raw::extract_preview(path).map(|_| ()).map_err(|e| e.to_string())
+    } else if heic::is_heic_file(path) {
+        // Uten libheif kan ikke HEIC dekodes; da stoler vi på headeren alene
+        if cfg!(feature = "heic") || scanner::read_dimensions(path).is_none() {
+            heic::decode(path).map(|_| ()).map_err(|e| e.to_string())
+        } else {
+            Ok(())
+        }
+    } else {
+        image::io::Reader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| e.to_string())?
+            .decode()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_problem_images() {
+        let dir = tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+
+        fs::write(path("tom.jpg"), b"").unwrap();
+        fs::write(path("søppel.jpg"), b"ikke et bilde").unwrap();
+        image::RgbImage::new(8, 8).save(path("ikon.png")).unwrap();
+        image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([x as u8 * 4, y as u8 * 4, 0]))
+            .save(path("ok.png"))
+            .unwrap();
+        // Avbrutt nedlasting: gyldig header, men bildedataene mangler
+        image::RgbImage::new(800, 800).save(path("stor.png")).unwrap();
+        let big = fs::read(path("stor.png")).unwrap();
+        fs::write(path("avkortet.png"), &big[..40]).unwrap();
+        fs::write(path("video.mp4"), b"").unwrap();
+
+        let paths: Vec<String> = ["tom.jpg", "søppel.jpg", "ikon.png", "ok.png", "avkortet.png", "video.mp4"]
+            .iter()
+            .map(|name| path(name))
+            .collect();
+        let problems = find_problem_images(&paths, DEFAULT_MIN_DIMENSION);
+        let reasons: Vec<(&str, &ProblemReason)> = problems
+            .iter()
+            .map(|p| (Path::new(&p.path).file_name().unwrap().to_str().unwrap(), &p.reason))
+            .collect();
+
+        assert_eq!(reasons.len(), 4, "{:?}", reasons);
+        assert_eq!(reasons[0], ("tom.jpg", &ProblemReason::ZeroBytes));
+        assert!(matches!(reasons[1], ("søppel.jpg", ProblemReason::DecodeFailed { .. })));
+        assert_eq!(reasons[2], ("ikon.png", &ProblemReason::TooSmall { width: 8, height: 8 }));
+        assert!(matches!(reasons[3], ("avkortet.png", ProblemReason::DecodeFailed { .. })));
+    }
+}
@@ -0,0 +1,349 @@
+//! Støtte for RAW-filer fra kamera (CR2, NEF, ARW, DNG m.fl.)
+//!
+//! `image`-craten kan ikke dekode RAW-data, men nesten alle RAW-formater har en
+//! innebygd JPEG-forhåndsvisning og en vanlig EXIF-blokk. Vi finner disse via
+//! TIFF-/RAF-headerne, eller direkte i filinnholdet, slik at vi slipper en egen RAW-dekoder.
+
+use crate::services::scanner::RAW_EXTENSIONS;
+use image::codecs::jpeg::JpegDecoder;
+use image::{DynamicImage, ImageDecoder};
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// EXIF-blokker i JPEG/RAF/CR3 starter med denne markøren, etterfulgt av TIFF-data
+const EXIF_MARKER: &[u8] = b"Exif\0\0";
+
+/// JPEG start-of-image etterfulgt av starten på neste markør
+const JPEG_SOI: &[u8] = &[0xFF, 0xD8, 0xFF];
+
+/// Øvre grense for antall IFD-er som følges, mot løkker i ødelagte filer
+const MAX_IFDS: usize = 32;
+
+/// TIFF-tagger som peker på innebygde JPEG-er
+const TAG_COMPRESSION: u16 = 0x0103;
+const TAG_STRIP_OFFSETS: u16 = 0x0111;
+const TAG_STRIP_BYTE_COUNTS: u16 = 0x0117;
+const TAG_SUB_IFDS: u16 = 0x014A;
+const TAG_JPEG_OFFSET: u16 = 0x0201;
+const TAG_JPEG_LENGTH: u16 = 0x0202;
+
+/// Sjekker om en sti har RAW-filendelse
+pub fn is_raw_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| RAW_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Finner rå TIFF-data for EXIF i en fil som `exif::Reader::read_from_container`
+/// ikke forstår (f.eks. RAF og CR3). Resultatet kan gis til `exif::Reader::read_raw`.
+pub fn find_exif_block(bytes: &[u8]) -> Option<Vec<u8>> {
+    let pos = find(bytes, EXIF_MARKER, 0)?;
+    Some(bytes[pos + EXIF_MARKER.len()..].to_vec())
+}
+
+/// Henter den største innebygde JPEG-forhåndsvisningen fra en RAW-fil
+///
+/// Filer har gjerne flere forhåndsvisninger (liten EXIF-thumbnail og en stor preview).
+/// TIFF-baserte formater (CR2, NEF, ARW, DNG, ORF, RW2 m.fl.) og RAF oppgir hvor
+/// forhåndsvisningene ligger, så bare headerne og den valgte JPEG-en leses fra disk.
+/// Andre formater (f.eks. CR3) leses i sin helhet og søkes gjennom etter JPEG-data.
+pub fn extract_preview(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+    let mut file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+
+    let mut ranges = tiff_preview_ranges(&mut file, file_len);
+    ranges.extend(raf_preview_range(&mut file, file_len));
+
+    let mut best: Option<((u64, u64), u64)> = None;
+    for range in ranges {
+        if let Some((width, height)) = jpeg_dimensions(&mut file, range) {
+            let pixels = width as u64 * height as u64;
+            if best.is_none_or(|(_, best_pixels)| pixels > best_pixels) {
+                best = Some((range, pixels));
+            }
+        }
+    }
+
+    match best {
+        Some(((offset, length), _)) => {
+            file.seek(SeekFrom::Start(offset))?;
+            let decoder = JpegDecoder::new(BufReader::new(file.take(length)))?;
+            Ok(DynamicImage::from_decoder(decoder)?)
+        }
+        None => extract_preview_by_scan(path),
+    }
+}
+
+/// Leter etter JPEG-data i hele filen, for formater uten kjent plassering av forhåndsvisningen
+fn extract_preview_by_scan(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = fs::read(path)?;
+
+    let mut best: Option<(usize, u64)> = None;
+    let mut offset = 0;
+    while let Some(pos) = find(&bytes, JPEG_SOI, offset) {
+        if let Ok(decoder) = JpegDecoder::new(&bytes[pos..]) {
+            let (width, height) = decoder.dimensions();
+            let pixels = width as u64 * height as u64;
+            if best.is_none_or(|(_, best_pixels)| pixels > best_pixels) {
+                best = Some((pos, pixels));
+            }
+        }
+        offset = pos + JPEG_SOI.len();
+    }
+
+    let (pos, _) = best.ok_or_else(|| format!("Fant ingen innebygd forhåndsvisning i {}", path.display()))?;
+    // JPEG-dekoderen stopper ved end-of-image, så data etter forhåndsvisningen ignoreres
+    let img = image::load_from_memory_with_format(&bytes[pos..], image::ImageFormat::Jpeg)?;
+    Ok(img)
+}
+
+/// Dimensjonene til JPEG-en i `(offset, lengde)`, lest fra headeren alene
+fn jpeg_dimensions(file: &mut File, (offset, length): (u64, u64)) -> Option<(u32, u32)> {
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let decoder = JpegDecoder::new(BufReader::new(file.take(length))).ok()?;
+    Some(decoder.dimensions())
+}
+
+/// RAF har en egen header med offset og lengde for JPEG-forhåndsvisningen
+fn raf_preview_range(file: &mut File, file_len: u64) -> Option<(u64, u64)> {
+    let mut header = [0u8; 92];
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_exact(&mut header).ok()?;
+    if !header.starts_with(b"FUJIFILMCCD-RAW") {
+        return None;
+    }
+    let offset = u32::from_be_bytes(header[84..88].try_into().ok()?) as u64;
+    let length = u32::from_be_bytes(header[88..92].try_into().ok()?) as u64;
+    in_file(offset, length, file_len)
+}
+
+/// Innebygde JPEG-er i en TIFF-basert RAW-fil, som `(offset, lengde)`
+///
+/// Følger IFD-kjeden og SubIFD-er. JPEG-en kan være oppgitt som
+/// JPEGInterchangeFormat/-Length, eller som én enkelt strip med JPEG-komprimering.
+fn tiff_preview_ranges<R: Read + Seek>(reader: &mut R, file_len: u64) -> Vec<(u64, u64)> {
+    let mut header = [0u8; 8];
+    if reader.seek(SeekFrom::Start(0)).is_err() || reader.read_exact(&mut header).is_err() {
+        return Vec::new();
+    }
+    // Magisk tall varierer (42 for TIFF, egne verdier for ORF/RW2), så bare byte-rekkefølgen sjekkes
+    let big_endian = match &header[..2] {
+        b"II" => false,
+        b"MM" => true,
+        _ => return Vec::new(),
+    };
+    let u16_at = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1]];
+        if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+    };
+    let u32_at = |bytes: &[u8]| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    };
+
+    let mut ranges = Vec::new();
+    let mut queue = vec![u32_at(&header[4..]) as u64];
+    let mut visited = Vec::new();
+    while let Some(ifd) = queue.pop() {
+        if ifd == 0 || visited.contains(&ifd) || visited.len() >= MAX_IFDS {
+            continue;
+        }
+        visited.push(ifd);
+
+        let mut count = [0u8; 2];
+        if reader.seek(SeekFrom::Start(ifd)).is_err() || reader.read_exact(&mut count).is_err() {
+            continue;
+        }
+        // Hver oppføring er 12 byte, etterfulgt av offset til neste IFD
+        let mut entries = vec![0u8; u16_at(&count) as usize * 12 + 4];
+        if reader.read_exact(&mut entries).is_err() {
+            continue;
+        }
+
+        let (mut compression, mut strip, mut jpeg) = (None, (None, None), (None, None));
+        for entry in entries.chunks_exact(12) {
+            let tag = u16_at(&entry[0..]);
+            let value_count = u32_at(&entry[4..]);
+            // SHORT-verdier ligger i de to første bytene av verdifeltet, LONG bruker alle fire
+            let value = match u16_at(&entry[2..]) {
+                3 => u16_at(&entry[8..]) as u64,
+                _ => u32_at(&entry[8..]) as u64,
+            };
+            match tag {
+                TAG_COMPRESSION => compression = Some(value),
+                TAG_STRIP_OFFSETS if value_count == 1 => strip.0 = Some(value),
+                TAG_STRIP_BYTE_COUNTS if value_count == 1 => strip.1 = Some(value),
+                TAG_JPEG_OFFSET => jpeg.0 = Some(value),
+                TAG_JPEG_LENGTH => jpeg.1 = Some(value),
+                TAG_SUB_IFDS if value_count == 1 => queue.push(value),
+                TAG_SUB_IFDS => {
+                    // Flere SubIFD-er: verdien er offset til en liste med IFD-offsets
+                    let mut offsets = vec![0u8; value_count.min(MAX_IFDS as u32) as usize * 4];
+                    if reader.seek(SeekFrom::Start(value)).is_ok() && reader.read_exact(&mut offsets).is_ok() {
+                        queue.extend(offsets.chunks_exact(4).map(|offset| u32_at(offset) as u64));
+                    }
+                }
+                _ => {}
+            }
+        }
+        queue.push(u32_at(&entries[entries.len() - 4..]) as u64);
+
+        if let (Some(offset), Some(length)) = jpeg {
+            ranges.extend(in_file(offset, length, file_len));
+        }
+        // Komprimering 6 og 7 er (gammel og ny) JPEG
+        if let (Some(6 | 7), (Some(offset), Some(length))) = (compression, strip) {
+            ranges.extend(in_file(offset, length, file_len));
+        }
+    }
+    ranges
+}
+
+/// `(offset, lengde)` hvis området er ikke-tomt og ligger innenfor filen
+fn in_file(offset: u64, length: u64, file_len: u64) -> Option<(u64, u64)> {
+    (length > 0 && offset.checked_add(length)? <= file_len).then_some((offset, length))
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    haystack
+        .get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|pos| pos + from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, RgbImage};
+    use tempfile::tempdir;
+
+    fn jpeg_bytes(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut bytes, image::ImageOutputFormat::Jpeg(80))
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_is_raw_file() {
+        assert!(is_raw_file(Path::new("/bilder/IMG_0001.CR2")));
+        assert!(is_raw_file(Path::new("DSC_0001.nef")));
+        assert!(!is_raw_file(Path::new("bilde.jpg")));
+        assert!(!is_raw_file(Path::new("uten_endelse")));
+    }
+
+    #[test]
+    fn test_extract_largest_preview() {
+        // Simulert RAW-fil: header, liten thumbnail, søppel, stor forhåndsvisning
+        let mut raw = b"II*\0fake raw header".to_vec();
+        raw.extend(jpeg_bytes(16, 12));
+        raw.extend([0xFF, 0xD8, 0xFF, 0x00, 0x01]); // Ugyldig JPEG skal ignoreres
+        raw.extend(jpeg_bytes(64, 48));
+        raw.extend(b"sensordata");
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("IMG_0001.CR2");
+        fs::write(&path, raw).unwrap();
+
+        let preview = extract_preview(&path).unwrap();
+        assert_eq!(preview.dimensions(), (64, 48));
+    }
+
+    /// Minimal TIFF: liten JPEG via JPEGInterchangeFormat i IFD0, stor JPEG som strip i en SubIFD
+    fn tiff_raw(small: &[u8], large: &[u8]) -> Vec<u8> {
+        let entry = |tag: u16, kind: u16, value: u32| {
+            let mut entry = tag.to_le_bytes().to_vec();
+            entry.extend(kind.to_le_bytes());
+            entry.extend(1u32.to_le_bytes());
+            entry.extend(value.to_le_bytes());
+            entry
+        };
+        // Header (8) + to IFD-er med tre oppføringer (2 + 36 + 4) før bildedataene
+        let sub_ifd = 8 + 42;
+        let small_offset = sub_ifd + 42;
+        let large_offset = small_offset + small.len() as u32;
+
+        let mut bytes = b"II*\0".to_vec();
+        bytes.extend(8u32.to_le_bytes());
+        bytes.extend(3u16.to_le_bytes());
+        bytes.extend(entry(TAG_SUB_IFDS, 4, sub_ifd));
+        bytes.extend(entry(TAG_JPEG_OFFSET, 4, small_offset));
+        bytes.extend(entry(TAG_JPEG_LENGTH, 4, small.len() as u32));
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(3u16.to_le_bytes());
+        bytes.extend(entry(TAG_COMPRESSION, 3, 7));
+        bytes.extend(entry(TAG_STRIP_OFFSETS, 4, large_offset));
+        bytes.extend(entry(TAG_STRIP_BYTE_COUNTS, 4, large.len() as u32));
+        bytes.extend(0u32.to_le_bytes());
+        bytes.extend(small);
+        bytes.extend(large);
+        bytes
+    }
+
+    #[test]
+    fn test_extract_preview_from_tiff_offsets() {
+        let small = jpeg_bytes(16, 12);
+        let large = jpeg_bytes(64, 48);
+        let mut raw = tiff_raw(&small, &large);
+        let small_offset = 92;
+        assert_eq!(
+            tiff_preview_ranges(&mut std::io::Cursor::new(&raw), raw.len() as u64),
+            vec![
+                (small_offset, small.len() as u64),
+                (small_offset + small.len() as u64, large.len() as u64),
+            ]
+        );
+
+        // En JPEG som ikke er oppgitt i IFD-ene skal ikke leses, selv om den er større
+        raw.extend(b"sensordata");
+        raw.extend(jpeg_bytes(128, 96));
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("DSC_0001.NEF");
+        fs::write(&path, &raw).unwrap();
+        assert_eq!(extract_preview(&path).unwrap().dimensions(), (64, 48));
+
+        // Offsets utenfor filen forkastes
+        raw.truncate(small_offset as usize + small.len() + 10);
+        assert_eq!(
+            tiff_preview_ranges(&mut std::io::Cursor::new(&raw), raw.len() as u64),
+            vec![(small_offset, small.len() as u64)]
+        );
+    }
+
+    #[test]
+    fn test_extract_preview_from_raf_header() {
+        let preview = jpeg_bytes(32, 24);
+        let mut raf = b"FUJIFILMCCD-RAW 0201FF383501".to_vec();
+        raf.resize(84, 0);
+        raf.extend(100u32.to_be_bytes());
+        raf.extend((preview.len() as u32).to_be_bytes());
+        raf.resize(100, 0);
+        raf.extend(&preview);
+        raf.extend(jpeg_bytes(64, 48));
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("DSCF0001.RAF");
+        fs::write(&path, raf).unwrap();
+        assert_eq!(extract_preview(&path).unwrap().dimensions(), (32, 24));
+    }
+
+    #[test]
+    fn test_extract_preview_without_jpeg() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tom.nef");
+        fs::write(&path, b"ingen forhandsvisning her").unwrap();
+
+        assert!(extract_preview(&path).is_err());
+    }
+
+    #[test]
+    fn test_find_exif_block() {
+        let bytes = b"FUJIFILMCCD-RAW Exif\0\0II*\0rest";
+        assert_eq!(find_exif_block(bytes).unwrap(), b"II*\0rest".to_vec());
+        assert!(find_exif_block(b"ingen exif").is_none());
+    }
+}
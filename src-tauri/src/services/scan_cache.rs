@@ -0,0 +1,81 @@
+//! Husker siste fullførte skanning, slik at kommandoer som jobber på en hel mappe
+//! (f.eks. `find_duplicates_in_folder`) slipper å gå gjennom filsystemet på nytt
+//! eller få alle stiene sendt tilbake fra frontend.
+//!
+//! Kommandoer som flytter, sletter eller oppretter filer tømmer cachen.
+
+use crate::services::scanner::ScanOptions;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Stiene fra siste skanning, nøklet på mappen og innstillingene den ble skannet med
+/// Klones billig; alle kloner deler samme innhold.
+#[derive(Debug, Clone, Default)]
+pub struct ScanCache(Arc<Mutex<Option<CachedScan>>>);
+
+#[derive(Debug)]
+struct CachedScan {
+    folder: PathBuf,
+    options: ScanOptions,
+    paths: Vec<String>,
+}
+
+impl ScanCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lagrer resultatet av en fullført skanning. Erstatter forrige skanning.
+    pub fn store(&self, folder: &Path, options: &ScanOptions, paths: Vec<String>) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(CachedScan {
+            folder: cache_key(folder),
+            options: options.clone(),
+            paths,
+        });
+    }
+
+    /// Stiene fra siste skanning av `folder`, hvis det var den som ble skannet sist og
+    /// med de samme innstillingene
+    pub fn get(&self, folder: &Path, options: &ScanOptions) -> Option<Vec<String>> {
+        let key = cache_key(folder);
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .filter(|cached| cached.folder == key && cached.options == *options)
+            .map(|cached| cached.paths.clone())
+    }
+
+    pub fn clear(&self) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+/// Samme mappe skrevet på ulike måter (relativ sti, `..`, symlenke) skal treffe samme oppføring
+fn cache_key(folder: &Path) -> PathBuf {
+    folder.canonicalize().unwrap_or_else(|_| folder.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_returns_paths_for_same_folder_and_options_only() {
+        let dir = tempdir().unwrap();
+        let other = tempdir().unwrap();
+        let options = ScanOptions::default();
+        let cache = ScanCache::new();
+        assert!(cache.get(dir.path(), &options).is_none());
+
+        cache.store(dir.path(), &options, vec!["a.jpg".to_string()]);
+        assert_eq!(cache.get(&dir.path().join("."), &options), Some(vec!["a.jpg".to_string()]));
+        assert!(cache.get(other.path(), &options).is_none());
+        let with_videos = ScanOptions { include_videos: true, ..Default::default() };
+        assert!(cache.get(dir.path(), &with_videos).is_none());
+
+        cache.clear();
+        assert!(cache.get(dir.path(), &options).is_none());
+    }
+}
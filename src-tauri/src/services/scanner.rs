@@ -1,71 +1,390 @@
 //! Filskanner for å finne bilder i mapper
 
-use std::path::Path;
+use crate::services::cancellation::CancellationToken;
+use crate::services::path_codec;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
 /// Representerer et bilde funnet under skanning
 #[derive(Debug, Clone)]
 pub struct ImageInfo {
+    /// Tapsfri sti (se `path_codec`); send den uendret tilbake til backend
     pub path: String,
+    /// Filnavn for visning, ugyldige tegn erstattet
     pub filename: String,
     pub extension: String,
     pub size_bytes: u64,
+    pub media_type: MediaType,
+    /// Bredde/høyde fra filens header, kun når `ScanOptions::read_dimensions` er satt
+    pub width: Option<u32>,
+    pub height: Option<u32>,
 }
 
-/// Støttede bildeformater
-const SUPPORTED_EXTENSIONS: &[&str] = &[
-    // Bilder
-    "jpg", "jpeg", "png", "gif", "bmp", "webp", "tiff", "tif", "ico", "heic", "heif",
-    // Video
-    "mp4", "mov", "avi", "mkv", "webm", "wmv", "m4v",
+/// Om en fil er et bilde eller en video
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    Image,
+    Video,
+}
+
+impl MediaType {
+    /// Avgjør medietype ut fra filendelsen; alt som ikke er video regnes som bilde
+    pub fn from_extension(ext: &str) -> Self {
+        if VIDEO_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
+            MediaType::Video
+        } else {
+            MediaType::Image
+        }
+    }
+}
+
+/// En fil eller mappe som ikke kunne leses under skanning
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanError {
+    pub path: String,
+    pub message: String,
+}
+
+/// Resultat av en skanning som kan avbrytes
+#[derive(Debug, Clone, Default)]
+pub struct ScanOutcome {
+    pub images: Vec<ImageInfo>,
+    /// Filer/mapper som ble hoppet over pga. feil (manglende tilgang, brutte symlenker osv.)
+    pub errors: Vec<ScanError>,
+    /// `true` hvis skanningen ble avbrutt; `images` inneholder da det som ble funnet så langt
+    pub cancelled: bool,
+}
+
+/// Støttede bildeformater, i tillegg til `RAW_EXTENSIONS`
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "bmp", "webp", "tiff", "tif", "ico", "heic", "heif"];
+
+/// Filendelser for vanlige RAW-formater fra kamera
+pub const RAW_EXTENSIONS: &[&str] = &[
+    "cr2", "cr3", "nef", "nrw", "arw", "srf", "sr2", "dng", "orf", "rw2", "raf", "pef", "srw",
 ];
 
+/// Videoformater, tas kun med når `ScanOptions::include_videos` er satt
+pub const VIDEO_EXTENSIONS: &[&str] = &["mp4", "mov", "avi", "mkv", "webm", "wmv", "m4v"];
+
+/// Innstillinger for skanning (sendes fra frontend, alle felter er valgfrie)
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanOptions {
+    /// Filendelser som skal tas med, f.eks. `["jpg", ".PNG"]`. `None` = `IMAGE_EXTENSIONS` og `RAW_EXTENSIONS`
+    pub allowed_extensions: Option<Vec<String>>,
+    /// Ta med videofiler (`VIDEO_EXTENSIONS`). Uten dette hoppes video over
+    /// også når den står i `allowed_extensions`.
+    pub include_videos: bool,
+    /// Minste filstørrelse som tas med (inklusiv)
+    pub min_size_bytes: Option<u64>,
+    /// Største filstørrelse som tas med (inklusiv)
+    pub max_size_bytes: Option<u64>,
+    /// Hvor mange nivåer med undermapper som skannes. `Some(0)` = kun valgt mappe,
+    /// `Some(1)` = valgt mappe og dens undermapper osv. `None` (standard) = ubegrenset.
+    pub max_depth: Option<usize>,
+    /// Les bredde/høyde fra filens header (uten full dekoding). Koster en ekstra lesing per fil.
+    pub read_dimensions: bool,
+    /// Mapper som hoppes helt over, f.eks. appens egne cache-mapper.
+    /// Settes av backend, ikke fra frontend.
+    #[serde(skip)]
+    pub excluded_dirs: Vec<PathBuf>,
+    /// Glob-mønstre som hoppes over, f.eks. `["@eaDir", ".thumbnails", "*.tmp", "eksport/**"]`
+    /// Mønstre uten `/` matcher navnet på filer og mapper på alle nivåer; mønstre med `/`
+    /// matcher stien relativt til skannet mappe. En mappe som matcher hoppes over med alt innhold.
+    pub ignore_patterns: Vec<String>,
+    /// Ikke følg symbolske lenker. Standard er å følge dem; lenker som peker tilbake til en
+    /// mappe over seg (løkker) hoppes da over og rapporteres i `ScanOutcome::errors`.
+    pub skip_symlinks: bool,
+    /// Ta med skjulte filer og mapper (navn som starter med `.`, og på Windows filer med
+    /// attributtet skjult eller system). Standard er å hoppe over dem, med alt innhold.
+    pub include_hidden: bool,
+}
+
+impl ScanOptions {
+    /// Filendelsene som skal tas med, normalisert til små bokstaver uten ledende punktum
+    fn extensions(&self) -> Vec<String> {
+        let extensions: Vec<String> = match &self.allowed_extensions {
+            Some(extensions) => extensions
+                .iter()
+                .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                .filter(|ext| !ext.is_empty())
+                .collect(),
+            None => IMAGE_EXTENSIONS
+                .iter()
+                .chain(RAW_EXTENSIONS)
+                .chain(VIDEO_EXTENSIONS)
+                .map(|ext| ext.to_string())
+                .collect(),
+        };
+
+        extensions
+            .into_iter()
+            .filter(|ext| self.include_videos || MediaType::from_extension(ext) == MediaType::Image)
+            .collect()
+    }
+
+    /// Ekskluderte mapper både som oppgitt og kanonisert, slik at symlenker
+    /// (f.eks. /tmp -> /private/tmp på macOS) ikke slipper gjennom
+    fn excluded_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        for dir in &self.excluded_dirs {
+            dirs.push(dir.clone());
+            if let Ok(canonical) = dir.canonicalize() {
+                dirs.push(canonical);
+            }
+        }
+        dirs
+    }
+
+    /// Bygger `ignore_patterns` til ett globsett; ugyldige mønstre gir feil
+    fn ignore_set(&self) -> Result<GlobSet, Box<dyn std::error::Error>> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.ignore_patterns {
+            let pattern = pattern.trim().trim_end_matches('/');
+            if pattern.is_empty() {
+                continue;
+            }
+            let glob = GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("Ugyldig ignoremønster {}: {}", pattern, e))?;
+            builder.add(glob);
+        }
+        Ok(builder.build()?)
+    }
+
+    /// Sjekker om en filstørrelse ligger innenfor grensene
+    fn size_in_range(&self, size_bytes: u64) -> bool {
+        self.min_size_bytes.is_none_or(|min| size_bytes >= min)
+            && self.max_size_bytes.is_none_or(|max| size_bytes <= max)
+    }
+}
+
 /// Sjekker om en filendelse er støttet
 pub fn is_supported_extension(ext: &str) -> bool {
-    SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str())
+    let ext = ext.to_lowercase();
+    [IMAGE_EXTENSIONS, RAW_EXTENSIONS, VIDEO_EXTENSIONS]
+        .iter()
+        .any(|extensions| extensions.contains(&ext.as_str()))
+}
+
+/// Leser dimensjoner fra filens header; `None` for formater `imagesize` ikke kjenner
+pub(crate) fn read_dimensions(path: &Path) -> Option<(u32, u32)> {
+    let size = imagesize::size(path).ok()?;
+    Some((u32::try_from(size.width).ok()?, u32::try_from(size.height).ok()?))
 }
 
 /// Skanner en mappe rekursivt og returnerer alle bilder
-pub fn scan_directory(path: &str) -> Result<Vec<ImageInfo>, Box<dyn std::error::Error>> {
-    let path = Path::new(path);
+pub fn scan_directory(path: &str, options: &ScanOptions) -> Result<Vec<ImageInfo>, Box<dyn std::error::Error>> {
+    scan_directory_cancellable(path, options, &CancellationToken::new()).map(|outcome| outcome.images)
+}
+
+/// Skanner en mappe rekursivt, og avbryter tidlig hvis `cancel` blir satt
+pub fn scan_directory_cancellable(
+    path: &str,
+    options: &ScanOptions,
+    cancel: &CancellationToken,
+) -> Result<ScanOutcome, Box<dyn std::error::Error>> {
+    let mut images = Vec::new();
+    let mut outcome = scan_directory_with(path, options, cancel, |image| images.push(image))?;
+    outcome.images = images;
+    Ok(outcome)
+}
+
+/// Som `scan_directory_cancellable`, men leverer bildene i bolker på `batch_size`
+/// etter hvert som de blir funnet, slik at UI kan vise dem før skanningen er ferdig.
+/// Den siste bolken kan være mindre. `images` i resultatet er tom.
+pub fn scan_directory_batched(
+    path: &str,
+    options: &ScanOptions,
+    cancel: &CancellationToken,
+    batch_size: usize,
+    mut on_batch: impl FnMut(Vec<ImageInfo>),
+) -> Result<ScanOutcome, Box<dyn std::error::Error>> {
+    let batch_size = batch_size.max(1);
+    let mut batch = Vec::with_capacity(batch_size);
+    let outcome = scan_directory_with(path, options, cancel, |image| {
+        batch.push(image);
+        if batch.len() >= batch_size {
+            on_batch(std::mem::replace(&mut batch, Vec::with_capacity(batch_size)));
+        }
+    })?;
+    if !batch.is_empty() {
+        on_batch(batch);
+    }
+    Ok(outcome)
+}
+
+/// Teller filene en skanning med samme `options` ville returnert, uten å bygge `ImageInfo`
+/// eller lese dimensjoner. Gir UI en nevner for fremdriften før selve skanningen.
+/// Returnerer antallet funnet så langt hvis `cancel` blir satt.
+pub fn count_images(
+    path: &str,
+    options: &ScanOptions,
+    cancel: &CancellationToken,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut count = 0;
+    walk_matching(path, options, cancel, |_, _, _| count += 1)?;
+    Ok(count)
+}
+
+/// Går gjennom mappen og kaller `on_image` for hvert bilde som matcher `options`
+/// Feil og avbrudd samles i resultatet; `images` fylles ikke.
+fn scan_directory_with(
+    path: &str,
+    options: &ScanOptions,
+    cancel: &CancellationToken,
+    mut on_image: impl FnMut(ImageInfo),
+) -> Result<ScanOutcome, Box<dyn std::error::Error>> {
+    walk_matching(path, options, cancel, |entry_path, ext_lower, metadata| {
+        let filename = entry_path
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let (width, height) = if options.read_dimensions {
+            read_dimensions(entry_path).unzip()
+        } else {
+            (None, None)
+        };
+
+        on_image(ImageInfo {
+            path: path_codec::encode(entry_path),
+            filename,
+            media_type: MediaType::from_extension(&ext_lower),
+            extension: ext_lower,
+            size_bytes: metadata.len(),
+            width,
+            height,
+        });
+    })
+}
+
+/// Skjult fil eller mappe: navnet starter med `.` (`.DS_Store`, `.thumbnails`), eller
+/// på Windows har attributtet skjult eller system (f.eks. `Thumbs.db`)
+fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    if entry.file_name().to_string_lossy().starts_with('.') {
+        return true;
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+        // På Windows kommer metadata fra selve mappelistingen, så dette koster ingen ekstra stat
+        if let Ok(metadata) = entry.metadata() {
+            return metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM) != 0;
+        }
+    }
+    false
+}
+
+/// Selve traverseringen, felles for skanning og telling
+/// Kaller `on_match(sti, filendelse med små bokstaver, metadata)` for hver fil som matcher
+/// filendelse, størrelse, dybde og ignoreringsmønstre i `options`.
+fn walk_matching(
+    path: &str,
+    options: &ScanOptions,
+    cancel: &CancellationToken,
+    mut on_match: impl FnMut(&Path, String, &std::fs::Metadata),
+) -> Result<ScanOutcome, Box<dyn std::error::Error>> {
+    let path = path_codec::decode(path);
+    let path = path.as_path();
 
     if !path.exists() {
-        return Err(format!("Mappen finnes ikke: {}", path.display()).into());
+        let message = format!("Mappen finnes ikke: {}", path.display());
+        return Err(std::io::Error::new(std::io::ErrorKind::NotFound, message).into());
     }
 
     if !path.is_dir() {
         return Err(format!("Stien er ikke en mappe: {}", path.display()).into());
     }
 
-    let mut images = Vec::new();
+    let extensions = options.extensions();
+    let mut outcome = ScanOutcome::default();
+
+    // WalkDir oppdager symlenke-løkker selv når lenker følges, og gir en feil i stedet for å gå i ring
+    let mut walker = WalkDir::new(path).follow_links(!options.skip_symlinks);
+    if let Some(max_depth) = options.max_depth {
+        // WalkDir teller selve rotmappen som dybde 0, og filene i den som dybde 1
+        walker = walker.max_depth(max_depth.saturating_add(1));
+    }
+
+    // Ekskluderte og ignorerte mapper beskjæres med `filter_entry`, så hele undertreet hoppes over
+    let excluded_dirs = options.excluded_dirs();
+    let ignore_set = options.ignore_set()?;
+    let is_ignored = |entry: &walkdir::DirEntry| {
+        // Rotmappen selv (dybde 0) skal alltid skannes
+        entry.depth() > 0
+            && (ignore_set.is_match(entry.file_name())
+                || entry.path().strip_prefix(path).is_ok_and(|relative| ignore_set.is_match(relative)))
+    };
+    // Skjulte filer og mapper beskjæres på samme måte; rotmappen skannes selv om den er skjult
+    let is_skipped_hidden = |entry: &walkdir::DirEntry| {
+        !options.include_hidden && entry.depth() > 0 && is_hidden(entry)
+    };
+    let walker = walker.into_iter().filter_entry(|entry| {
+        !excluded_dirs.iter().any(|dir| entry.path().starts_with(dir))
+            && !is_ignored(entry)
+            && !is_skipped_hidden(entry)
+    });
+
+    for entry in walker {
+        if cancel.is_cancelled() {
+            outcome.cancelled = true;
+            return Ok(outcome);
+        }
+
+        // Feil for enkeltfiler samles opp i stedet for å stoppe skanningen
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let message = match e.loop_ancestor() {
+                    Some(ancestor) => format!("Symlenke-løkke til {}, hoppet over", ancestor.display()),
+                    None => e.to_string(),
+                };
+                outcome.errors.push(ScanError {
+                    path: path_codec::encode(e.path().unwrap_or(path)),
+                    message,
+                });
+                continue;
+            }
+        };
 
-    for entry in WalkDir::new(path).follow_links(true).into_iter().flatten() {
         let entry_path = entry.path();
 
-        if entry_path.is_file() {
+        // `file_type` kommer fra selve traverseringen, så filen stat-es kun én gang (i `metadata`)
+        if entry.file_type().is_file() {
             if let Some(ext) = entry_path.extension() {
                 let ext_lower = ext.to_string_lossy().to_lowercase();
 
-                if SUPPORTED_EXTENSIONS.contains(&ext_lower.as_str()) {
-                    if let Ok(metadata) = entry.metadata() {
-                        let filename = entry_path
-                            .file_name()
-                            .map(|s| s.to_string_lossy().to_string())
-                            .unwrap_or_default();
-
-                        images.push(ImageInfo {
-                            path: entry_path.to_string_lossy().to_string(),
-                            filename,
-                            extension: ext_lower,
-                            size_bytes: metadata.len(),
-                        });
+                if extensions.contains(&ext_lower) {
+                    let metadata = match entry.metadata() {
+                        Ok(metadata) => metadata,
+                        Err(e) => {
+                            outcome.errors.push(ScanError {
+                                path: path_codec::encode(entry_path),
+                                message: e.to_string(),
+                            });
+                            continue;
+                        }
+                    };
+
+                    if !options.size_in_range(metadata.len()) {
+                        continue;
                     }
+
+                    on_match(entry_path, ext_lower, &metadata);
                 }
             }
         }
     }
 
-    Ok(images)
+    Ok(outcome)
 }
 
 #[cfg(test)]
@@ -77,15 +396,22 @@ mod tests {
 
     #[test]
     fn test_supported_extensions() {
-        assert!(SUPPORTED_EXTENSIONS.contains(&"jpg"));
-        assert!(SUPPORTED_EXTENSIONS.contains(&"jpeg"));
-        assert!(SUPPORTED_EXTENSIONS.contains(&"png"));
-        assert!(SUPPORTED_EXTENSIONS.contains(&"gif"));
-        assert!(SUPPORTED_EXTENSIONS.contains(&"webp"));
-        assert!(!SUPPORTED_EXTENSIONS.contains(&"txt"));
-        assert!(!SUPPORTED_EXTENSIONS.contains(&"pdf"));
-        assert!(SUPPORTED_EXTENSIONS.contains(&"mp4"));
-        assert!(SUPPORTED_EXTENSIONS.contains(&"mov"));
+        assert!(IMAGE_EXTENSIONS.contains(&"jpg"));
+        assert!(IMAGE_EXTENSIONS.contains(&"jpeg"));
+        assert!(IMAGE_EXTENSIONS.contains(&"png"));
+        assert!(IMAGE_EXTENSIONS.contains(&"gif"));
+        assert!(IMAGE_EXTENSIONS.contains(&"webp"));
+        assert!(!IMAGE_EXTENSIONS.contains(&"txt"));
+        assert!(!IMAGE_EXTENSIONS.contains(&"pdf"));
+        assert!(VIDEO_EXTENSIONS.contains(&"mp4"));
+        assert!(VIDEO_EXTENSIONS.contains(&"mov"));
+        assert!(is_supported_extension("mkv"));
+        // Alle RAW-formater skal skannes
+        let default_extensions = ScanOptions::default().extensions();
+        for ext in RAW_EXTENSIONS {
+            assert!(is_supported_extension(ext), "{} mangler", ext);
+            assert!(default_extensions.iter().any(|e| e == ext), "{} skannes ikke", ext);
+        }
     }
 
     #[test]
@@ -100,15 +426,37 @@ mod tests {
 
     #[test]
     fn test_scan_nonexistent_directory() {
-        let result = scan_directory("/nonexistent/path/12345");
+        let result = scan_directory("/nonexistent/path/12345", &ScanOptions::default());
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("finnes ikke"));
     }
 
+    #[test]
+    fn test_scan_batched() {
+        let dir = tempdir().unwrap();
+        for i in 0..5 {
+            File::create(dir.path().join(format!("bilde{}.jpg", i))).unwrap();
+        }
+
+        let mut batches = Vec::new();
+        let outcome = scan_directory_batched(
+            dir.path().to_str().unwrap(),
+            &ScanOptions::default(),
+            &CancellationToken::new(),
+            2,
+            |batch| batches.push(batch.len()),
+        )
+        .unwrap();
+
+        assert_eq!(batches, vec![2, 2, 1]);
+        assert!(outcome.images.is_empty());
+        assert!(!outcome.cancelled);
+    }
+
     #[test]
     fn test_scan_empty_directory() {
         let dir = tempdir().unwrap();
-        let result = scan_directory(dir.path().to_str().unwrap());
+        let result = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap().len(), 0);
     }
@@ -126,7 +474,7 @@ mod tests {
         File::create(&png_path).unwrap().write_all(b"fake png").unwrap();
         File::create(&txt_path).unwrap().write_all(b"text file").unwrap();
         
-        let result = scan_directory(dir.path().to_str().unwrap());
+        let result = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default());
         assert!(result.is_ok());
         
         let images = result.unwrap();
@@ -138,6 +486,110 @@ mod tests {
         assert!(!filenames.contains(&"test.txt"));
     }
 
+    #[test]
+    fn test_videos_only_with_include_videos() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("clip.MOV")).unwrap();
+
+        let images = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default()).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].media_type, MediaType::Image);
+
+        let options = ScanOptions {
+            include_videos: true,
+            ..Default::default()
+        };
+        let mut media = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+        media.sort_by(|a, b| a.filename.cmp(&b.filename));
+        assert_eq!(media.len(), 2);
+        assert_eq!(media[0].filename, "clip.MOV");
+        assert_eq!(media[0].media_type, MediaType::Video);
+        assert_eq!(media[1].media_type, MediaType::Image);
+    }
+
+    #[test]
+    fn test_read_dimensions_optional() {
+        let dir = tempdir().unwrap();
+        image::RgbImage::new(40, 30).save(dir.path().join("real.png")).unwrap();
+        File::create(dir.path().join("fake.jpg")).unwrap().write_all(b"ikke et bilde").unwrap();
+
+        let scan = |read_dimensions| {
+            let options = ScanOptions {
+                read_dimensions,
+                ..Default::default()
+            };
+            let mut images = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+            images.sort_by(|a, b| a.filename.cmp(&b.filename));
+            images.into_iter().map(|i| (i.width, i.height)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(scan(false), vec![(None, None), (None, None)]);
+        assert_eq!(scan(true), vec![(None, None), (Some(40), Some(30))]);
+    }
+
+    #[test]
+    fn test_scan_with_allowed_extensions() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("a.jpg")).unwrap();
+        File::create(dir.path().join("b.JPEG")).unwrap();
+        File::create(dir.path().join("c.png")).unwrap();
+
+        let options = ScanOptions {
+            allowed_extensions: Some(vec!["png".to_string()]),
+            ..Default::default()
+        };
+        let images = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].filename, "c.png");
+    }
+
+    #[test]
+    fn test_allowed_extensions_are_normalized() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("a.avif")).unwrap();
+        File::create(dir.path().join("b.jpg")).unwrap();
+
+        let options = ScanOptions {
+            allowed_extensions: Some(vec![".AVIF".to_string(), " ".to_string()]),
+            ..Default::default()
+        };
+        let images = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].extension, "avif");
+    }
+
+    #[test]
+    fn test_size_bounds_are_inclusive() {
+        let dir = tempdir().unwrap();
+        for (name, size) in [("tiny.jpg", 5), ("min.jpg", 10), ("max.jpg", 20), ("huge.jpg", 21)] {
+            File::create(dir.path().join(name)).unwrap().write_all(&vec![0u8; size]).unwrap();
+        }
+
+        let options = ScanOptions {
+            min_size_bytes: Some(10),
+            max_size_bytes: Some(20),
+            ..Default::default()
+        };
+        let images = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+
+        let mut filenames: Vec<&str> = images.iter().map(|i| i.filename.as_str()).collect();
+        filenames.sort();
+        assert_eq!(filenames, vec!["max.jpg", "min.jpg"]);
+    }
+
+    #[test]
+    fn test_no_size_bounds_by_default() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("empty.jpg")).unwrap();
+        File::create(dir.path().join("big.jpg")).unwrap().write_all(&vec![0u8; 4096]).unwrap();
+
+        let images = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default()).unwrap();
+        assert_eq!(images.len(), 2);
+    }
+
     #[test]
     fn test_scan_recursive() {
         let dir = tempdir().unwrap();
@@ -148,18 +600,194 @@ mod tests {
         File::create(dir.path().join("image1.jpg")).unwrap();
         File::create(subdir.join("image2.png")).unwrap();
         
-        let result = scan_directory(dir.path().to_str().unwrap());
+        let result = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default());
         assert!(result.is_ok());
         assert_eq!(result.unwrap().len(), 2); // Begge bildene funnet
     }
 
+    #[test]
+    fn test_max_depth() {
+        let dir = tempdir().unwrap();
+        let child = dir.path().join("child");
+        let grandchild = child.join("grandchild");
+        fs::create_dir_all(&grandchild).unwrap();
+
+        File::create(dir.path().join("top.jpg")).unwrap();
+        File::create(child.join("child.jpg")).unwrap();
+        File::create(grandchild.join("grandchild.jpg")).unwrap();
+
+        let scan = |max_depth| {
+            let options = ScanOptions {
+                max_depth,
+                ..Default::default()
+            };
+            let mut filenames: Vec<String> = scan_directory(dir.path().to_str().unwrap(), &options)
+                .unwrap()
+                .into_iter()
+                .map(|i| i.filename)
+                .collect();
+            filenames.sort();
+            filenames
+        };
+
+        assert_eq!(scan(Some(0)), vec!["top.jpg"]);
+        assert_eq!(scan(Some(1)), vec!["child.jpg", "top.jpg"]);
+        assert_eq!(scan(None), vec!["child.jpg", "grandchild.jpg", "top.jpg"]);
+    }
+
+    #[test]
+    fn test_count_matches_scan() {
+        let dir = tempdir().unwrap();
+        let child = dir.path().join("child");
+        fs::create_dir_all(child.join("@eaDir")).unwrap();
+        fs::write(dir.path().join("top.jpg"), vec![0u8; 100]).unwrap();
+        fs::write(dir.path().join("tiny.png"), vec![0u8; 5]).unwrap();
+        fs::write(dir.path().join("notes.txt"), b"x").unwrap();
+        fs::write(child.join("child.jpg"), vec![0u8; 100]).unwrap();
+        fs::write(child.join("@eaDir").join("thumb.jpg"), vec![0u8; 100]).unwrap();
+
+        let root = dir.path().to_str().unwrap();
+        let cancel = CancellationToken::new();
+        for options in [
+            ScanOptions::default(),
+            ScanOptions { min_size_bytes: Some(10), ..Default::default() },
+            ScanOptions { max_depth: Some(0), ..Default::default() },
+            ScanOptions { ignore_patterns: vec!["@eaDir".to_string()], ..Default::default() },
+        ] {
+            let expected = scan_directory(root, &options).unwrap().len();
+            assert_eq!(count_images(root, &options, &cancel).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_excluded_dirs_are_pruned() {
+        let dir = tempdir().unwrap();
+        let cache = dir.path().join("imagesorter-thumbnails");
+        fs::create_dir(&cache).unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(cache.join("0123abcd.jpg")).unwrap();
+
+        let options = ScanOptions {
+            excluded_dirs: vec![cache],
+            ..Default::default()
+        };
+        let images = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].filename, "photo.jpg");
+    }
+
+    #[test]
+    fn test_ignore_pattern_prunes_directories() {
+        let dir = tempdir().unwrap();
+        let synology = dir.path().join("2023").join("@eaDir");
+        fs::create_dir_all(&synology).unwrap();
+        File::create(dir.path().join("2023").join("photo.jpg")).unwrap();
+        File::create(synology.join("SYNOPHOTO_THUMB_XL.jpg")).unwrap();
+
+        let options = ScanOptions {
+            ignore_patterns: vec!["@eaDir/".to_string()],
+            ..Default::default()
+        };
+        let images = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].filename, "photo.jpg");
+    }
+
+    #[test]
+    fn test_scan_skips_hidden_files() {
+        let dir = tempdir().unwrap();
+        let hidden_dir = dir.path().join(".thumbnails");
+        fs::create_dir_all(&hidden_dir).unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("._photo.jpg")).unwrap();
+        File::create(hidden_dir.join("cache.jpg")).unwrap();
+
+        let images = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default()).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].filename, "photo.jpg");
+
+        let options = ScanOptions { include_hidden: true, ..Default::default() };
+        let mut names: Vec<String> = scan_directory(dir.path().to_str().unwrap(), &options)
+            .unwrap()
+            .into_iter()
+            .map(|image| image.filename)
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["._photo.jpg", "cache.jpg", "photo.jpg"]);
+
+        // En skjult mappe som selv er valgt skannes likevel
+        let images = scan_directory(hidden_dir.to_str().unwrap(), &ScanOptions::default()).unwrap();
+        assert_eq!(images.len(), 1);
+    }
+
+    #[test]
+    fn test_ignore_pattern_matches_files() {
+        let dir = tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("eksport")).unwrap();
+        File::create(dir.path().join("photo.jpg")).unwrap();
+        File::create(dir.path().join("photo_edit.jpg")).unwrap();
+        File::create(dir.path().join("eksport").join("photo.jpg")).unwrap();
+
+        let options = ScanOptions {
+            ignore_patterns: vec!["*_edit.jpg".to_string(), "eksport/*.jpg".to_string()],
+            ..Default::default()
+        };
+        let images = scan_directory(dir.path().to_str().unwrap(), &options).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].path, dir.path().join("photo.jpg").to_string_lossy());
+
+        let invalid = ScanOptions {
+            ignore_patterns: vec!["[".to_string()],
+            ..Default::default()
+        };
+        assert!(scan_directory(dir.path().to_str().unwrap(), &invalid).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_broken_symlink_reported_as_error() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("ok.jpg")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("mangler.jpg"), dir.path().join("brutt.jpg")).unwrap();
+
+        let outcome =
+            scan_directory_cancellable(dir.path().to_str().unwrap(), &ScanOptions::default(), &CancellationToken::new())
+                .unwrap();
+
+        assert_eq!(outcome.images.len(), 1);
+        assert_eq!(outcome.errors.len(), 1);
+        assert!(outcome.errors[0].path.ends_with("brutt.jpg"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_is_skipped() {
+        let dir = tempdir().unwrap();
+        fs::create_dir(dir.path().join("album")).unwrap();
+        File::create(dir.path().join("album").join("ok.jpg")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("album").join("tilbake")).unwrap();
+
+        let root = dir.path().to_str().unwrap();
+        let outcome = scan_directory_cancellable(root, &ScanOptions::default(), &CancellationToken::new()).unwrap();
+        assert_eq!(outcome.images.len(), 1);
+        assert_eq!(outcome.errors.len(), 1);
+        assert!(outcome.errors[0].message.contains("løkke"), "{}", outcome.errors[0].message);
+
+        let options = ScanOptions { skip_symlinks: true, ..Default::default() };
+        let outcome = scan_directory_cancellable(root, &options, &CancellationToken::new()).unwrap();
+        assert_eq!(outcome.images.len(), 1);
+        assert!(outcome.errors.is_empty());
+    }
+
     #[test]
     fn test_image_info_fields() {
         let dir = tempdir().unwrap();
         let jpg_path = dir.path().join("testfile.jpg");
         File::create(&jpg_path).unwrap().write_all(b"12345678").unwrap();
         
-        let result = scan_directory(dir.path().to_str().unwrap());
+        let result = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default());
         let images = result.unwrap();
         
         assert_eq!(images.len(), 1);
@@ -171,13 +799,26 @@ mod tests {
         assert!(img.path.ends_with("testfile.jpg"));
     }
 
+    #[test]
+    fn test_scan_cancelled() {
+        let dir = tempdir().unwrap();
+        File::create(dir.path().join("image1.jpg")).unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let outcome = scan_directory_cancellable(dir.path().to_str().unwrap(), &ScanOptions::default(), &token).unwrap();
+        assert!(outcome.cancelled);
+        assert!(outcome.images.is_empty());
+    }
+
     #[test]
     fn test_scan_file_not_directory() {
         let dir = tempdir().unwrap();
         let file_path = dir.path().join("file.txt");
         File::create(&file_path).unwrap();
         
-        let result = scan_directory(file_path.to_str().unwrap());
+        let result = scan_directory(file_path.to_str().unwrap(), &ScanOptions::default());
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("ikke en mappe"));
     }
@@ -0,0 +1,117 @@
+//! Oversikt over en mappe før brukeren bestemmer seg for hva som skal gjøres:
+//! antall filer og størrelse per filtype, og hvilket tidsrom bildene dekker.
+
+use crate::services::{metadata, path_codec};
+use crate::services::scanner::ImageInfo;
+use chrono::{DateTime, Local};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Antall og samlet størrelse for én filtype
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ExtStat {
+    pub count: usize,
+    pub total_bytes: u64,
+}
+
+/// Statistikk for en skannet mappe
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderStats {
+    /// Nøklet på filendelse med små bokstaver, f.eks. "jpg"
+    pub by_extension: HashMap<String, ExtStat>,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    /// Eldste og nyeste opprettelsesdato i RFC 3339-format
+    pub oldest: Option<String>,
+    pub newest: Option<String>,
+}
+
+/// Delresultat per rayon-tråd, slått sammen til slutt
+#[derive(Default)]
+struct Partial {
+    by_extension: HashMap<String, ExtStat>,
+    oldest: Option<DateTime<Local>>,
+    newest: Option<DateTime<Local>>,
+}
+
+impl Partial {
+    fn add(mut self, image: &ImageInfo) -> Self {
+        let stat = self.by_extension.entry(image.extension.to_lowercase()).or_default();
+        stat.count += 1;
+        stat.total_bytes += image.size_bytes;
+
+        if let Some(found) = metadata::read_creation_date(&path_codec::decode(&image.path)) {
+            self.include_date(found.date, found.date);
+        }
+        self
+    }
+
+    fn include_date(&mut self, oldest: DateTime<Local>, newest: DateTime<Local>) {
+        self.oldest = Some(self.oldest.map_or(oldest, |current| current.min(oldest)));
+        self.newest = Some(self.newest.map_or(newest, |current| current.max(newest)));
+    }
+
+    fn merge(mut self, other: Self) -> Self {
+        for (ext, stat) in other.by_extension {
+            let entry = self.by_extension.entry(ext).or_default();
+            entry.count += stat.count;
+            entry.total_bytes += stat.total_bytes;
+        }
+        if let (Some(oldest), Some(newest)) = (other.oldest, other.newest) {
+            self.include_date(oldest, newest);
+        }
+        self
+    }
+}
+
+/// Beregner statistikk for bildene fra en skanning i én parallell gjennomgang
+/// Datoene leses som ved sortering (EXIF, sidecar, ev. endringsdato).
+pub fn folder_stats(images: &[ImageInfo]) -> FolderStats {
+    let partial = images
+        .par_iter()
+        .fold(Partial::default, Partial::add)
+        .reduce(Partial::default, Partial::merge);
+
+    FolderStats {
+        total_files: images.len(),
+        total_bytes: images.iter().map(|image| image.size_bytes).sum(),
+        by_extension: partial.by_extension,
+        oldest: partial.oldest.map(|date| date.to_rfc3339()),
+        newest: partial.newest.map(|date| date.to_rfc3339()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::scanner::{scan_directory, ScanOptions};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_folder_stats_by_extension() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.jpg"), vec![0u8; 100]).unwrap();
+        fs::write(dir.path().join("b.JPG"), vec![0u8; 50]).unwrap();
+        fs::write(dir.path().join("c.png"), vec![0u8; 10]).unwrap();
+
+        let images = scan_directory(dir.path().to_str().unwrap(), &ScanOptions::default()).unwrap();
+        let stats = folder_stats(&images);
+
+        assert_eq!(stats.total_files, 3);
+        assert_eq!(stats.total_bytes, 160);
+        assert_eq!(stats.by_extension["jpg"], ExtStat { count: 2, total_bytes: 150 });
+        assert_eq!(stats.by_extension["png"], ExtStat { count: 1, total_bytes: 10 });
+        // Uten EXIF brukes endringsdatoen
+        assert!(stats.oldest.is_some());
+        assert!(stats.oldest <= stats.newest);
+    }
+
+    #[test]
+    fn test_folder_stats_empty() {
+        assert_eq!(folder_stats(&[]), FolderStats::default());
+    }
+}
@@ -0,0 +1,443 @@
+//! Mappemaler for sortering, f.eks. `"{year}/{month:02}-{month_name}/{camera_model}"`
+//!
+//! Plassholdere: `{year}`, `{month}`, `{month:02}`, `{month_name}`, `{day}`, `{day:02}`,
+//! `{camera_make}`, `{camera_model}`, `{lens}`, `{orientation}` og `{ext}`.
+//!
+//! Malen parses én gang og rendres per bilde. Hver mappekomponent saneres slik at
+//! ugyldige tegn i metadata (f.eks. `/` i et kameranavn) ikke ødelegger stien.
+//!
+//! `RenamePattern` er tilsvarende for filnavn ved omdøping på stedet, f.eks. `"{date}_{counter:04}"`.
+
+use crate::services::aspect::Orientation;
+use chrono::{DateTime, Datelike, Local};
+use std::path::PathBuf;
+
+/// Norske månedsnavn, indeksert fra 0 (januar)
+/// Standard for `{month_name}`; kan overstyres med `SortConfig::month_names`.
+pub const MONTH_NAMES: [&str; 12] = [
+    "Januar", "Februar", "Mars", "April", "Mai", "Juni",
+    "Juli", "August", "September", "Oktober", "November", "Desember",
+];
+
+/// Sjekker egne månedsnavn: hvert navn må være et gyldig, ikke-tomt mappenavn
+pub fn validate_month_names(names: &[String; 12]) -> Result<(), String> {
+    for (index, name) in names.iter().enumerate() {
+        let trimmed = name.trim();
+        if trimmed.is_empty() || sanitize_component(trimmed) != trimmed || trimmed == "." || trimmed == ".." {
+            return Err(format!("Ugyldig navn for måned {}: {:?}", index + 1, name));
+        }
+    }
+    Ok(())
+}
+
+/// Brukes for `{camera_make}`/`{camera_model}` når bildet mangler kamerainformasjon
+const UNKNOWN_CAMERA: &str = "Ukjent kamera";
+
+/// Brukes for `{lens}` når bildet mangler objektivinformasjon
+const UNKNOWN_LENS: &str = "Ukjent objektiv";
+
+/// Brukes for `{orientation}` når dimensjonene ikke kan leses
+const UNKNOWN_ORIENTATION: &str = "Ukjent format";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Literal(String),
+    Year,
+    Month { padded: bool },
+    MonthName,
+    Day { padded: bool },
+    CameraMake,
+    CameraModel,
+    Lens,
+    Orientation,
+    Ext,
+}
+
+/// Verdier som settes inn i malen for ett bilde
+#[derive(Debug, Clone, Default)]
+pub struct TemplateContext<'a> {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub camera_make: Option<&'a str>,
+    pub camera_model: Option<&'a str>,
+    pub lens: Option<&'a str>,
+    /// Liggende/stående/kvadratisk for `{orientation}` (se `aspect::read_orientation`)
+    pub orientation: Option<Orientation>,
+    pub ext: &'a str,
+    /// Egne månedsnavn for `{month_name}`; `None` gir `MONTH_NAMES`
+    pub month_names: Option<&'a [String; 12]>,
+}
+
+/// En parset mappemal
+#[derive(Debug, Clone, PartialEq)]
+pub struct FolderTemplate {
+    tokens: Vec<Token>,
+}
+
+/// Del av en mal før plassholderne tolkes
+enum Part {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// Deler en mal i tekst og `{plassholdere}`. Uavsluttede `{` gir feil.
+fn split_placeholders(template: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '{' {
+            literal.push(c);
+            continue;
+        }
+
+        let mut name = String::new();
+        let mut closed = false;
+        for c in chars.by_ref() {
+            if c == '}' {
+                closed = true;
+                break;
+            }
+            name.push(c);
+        }
+        if !closed {
+            return Err(format!("Mangler '}}' i mal: {}", template));
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(std::mem::take(&mut literal)));
+        }
+        parts.push(Part::Placeholder(name.trim().to_string()));
+    }
+
+    if !literal.is_empty() {
+        parts.push(Part::Literal(literal));
+    }
+    Ok(parts)
+}
+
+impl FolderTemplate {
+    /// Parser en mal. Ukjente plassholdere og uavsluttede `{` gir feil.
+    pub fn parse(template: &str) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+
+        for part in split_placeholders(template)? {
+            let name = match part {
+                Part::Literal(text) => {
+                    tokens.push(Token::Literal(text));
+                    continue;
+                }
+                Part::Placeholder(name) => name,
+            };
+            tokens.push(match name.as_str() {
+                "year" => Token::Year,
+                "month" => Token::Month { padded: false },
+                "month:02" => Token::Month { padded: true },
+                "month_name" => Token::MonthName,
+                "day" => Token::Day { padded: false },
+                "day:02" => Token::Day { padded: true },
+                "camera_make" => Token::CameraMake,
+                "camera_model" => Token::CameraModel,
+                "lens" => Token::Lens,
+                "orientation" => Token::Orientation,
+                "ext" => Token::Ext,
+                other => return Err(format!("Ukjent plassholder i mal: {{{}}}", other)),
+            });
+        }
+
+        Ok(Self { tokens })
+    }
+
+    /// `true` hvis malen bruker kamera eller objektiv, slik at EXIF kun leses ved behov
+    pub fn needs_camera_info(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|t| matches!(t, Token::CameraMake | Token::CameraModel | Token::Lens))
+    }
+
+    /// `true` hvis malen bruker `{orientation}`, slik at dimensjonene kun leses ved behov
+    pub fn needs_orientation(&self) -> bool {
+        self.tokens.contains(&Token::Orientation)
+    }
+
+    /// Rendrer malen til en relativ sti
+    /// Tomme komponenter, `.` og `..` fjernes slik at resultatet alltid ligger under målmappen.
+    pub fn render(&self, ctx: &TemplateContext) -> PathBuf {
+        let mut rendered = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Literal(s) => rendered.push_str(s),
+                Token::Year => rendered.push_str(&ctx.year.to_string()),
+                Token::Month { padded: true } => rendered.push_str(&format!("{:02}", ctx.month)),
+                Token::Month { padded: false } => rendered.push_str(&ctx.month.to_string()),
+                Token::MonthName => {
+                    let name = (ctx.month as usize).checked_sub(1).and_then(|i| match ctx.month_names {
+                        Some(names) => names.get(i).map(|name| name.trim()),
+                        None => MONTH_NAMES.get(i).copied(),
+                    });
+                    rendered.push_str(name.unwrap_or_default());
+                }
+                Token::Day { padded: true } => rendered.push_str(&format!("{:02}", ctx.day)),
+                Token::Day { padded: false } => rendered.push_str(&ctx.day.to_string()),
+                // Verdier fra metadata kan inneholde skilletegn, så de saneres før innsetting
+                Token::CameraMake => {
+                    rendered.push_str(&sanitize_component(ctx.camera_make.unwrap_or(UNKNOWN_CAMERA)))
+                }
+                Token::CameraModel => {
+                    rendered.push_str(&sanitize_component(ctx.camera_model.unwrap_or(UNKNOWN_CAMERA)))
+                }
+                Token::Lens => rendered.push_str(&sanitize_component(ctx.lens.unwrap_or(UNKNOWN_LENS))),
+                Token::Orientation => rendered.push_str(
+                    ctx.orientation.map(|orientation| orientation.folder_name()).unwrap_or(UNKNOWN_ORIENTATION),
+                ),
+                Token::Ext => rendered.push_str(&sanitize_component(&ctx.ext.to_lowercase())),
+            }
+        }
+
+        rendered
+            .split(['/', '\\'])
+            .map(sanitize_component)
+            .filter(|c| !c.is_empty() && c != "." && c != "..")
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum NameToken {
+    Literal(String),
+    Date,
+    Time,
+    Year,
+    Month,
+    Day,
+    Counter { width: usize },
+    Name,
+    CameraModel,
+}
+
+/// Verdier som settes inn i et filnavnmønster for én fil
+#[derive(Debug, Clone)]
+pub struct RenameContext<'a> {
+    pub date: Option<DateTime<Local>>,
+    /// Løpenummer i operasjonen, fra 1
+    pub counter: usize,
+    /// Originalt filnavn uten endelse
+    pub name: &'a str,
+    pub camera_model: Option<&'a str>,
+}
+
+/// Et parset filnavnmønster for `rename_images`
+///
+/// Plassholdere: `{date}` (20240307), `{time}` (142501), `{year}`, `{month}`, `{day}`
+/// (alltid to sifre), `{counter}` eller `{counter:04}`, `{name}` og `{camera_model}`.
+/// Filendelsen beholdes og legges til automatisk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenamePattern {
+    tokens: Vec<NameToken>,
+}
+
+impl RenamePattern {
+    /// Parser et mønster. Skilletegn (`/`, `\`) gir feil, så filene blir i samme mappe.
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        if pattern.contains(['/', '\\']) {
+            return Err(format!("Mønsteret kan ikke inneholde mappeskilletegn: {}", pattern));
+        }
+
+        let mut tokens = Vec::new();
+        for part in split_placeholders(pattern)? {
+            let name = match part {
+                Part::Literal(text) => {
+                    tokens.push(NameToken::Literal(text));
+                    continue;
+                }
+                Part::Placeholder(name) => name,
+            };
+            tokens.push(match name.as_str() {
+                "date" => NameToken::Date,
+                "time" => NameToken::Time,
+                "year" => NameToken::Year,
+                "month" => NameToken::Month,
+                "day" => NameToken::Day,
+                "counter" => NameToken::Counter { width: 0 },
+                "name" => NameToken::Name,
+                "camera_model" => NameToken::CameraModel,
+                other => match other.strip_prefix("counter:").and_then(|w| w.parse().ok()) {
+                    Some(width) => NameToken::Counter { width },
+                    None => return Err(format!("Ukjent plassholder i mønster: {{{}}}", other)),
+                },
+            });
+        }
+
+        if tokens.is_empty() {
+            return Err("Mønsteret er tomt".to_string());
+        }
+        Ok(Self { tokens })
+    }
+
+    /// `true` hvis mønsteret bruker dato eller tid
+    pub fn needs_date(&self) -> bool {
+        self.tokens.iter().any(|t| {
+            matches!(t, NameToken::Date | NameToken::Time | NameToken::Year | NameToken::Month | NameToken::Day)
+        })
+    }
+
+    /// `true` hvis mønsteret bruker kameramodellen, slik at EXIF kun leses ved behov
+    pub fn needs_camera_info(&self) -> bool {
+        self.tokens.contains(&NameToken::CameraModel)
+    }
+
+    /// Rendrer filnavnet uten endelse. `None` hvis mønsteret trenger en dato filen mangler,
+    /// eller resultatet blir tomt.
+    pub fn render(&self, ctx: &RenameContext) -> Option<String> {
+        let mut rendered = String::new();
+        for token in &self.tokens {
+            match token {
+                NameToken::Literal(s) => rendered.push_str(s),
+                NameToken::Date => rendered.push_str(&ctx.date?.format("%Y%m%d").to_string()),
+                NameToken::Time => rendered.push_str(&ctx.date?.format("%H%M%S").to_string()),
+                NameToken::Year => rendered.push_str(&ctx.date?.year().to_string()),
+                NameToken::Month => rendered.push_str(&format!("{:02}", ctx.date?.month())),
+                NameToken::Day => rendered.push_str(&format!("{:02}", ctx.date?.day())),
+                NameToken::Counter { width } => rendered.push_str(&format!("{:0width$}", ctx.counter, width = *width)),
+                NameToken::Name => rendered.push_str(ctx.name),
+                NameToken::CameraModel => rendered.push_str(ctx.camera_model.unwrap_or(UNKNOWN_CAMERA)),
+            }
+        }
+
+        let name = sanitize_component(&rendered);
+        (!name.is_empty() && name != "." && name != "..").then_some(name)
+    }
+}
+
+/// Erstatter tegn som er ugyldige i mappenavn (på tvers av plattformer) med `_`
+/// og fjerner mellomrom og punktum i enden, som Windows ikke tillater.
+pub fn sanitize_component(component: &str) -> String {
+    let cleaned: String = component
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    cleaned.trim().trim_end_matches('.').trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::path::Path;
+
+    fn context(camera_model: Option<&str>) -> TemplateContext<'_> {
+        TemplateContext {
+            year: 2024,
+            month: 3,
+            day: 7,
+            camera_make: Some("Canon"),
+            camera_model,
+            lens: None,
+            orientation: None,
+            ext: "JPG",
+            month_names: None,
+        }
+    }
+
+    #[test]
+    fn test_render_all_placeholders() {
+        let template =
+            FolderTemplate::parse("{year}/{month:02}-{month_name}/{month}.{day}.{day:02}/{camera_model}/{ext}").unwrap();
+        let path = template.render(&context(Some("EOS R5")));
+
+        assert_eq!(path, Path::new("2024/03-Mars/3.7.07/EOS R5/jpg"));
+    }
+
+    #[test]
+    fn test_render_custom_month_names() {
+        let english = [
+            "January", "February", "March", "April", "May", "June",
+            "July", "August", "September", "October", "November", "December",
+        ]
+        .map(String::from);
+        assert!(validate_month_names(&english).is_ok());
+
+        let template = FolderTemplate::parse("{year}/{month:02} - {month_name}").unwrap();
+        let ctx = TemplateContext { month_names: Some(&english), ..context(None) };
+        assert_eq!(template.render(&ctx), Path::new("2024/03 - March"));
+
+        let mut invalid = english.clone();
+        invalid[4] = "  ".to_string();
+        assert!(validate_month_names(&invalid).is_err());
+        invalid[4] = "Mai/Juni".to_string();
+        assert!(validate_month_names(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_render_sanitizes_metadata() {
+        let template = FolderTemplate::parse("{year}/{camera_model}").unwrap();
+        assert!(template.needs_camera_info());
+
+        let path = template.render(&context(Some("Model: A/B ")));
+        assert_eq!(path, Path::new("2024/Model_ A_B"));
+
+        let path = template.render(&context(None));
+        assert_eq!(path, Path::new("2024/Ukjent kamera"));
+    }
+
+    #[test]
+    fn test_render_make_and_lens() {
+        let template = FolderTemplate::parse("{camera_make}/{lens}").unwrap();
+        assert!(template.needs_camera_info());
+        assert!(!FolderTemplate::parse("{year}/{ext}").unwrap().needs_camera_info());
+
+        let path = template.render(&context(None));
+        assert_eq!(path, Path::new("Canon/Ukjent objektiv"));
+    }
+
+    #[test]
+    fn test_render_orientation() {
+        let template = FolderTemplate::parse("{orientation}/{year}").unwrap();
+        assert!(template.needs_orientation());
+        assert!(!template.needs_camera_info());
+
+        let ctx = TemplateContext { orientation: Some(Orientation::Portrait), ..context(None) };
+        assert_eq!(template.render(&ctx), Path::new("Stående/2024"));
+        assert_eq!(template.render(&context(None)), Path::new("Ukjent format/2024"));
+    }
+
+    #[test]
+    fn test_render_drops_traversal_components() {
+        let template = FolderTemplate::parse("../{year}//./x").unwrap();
+        assert_eq!(template.render(&context(None)), Path::new("2024/x"));
+    }
+
+    #[test]
+    fn test_rename_pattern() {
+        let pattern = RenamePattern::parse("{date}_{time}_{counter:03}-{name}").unwrap();
+        assert!(pattern.needs_date());
+        let ctx = RenameContext {
+            date: Local.with_ymd_and_hms(2024, 3, 7, 14, 25, 1).single(),
+            counter: 7,
+            name: "IMG_0001",
+            camera_model: Some("EOS R5"),
+        };
+        assert_eq!(pattern.render(&ctx).unwrap(), "20240307_142501_007-IMG_0001");
+
+        let undated = RenameContext { date: None, ..ctx.clone() };
+        assert_eq!(pattern.render(&undated), None);
+        let model = RenamePattern::parse("{camera_model}: {counter}").unwrap();
+        assert_eq!(model.render(&undated).unwrap(), "EOS R5_ 7");
+
+        assert!(RenamePattern::parse("{year}/{name}").is_err());
+        assert!(RenamePattern::parse("{counter:x}").is_err());
+        assert!(RenamePattern::parse("").is_err());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(FolderTemplate::parse("{year}/{unknown}").is_err());
+        assert!(FolderTemplate::parse("{year").is_err());
+    }
+}
@@ -2,55 +2,458 @@
 //!
 //! Genererer thumbnails på forespørsel og cacher dem for raskere lasting.
 
-// use image::GenericImageView;
+use crate::services::jpeg::{self, ChromaSubsampling};
+use crate::services::scanner::{self, MediaType};
+use crate::services::{heic, metadata, raw};
+use base64::Engine;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
 
 /// Standard thumbnail-størrelse
 pub const THUMBNAIL_SIZE: u32 = 200;
 
-/// Henter eller genererer en thumbnail for et bilde
+/// Versjon av thumbnail-formatet. Økes når genereringen endres slik at gamle
+/// thumbnails i cachen (f.eks. uten EXIF-rotasjon) blir generert på nytt.
+const CACHE_VERSION: u8 = 2;
+
+/// Standard JPEG-kvalitet (samme som `image`-cratets standard)
+pub const DEFAULT_QUALITY: u8 = 75;
+
+/// Filendelser for thumbnails (og blurhasher) som kan ligge i cache-mappen
+const THUMBNAIL_EXTENSIONS: [&str; 5] = ["jpg", "png", "webp", "avif", "blurhash"];
+
+/// Antall komponenter (bredde, høyde) i en blurhash; 4x3 passer de fleste bildeformater
+const BLURHASH_COMPONENTS: (u32, u32) = (4, 3);
+
+/// Thumbnailen skaleres hit før blurhash-kodingen, som uansett bare ser grove farger
+const BLURHASH_SOURCE_SIZE: u32 = 32;
+
+/// Standard maksstørrelse for thumbnail-cachen
+pub const DEFAULT_MAX_CACHE_BYTES: u64 = 500 * 1024 * 1024;
+
+/// Indeks over når hver thumbnail sist ble brukt, lagret i cache-mappen
+const ACCESS_INDEX_FILE: &str = "access_index.json";
+
+/// Innstillinger for thumbnail-generering
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ThumbnailConfig {
+    /// Maks lengde på lengste side i piksler
+    pub max_edge: u32,
+    /// "jpeg", "png", "webp" eller "avif"; de to siste krever featurene `webp`/`avif`
+    pub format: String,
+    /// Kvalitet 1-100 (JPEG, WebP og AVIF)
+    pub quality: u8,
+    /// Chroma subsampling for JPEG. Gjelder ikke video-thumbnails, som lages av ffmpeg.
+    pub subsampling: ChromaSubsampling,
+    /// Maks samlet størrelse på cachen; eldst brukte thumbnails slettes over grensen.
+    /// 0 = ubegrenset.
+    pub max_cache_bytes: u64,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            max_edge: THUMBNAIL_SIZE,
+            format: "jpeg".to_string(),
+            quality: DEFAULT_QUALITY,
+            subsampling: ChromaSubsampling::default(),
+            max_cache_bytes: DEFAULT_MAX_CACHE_BYTES,
+        }
+    }
+}
+
+impl ThumbnailConfig {
+    /// Sjekker at formatet er kjent og kompilert inn, uten å se på kvaliteten
+    pub fn check_format(&self) -> Result<(), String> {
+        self.format_codec().map(|_| ())
+    }
+
+    /// Kodeken for konfigurasjonen
+    /// Kvalitet utenfor 1-100 gir feil.
+    fn codec(&self) -> Result<ThumbnailCodec, String> {
+        jpeg::validate_quality(self.quality)?;
+        self.format_codec()
+    }
+
+    fn format_codec(&self) -> Result<ThumbnailCodec, String> {
+        let quality = self.quality;
+        let codec = match self.format.to_lowercase().as_str() {
+            "jpeg" | "jpg" => ThumbnailCodec::Jpeg(quality, self.subsampling),
+            "png" => ThumbnailCodec::Png,
+            "webp" => ThumbnailCodec::WebP(quality),
+            "avif" => ThumbnailCodec::Avif(quality),
+            other => return Err(format!("Ukjent thumbnail-format: {}", other)),
+        };
+        codec.available()
+    }
+}
+
+/// Kodek for thumbnails i cachen
+///
+/// WebP og AVIF krever featurene `webp` og `avif`; uten dem brukes JPEG.
+/// AVIF-koding er vesentlig tregere enn de andre kodekene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ThumbnailCodec {
+    Jpeg(u8, ChromaSubsampling),
+    Png,
+    WebP(u8),
+    Avif(u8),
+}
+
+impl ThumbnailCodec {
+    /// Filendelsen er en del av cache-nøkkelen, så hver kodek får egne filer
+    fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailCodec::Jpeg(..) => "jpg",
+            ThumbnailCodec::Png => "png",
+            ThumbnailCodec::WebP(_) => "webp",
+            ThumbnailCodec::Avif(_) => "avif",
+        }
+    }
+
+    /// Feil når koderen ikke er kompilert inn, i stedet for å lage et annet format enn forespurt
+    fn available(self) -> Result<Self, String> {
+        match self {
+            ThumbnailCodec::Avif(_) if !cfg!(feature = "avif") => {
+                Err("AVIF-thumbnails krever at featuren `avif` er kompilert inn".to_string())
+            }
+            ThumbnailCodec::WebP(_) if !cfg!(feature = "webp") => {
+                Err("WebP-thumbnails krever at featuren `webp` er kompilert inn".to_string())
+            }
+            codec => Ok(codec),
+        }
+    }
+
+    /// ffmpeg lager video-thumbnails, og kan ikke regnes med å ha WebP/AVIF-støtte
+    fn for_video(self) -> Self {
+        match self {
+            ThumbnailCodec::WebP(quality) | ThumbnailCodec::Avif(quality) => {
+                ThumbnailCodec::Jpeg(quality, ChromaSubsampling::default())
+            }
+            codec => codec,
+        }
+    }
+
+    fn encode(&self, img: &DynamicImage) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut bytes = Vec::new();
+        match *self {
+            ThumbnailCodec::Jpeg(quality, subsampling) => bytes = jpeg::encode(img, quality, subsampling)?,
+            ThumbnailCodec::Png => img.write_to(&mut bytes, ImageOutputFormat::Png)?,
+            ThumbnailCodec::WebP(quality) => bytes = encode_webp(img, quality)?,
+            ThumbnailCodec::Avif(quality) => bytes = encode_avif(img, quality)?,
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(feature = "webp")]
+fn encode_webp(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let rgb = img.to_rgb8();
+    let encoded = webp::Encoder::from_rgb(&rgb, rgb.width(), rgb.height()).encode(quality as f32);
+    Ok(encoded.to_vec())
+}
+
+#[cfg(not(feature = "webp"))]
+fn encode_webp(_img: &DynamicImage, _quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    Err("WebP-støtte er ikke kompilert inn".into())
+}
+
+#[cfg(feature = "avif")]
+fn encode_avif(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    let rgb = img.to_rgb8();
+    let pixels: Vec<ravif::RGB8> = rgb.pixels().map(|p| ravif::RGB8::new(p[0], p[1], p[2])).collect();
+    let encoded = ravif::Encoder::new()
+        .with_quality(quality as f32)
+        // Høyeste hastighet: thumbnails er små, og kodingen er ellers svært treg
+        .with_speed(10)
+        .encode_rgb(ravif::Img::new(&pixels, rgb.width() as usize, rgb.height() as usize))?;
+    Ok(encoded.avif_file)
+}
+
+#[cfg(not(feature = "avif"))]
+fn encode_avif(_img: &DynamicImage, _quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+    Err("AVIF-støtte er ikke kompilert inn".into())
+}
+
+/// Henter eller genererer en thumbnail for et bilde med standard innstillinger
 /// Returnerer stien til thumbnail-filen
 pub fn get_or_create_thumbnail(
     image_path: &Path,
     cache_dir: &Path,
 ) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
-    // Generer unik cache-nøkkel basert på filsti og mtime
-    let cache_key = generate_cache_key(image_path)?;
-    let thumbnail_path = cache_dir.join(format!("{}.jpg", cache_key));
+    get_or_create_thumbnail_with_config(image_path, cache_dir, &ThumbnailConfig::default())
+}
 
-    // Returner cached thumbnail hvis den finnes
-    if thumbnail_path.exists() {
-        return Ok(thumbnail_path);
+/// En thumbnail og om originalen er animert
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThumbnailInfo {
+    pub path: PathBuf,
+    /// Animert GIF; thumbnailen viser første bilde, så UI kan vise et avspillingsmerke
+    pub animated: bool,
+}
+
+/// Som `get_or_create_thumbnail_with_config`, men forteller også om bildet er animert
+pub fn get_thumbnail_info(
+    image_path: &Path,
+    cache_dir: &Path,
+    config: &ThumbnailConfig,
+) -> Result<ThumbnailInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let path = get_or_create_thumbnail_with_config(image_path, cache_dir, config)?;
+    Ok(ThumbnailInfo {
+        path,
+        animated: is_animated_gif(image_path),
+    })
+}
+
+/// Stien til en allerede generert thumbnail, uten å generere den eller røre LRU-indeksen
+pub fn cached_thumbnail(image_path: &Path, cache_dir: &Path, config: &ThumbnailConfig) -> Option<PathBuf> {
+    let (thumbnail_path, _, _) = thumbnail_target(image_path, cache_dir, config).ok()?;
+    thumbnail_path.exists().then_some(thumbnail_path)
+}
+
+/// Thumbnail-sti, kodek og om kilden er en video
+fn thumbnail_target(
+    image_path: &Path,
+    cache_dir: &Path,
+    config: &ThumbnailConfig,
+) -> Result<(PathBuf, ThumbnailCodec, bool), Box<dyn std::error::Error + Send + Sync>> {
+    if config.max_edge == 0 {
+        return Err("Thumbnail-størrelse må være større enn 0".into());
     }
+    let codec = config.codec()?;
 
-    // Sørg for at cache-mappen finnes
-    fs::create_dir_all(cache_dir)?;
+    let ext = image_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let is_video = MediaType::from_extension(&ext) == MediaType::Video;
+    let codec = if is_video { codec.for_video() } else { codec };
+
+    // Generer unik cache-nøkkel basert på filsti og mtime, pluss innstillingene
+    let cache_key = generate_cache_key(image_path)?;
+    // 4:4:4 har ingen suffiks, så thumbnails fra før subsampling ble valgbar gjenbrukes
+    let subsampling = match codec {
+        ThumbnailCodec::Jpeg(_, subsampling) if subsampling != ChromaSubsampling::Yuv444 => {
+            format!("_{}", subsampling.as_str())
+        }
+        _ => String::new(),
+    };
+    let thumbnail_path = cache_dir.join(format!(
+        "{}_{}_q{}{}.{}",
+        cache_key, config.max_edge, config.quality, subsampling, codec.extension()
+    ));
+    Ok((thumbnail_path, codec, is_video))
+}
 
+/// Henter eller genererer en thumbnail med gitt størrelse, format og kvalitet
+/// Konfigurasjonen er en del av filnavnet, så endrede innstillinger gir en ny thumbnail.
+pub fn get_or_create_thumbnail_with_config(
+    image_path: &Path,
+    cache_dir: &Path,
+    config: &ThumbnailConfig,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let (thumbnail_path, codec, is_video) = thumbnail_target(image_path, cache_dir, config)?;
     let ext = image_path
         .extension()
         .and_then(|e| e.to_str())
         .map(|e| e.to_lowercase())
         .unwrap_or_default();
 
-    let video_extensions = ["mp4", "mov", "avi", "mkv", "webm", "wmv", "m4v"];
+    let file_name = thumbnail_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
 
-    if video_extensions.contains(&ext.as_str()) {
-        generate_video_thumbnail(image_path, &thumbnail_path)?;
+    // Returner cached thumbnail hvis den finnes
+    if thumbnail_path.exists() {
+        with_access_index(cache_dir, |index| index.touch(&file_name));
+        return Ok(thumbnail_path);
+    }
+
+    // Sørg for at cache-mappen finnes
+    fs::create_dir_all(cache_dir)?;
+
+    if is_video {
+        generate_video_thumbnail(image_path, &thumbnail_path, config.max_edge)?;
     } else {
-        // Last og resize bildet (Opprinnelig logikk)
-        let img = load_image(image_path)?;
-        let thumbnail = img.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
-        // Lagre som JPEG med god komprimering
-        thumbnail.save(&thumbnail_path)?;
+        // Last bildet, roter etter EXIF-orientering og resize
+        // RAW kan ikke dekodes direkte, så vi bruker den innebygde JPEG-forhåndsvisningen
+        let img = if raw::is_raw_file(image_path) {
+            raw::extract_preview(image_path)?
+        } else if heic::is_heic_file(image_path) {
+            heic::decode(image_path)?
+        } else if ext == "gif" {
+            load_first_gif_frame(image_path)?
+        } else if let Some(thumb) = load_exif_thumbnail(image_path, config.max_edge) {
+            thumb
+        } else {
+            load_image(image_path)?
+        };
+        let orientation = if heic::is_oriented_on_decode(image_path) {
+            None
+        } else {
+            metadata::read_orientation(image_path)
+        };
+        let img = match orientation {
+            Some(orientation) => apply_orientation(img, orientation),
+            None => img,
+        };
+        let thumbnail = img.thumbnail(config.max_edge, config.max_edge);
+        let encoded = codec.encode(&thumbnail)?;
+        if let Err(e) = fs::write(&thumbnail_path, encoded) {
+            // Ikke la en halvskrevet fil ligge igjen i cachen
+            let _ = fs::remove_file(&thumbnail_path);
+            return Err(e.into());
+        }
     }
 
+    let size = fs::metadata(&thumbnail_path).map(|m| m.len()).unwrap_or(0);
+    with_access_index(cache_dir, |index| {
+        index.touch(&file_name);
+        index.add_bytes(cache_dir, size);
+        if config.max_cache_bytes > 0 {
+            index.evict(cache_dir, config.max_cache_bytes, &file_name);
+        }
+        index.save(cache_dir);
+    });
+
     Ok(thumbnail_path)
 }
 
-fn generate_video_thumbnail(input: &Path, output: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// Blurhash for et bilde, som plassholder i rutenettet mens thumbnailen lastes
+/// Beregnes fra en nedskalert standard-thumbnail (genereres ved behov) og caches i
+/// cache-mappen med samme nøkkel som thumbnailen (sti + mtime).
+pub fn compute_blurhash(image_path: &Path, cache_dir: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let cached = cache_dir.join(format!("{}.blurhash", generate_cache_key(image_path)?));
+    if let Ok(hash) = fs::read_to_string(&cached) {
+        if !hash.is_empty() {
+            return Ok(hash);
+        }
+    }
+
+    let thumbnail_path = get_or_create_thumbnail(image_path, cache_dir)?;
+    let small = image::open(&thumbnail_path)?
+        .thumbnail(BLURHASH_SOURCE_SIZE, BLURHASH_SOURCE_SIZE)
+        .to_rgba8();
+    let (components_x, components_y) = BLURHASH_COMPONENTS;
+    let hash = blurhash::encode(components_x, components_y, small.width(), small.height(), small.as_raw())?;
+    let _ = fs::write(&cached, &hash);
+    Ok(hash)
+}
+
+/// LRU-indeks for thumbnail-cachen
+///
+/// Filsystemets atime er ofte slått av, så bruk spores selv: hver thumbnail får et
+/// stigende løpenummer når den hentes. Indeksen holdes i minnet og lagres når nye
+/// thumbnails genereres; filer uten oppføring regnes som eldst.
+#[derive(Serialize, Deserialize, Default)]
+struct AccessIndex {
+    /// Filnavn -> løpenummer for siste bruk
+    last_access: HashMap<String, u64>,
+    clock: u64,
+    /// Samlet størrelse på thumbnails, beregnes ved første behov
+    #[serde(skip)]
+    total_bytes: Option<u64>,
+}
+
+/// Én indeks per cache-mappe, delt mellom trådene som genererer thumbnails
+static ACCESS_INDEXES: LazyLock<Mutex<HashMap<PathBuf, AccessIndex>>> = LazyLock::new(Default::default);
+
+fn with_access_index<R>(cache_dir: &Path, f: impl FnOnce(&mut AccessIndex) -> R) -> R {
+    let mut indexes = ACCESS_INDEXES.lock().unwrap_or_else(|e| e.into_inner());
+    let index = indexes
+        .entry(cache_dir.to_path_buf())
+        .or_insert_with(|| AccessIndex::load(cache_dir));
+    f(index)
+}
+
+impl AccessIndex {
+    fn load(cache_dir: &Path) -> Self {
+        fs::read_to_string(cache_dir.join(ACCESS_INDEX_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, cache_dir: &Path) {
+        if let Ok(content) = serde_json::to_string(self) {
+            let _ = fs::write(cache_dir.join(ACCESS_INDEX_FILE), content);
+        }
+    }
+
+    fn touch(&mut self, file_name: &str) {
+        self.clock += 1;
+        self.last_access.insert(file_name.to_string(), self.clock);
+    }
+
+    fn add_bytes(&mut self, cache_dir: &Path, size: u64) {
+        self.total_bytes = Some(match self.total_bytes {
+            Some(total) => total + size,
+            // Første gang telles hele mappen, inkludert filen som nettopp ble laget
+            None => thumbnail_files(cache_dir).iter().map(|(_, size)| size).sum(),
+        });
+    }
+
+    /// Sletter minst nylig brukte thumbnails til cachen er under `max_bytes`.
+    /// `keep` (thumbnailen som nettopp ble laget) slettes aldri.
+    fn evict(&mut self, cache_dir: &Path, max_bytes: u64, keep: &str) {
+        if self.total_bytes.unwrap_or(0) <= max_bytes {
+            return;
+        }
+
+        let mut files = thumbnail_files(cache_dir);
+        let mut total: u64 = files.iter().map(|(_, size)| size).sum();
+        files.sort_by_key(|(name, _)| (self.last_access.get(name).copied().unwrap_or(0), name.clone()));
+
+        for (name, size) in files {
+            if total <= max_bytes {
+                break;
+            }
+            if name != keep && fs::remove_file(cache_dir.join(&name)).is_ok() {
+                total -= size;
+                self.last_access.remove(&name);
+            }
+        }
+        self.total_bytes = Some(total);
+    }
+}
+
+fn is_thumbnail_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| THUMBNAIL_EXTENSIONS.contains(&e))
+        .unwrap_or(false)
+}
+
+/// Thumbnail-filer i cache-mappen som (filnavn, størrelse)
+fn thumbnail_files(cache_dir: &Path) -> Vec<(String, u64)> {
+    let Ok(entries) = fs::read_dir(cache_dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| is_thumbnail_file(&entry.path()))
+        .map(|entry| {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            (entry.file_name().to_string_lossy().to_string(), size)
+        })
+        .collect()
+}
+
+fn generate_video_thumbnail(
+    input: &Path,
+    output: &Path,
+    max_edge: u32,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     use std::process::Command;
     
     // Bruk ffmpeg til å hente ut en frame
@@ -58,14 +461,20 @@ fn generate_video_thumbnail(input: &Path, output: &Path) -> Result<(), Box<dyn s
     // -ss: seek til 1 sekund (unngå svart start-frame)
     // -i: input
     // -vframes 1: kun ett bilde
+    // -vf scale: skaler ned til maks kantlengde med bevart aspect ratio
     // -q:v 2: god kvalitet jpeg
-    
+    let scale = format!(
+        "scale=w='min({0},iw)':h='min({0},ih)':force_original_aspect_ratio=decrease",
+        max_edge
+    );
+
     let status = Command::new("ffmpeg")
         .args(&[
             "-y",
             "-ss", "00:00:01",
             "-i", input.to_str().unwrap_or_default(), // todo: handle formatting error?
             "-vframes", "1",
+            "-vf", &scale,
             "-q:v", "2",
             output.to_str().unwrap_or_default(),
         ])
@@ -78,6 +487,55 @@ fn generate_video_thumbnail(input: &Path, output: &Path) -> Result<(), Box<dyn s
     Ok(())
 }
 
+/// Roterer/speiler et bilde slik at det vises riktig vei i henhold til EXIF-orientering
+/// 1 = normal, 2 = speilet, 3 = 180°, 4 = speilet vertikalt,
+/// 5 = transponert, 6 = 90° med klokka, 7 = transversert, 8 = 270° med klokka
+pub fn apply_orientation(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// Tillatt avvik i sideforhold mellom EXIF-thumbnailen og hovedbildet
+const EXIF_THUMBNAIL_ASPECT_TOLERANCE: f64 = 0.02;
+
+/// Den innebygde EXIF-thumbnailen i en JPEG, hvis den er stor nok til å brukes i stedet for hele bildet
+///
+/// Sparer full dekoding av store JPEG-er, som dominerer tiden for thumbnail-generering.
+/// Kameraer lagrer som regel bare 160x120, som er for lite for standardstørrelsen, så gevinsten
+/// gjelder mest telefoner og kameraer med større thumbnails, eller en lavere `max_edge`.
+/// Thumbnails med annet sideforhold enn bildet (svarte kanter, eller rotert i et
+/// redigeringsprogram uten at thumbnailen ble oppdatert) forkastes.
+fn load_exif_thumbnail(path: &Path, max_edge: u32) -> Option<DynamicImage> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if ext != "jpg" && ext != "jpeg" {
+        return None;
+    }
+    let bytes = metadata::read_exif_thumbnail(path)?;
+    let thumb = image::load_from_memory_with_format(&bytes, ImageFormat::Jpeg).ok()?;
+    let (width, height) = thumb.dimensions();
+    if width.max(height) < max_edge || height == 0 {
+        return None;
+    }
+    let (full_width, full_height) = scanner::read_dimensions(path)?;
+    if full_height == 0 {
+        return None;
+    }
+    let full_aspect = full_width as f64 / full_height as f64;
+    let aspect = width as f64 / height as f64;
+    if (aspect - full_aspect).abs() / full_aspect > EXIF_THUMBNAIL_ASPECT_TOLERANCE {
+        return None;
+    }
+    Some(thumb)
+}
+
 /// Laster et bilde fra fil
 fn load_image(path: &Path) -> Result<image::DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
     let mut file = File::open(path)?;
@@ -87,6 +545,79 @@ fn load_image(path: &Path) -> Result<image::DynamicImage, Box<dyn std::error::Er
     Ok(img)
 }
 
+/// Dekoder første bilde i en GIF, komponert på hele lerretet
+/// Animerte GIF-er gir da samme thumbnail uansett hvordan resten av animasjonen ser ut.
+fn load_first_gif_frame(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+    use image::AnimationDecoder;
+
+    let decoder = image::codecs::gif::GifDecoder::new(BufReader::new(File::open(path)?))?;
+    let frame = decoder.into_frames().next().ok_or("GIF-filen inneholder ingen bilder")??;
+    Ok(DynamicImage::ImageRgba8(frame.into_buffer()))
+}
+
+/// `true` for GIF-er med mer enn ett bilde
+pub fn is_animated_gif(path: &Path) -> bool {
+    let is_gif = path
+        .extension()
+        .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case("gif"));
+    is_gif
+        && File::open(path)
+            .ok()
+            .and_then(|file| gif_frame_count(BufReader::new(file), 2))
+            .is_some_and(|frames| frames > 1)
+}
+
+/// Teller bildene i en GIF ved å gå gjennom blokkstrukturen, uten å dekode pikslene
+/// Stopper ved `limit`. `None` hvis filen ikke er en gyldig GIF.
+fn gif_frame_count(mut reader: impl Read, limit: usize) -> Option<usize> {
+    let byte = |reader: &mut dyn Read| {
+        let mut buf = [0u8; 1];
+        reader.read_exact(&mut buf).ok().map(|_| buf[0])
+    };
+    let skip = |reader: &mut dyn Read, count: u64| {
+        std::io::copy(&mut reader.take(count), &mut std::io::sink()).ok().filter(|&n| n == count)
+    };
+    // Datablokker: lengdebyte + data, avsluttet av en tom blokk
+    let skip_sub_blocks = |reader: &mut dyn Read| loop {
+        let mut len = [0u8; 1];
+        reader.read_exact(&mut len).ok()?;
+        if len[0] == 0 {
+            return Some(());
+        }
+        skip(reader, len[0] as u64)?;
+    };
+    let color_table_len = |packed: u8| if packed & 0x80 != 0 { 3u64 << ((packed & 0x07) + 1) } else { 0 };
+
+    let mut header = [0u8; 13];
+    reader.read_exact(&mut header).ok()?;
+    if &header[..3] != b"GIF" {
+        return None;
+    }
+    skip(&mut reader, color_table_len(header[10]))?;
+
+    let mut frames = 0;
+    while frames < limit {
+        match byte(&mut reader) {
+            // Bildebeskrivelse: posisjon og størrelse, ev. lokal fargetabell, LZW-størrelse og data
+            Some(0x2C) => {
+                let mut descriptor = [0u8; 9];
+                reader.read_exact(&mut descriptor).ok()?;
+                skip(&mut reader, color_table_len(descriptor[8]) + 1)?;
+                skip_sub_blocks(&mut reader)?;
+                frames += 1;
+            }
+            // Utvidelse: etikett og datablokker
+            Some(0x21) => {
+                byte(&mut reader)?;
+                skip_sub_blocks(&mut reader)?;
+            }
+            // Slutt på filen (0x3B), eller avkortet fil
+            _ => break,
+        }
+    }
+    Some(frames)
+}
+
 /// Genererer en unik cache-nøkkel for et bilde basert på sti og mtime
 pub fn generate_cache_key(path: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
     let metadata = fs::metadata(path)?;
@@ -102,34 +633,92 @@ pub fn generate_cache_key(path: &Path) -> Result<String, Box<dyn std::error::Err
     let mut hasher = Sha256::new();
     hasher.update(path.to_string_lossy().as_bytes());
     hasher.update(mtime.to_le_bytes());
+    hasher.update([CACHE_VERSION]);
     let result = hasher.finalize();
 
     Ok(hex::encode(&result[..16])) // Bruk kun første 16 bytes for kortere filnavn
 }
 
+/// Leser en thumbnail og returnerer den som `data:`-URL
+/// MIME-typen bestemmes fra filinnholdet, ikke filendelsen
+pub fn thumbnail_data_url(thumbnail_path: &Path) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+    let bytes = fs::read(thumbnail_path)?;
+    // `image` 0.23 kjenner ikke igjen AVIF (ISO BMFF med merket "avif")
+    let mime = if bytes.get(4..12) == Some(b"ftypavif".as_slice()) {
+        "image/avif"
+    } else {
+        match image::guess_format(&bytes)? {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::WebP => "image/webp",
+            ImageFormat::Bmp => "image/bmp",
+            other => return Err(format!("Ukjent thumbnail-format: {:?}", other).into()),
+        }
+    };
+    let encoded = base64::engine::general_purpose::STANDARD.encode(&bytes);
+    Ok(format!("data:{};base64,{}", mime, encoded))
+}
+
+/// Resultat av å tømme thumbnail-cachen
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheClearResult {
+    pub files_removed: usize,
+    pub bytes_freed: u64,
+    /// Filer som ikke kunne slettes; resten av cachen er likevel tømt
+    pub errors: Vec<String>,
+}
+
 /// Sletter alle thumbnails i cache-mappen
 pub fn clear_cache(cache_dir: &Path) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(clear_cache_with_stats(cache_dir)?.files_removed)
+}
+
+/// Sletter alle thumbnails i cache-mappen og teller opp frigjort plass
+/// En mappe som ikke finnes gir nuller. Feil for enkeltfiler samles opp i stedet for å stoppe.
+pub fn clear_cache_with_stats(cache_dir: &Path) -> Result<CacheClearResult, Box<dyn std::error::Error + Send + Sync>> {
+    let mut result = CacheClearResult::default();
     if !cache_dir.exists() {
-        return Ok(0);
+        return Ok(result);
     }
 
-    let mut count = 0;
     for entry in fs::read_dir(cache_dir)? {
-        if let Ok(entry) = entry {
-            if entry.path().extension().map(|e| e == "jpg").unwrap_or(false) {
-                if fs::remove_file(entry.path()).is_ok() {
-                    count += 1;
-                }
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                result.errors.push(e.to_string());
+                continue;
+            }
+        };
+        let path = entry.path();
+        if !is_thumbnail_file(&path) {
+            continue;
+        }
+
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        match fs::remove_file(&path) {
+            Ok(_) => {
+                result.files_removed += 1;
+                result.bytes_freed += size;
             }
+            Err(e) => result.errors.push(format!("{}: {}", path.display(), e)),
         }
     }
-    Ok(count)
+
+    // LRU-indeksen gjelder ikke lenger
+    ACCESS_INDEXES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(cache_dir);
+    let _ = fs::remove_file(cache_dir.join(ACCESS_INDEX_FILE));
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use image::{Rgba, RgbaImage};
+    use image::{GenericImageView, Rgba, RgbaImage};
     use tempfile::tempdir;
 
     /// Lager et test-bilde som kan lagres til disk
@@ -187,6 +776,7 @@ mod tests {
         // Lag et test-bilde
         let img = create_test_image(400, 400);
         img.save(&image_path).unwrap();
+        assert_eq!(cached_thumbnail(&image_path, &cache_dir, &ThumbnailConfig::default()), None);
 
         // Generer thumbnail
         let result = get_or_create_thumbnail(&image_path, &cache_dir);
@@ -194,6 +784,10 @@ mod tests {
 
         let thumbnail_path = result.unwrap();
         assert!(thumbnail_path.exists());
+        assert_eq!(
+            cached_thumbnail(&image_path, &cache_dir, &ThumbnailConfig::default()).as_ref(),
+            Some(&thumbnail_path)
+        );
         assert!(thumbnail_path.to_string_lossy().ends_with(".jpg"));
 
         // Verifiser at thumbnail er mindre enn originalen
@@ -203,6 +797,109 @@ mod tests {
         assert!(height <= THUMBNAIL_SIZE);
     }
 
+    /// JPEG av `main` med `thumb` som EXIF-thumbnail (tom IFD0, IFD1 med JPEG-offset og -lengde)
+    fn jpeg_with_exif_thumbnail(main: &DynamicImage, thumb: &DynamicImage) -> Vec<u8> {
+        let encode = |img: &DynamicImage| {
+            let mut bytes = Vec::new();
+            img.write_to(&mut std::io::Cursor::new(&mut bytes), ImageOutputFormat::Jpeg(90)).unwrap();
+            bytes
+        };
+        let (main, thumb) = (encode(main), encode(thumb));
+
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+        // IFD0 uten felter, neste IFD (IFD1) rett etter
+        tiff.extend_from_slice(&0u16.to_le_bytes());
+        tiff.extend_from_slice(&14u32.to_le_bytes());
+        // IFD1: to LONG-felter, deretter thumbnailen
+        let thumb_offset = 14 + 2 + 2 * 12 + 4;
+        tiff.extend_from_slice(&2u16.to_le_bytes());
+        for (tag, value) in [(0x0201u16, thumb_offset as u32), (0x0202, thumb.len() as u32)] {
+            tiff.extend_from_slice(&tag.to_le_bytes());
+            tiff.extend_from_slice(&4u16.to_le_bytes());
+            tiff.extend_from_slice(&1u32.to_le_bytes());
+            tiff.extend_from_slice(&value.to_le_bytes());
+        }
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        tiff.extend_from_slice(&thumb);
+
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        app1.extend_from_slice(b"Exif\0\0");
+        app1.extend_from_slice(&tiff);
+        [&main[..2], &app1, &main[2..]].concat()
+    }
+
+    #[test]
+    fn test_exif_thumbnail_fast_path() {
+        let dir = tempdir().unwrap();
+        let red = DynamicImage::ImageRgb8(image::RgbImage::from_pixel(400, 300, image::Rgb([255, 0, 0])));
+        let blue = |width, height| DynamicImage::ImageRgb8(image::RgbImage::from_pixel(width, height, image::Rgb([0, 0, 255])));
+        let thumbnail_color = |name: &str, thumb: &DynamicImage, max_edge: u32| {
+            let path = dir.path().join(name);
+            fs::write(&path, jpeg_with_exif_thumbnail(&red, thumb)).unwrap();
+            let config = ThumbnailConfig { max_edge, ..ThumbnailConfig::default() };
+            let thumbnail = get_or_create_thumbnail_with_config(&path, &dir.path().join("cache"), &config).unwrap();
+            let img = image::open(thumbnail).unwrap();
+            let pixel = img.get_pixel(img.width() / 2, img.height() / 2);
+            if pixel[2] > pixel[0] { "blue" } else { "red" }
+        };
+
+        // Stor nok og samme sideforhold: thumbnailen brukes
+        assert_eq!(thumbnail_color("stor.jpg", &blue(240, 180), 200), "blue");
+        // For liten for ønsket størrelse
+        assert_eq!(thumbnail_color("liten.jpg", &blue(160, 120), 200), "red");
+        // Feil sideforhold (f.eks. ikke rotert sammen med bildet)
+        assert_eq!(thumbnail_color("rotert.jpg", &blue(180, 240), 200), "red");
+    }
+
+    #[test]
+    fn test_compute_blurhash_is_cached() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let image_path = dir.path().join("bilde.png");
+        create_test_image(300, 200).save(&image_path).unwrap();
+
+        let hash = compute_blurhash(&image_path, &cache_dir).unwrap();
+        // 4x3 komponenter: størrelse, maksverdi, DC (4 tegn) og 11 AC-komponenter (2 tegn hver)
+        assert_eq!(hash.len(), 28);
+        let cached = cache_dir.join(format!("{}.blurhash", generate_cache_key(&image_path).unwrap()));
+        assert_eq!(fs::read_to_string(&cached).unwrap(), hash);
+        assert_eq!(compute_blurhash(&image_path, &cache_dir).unwrap(), hash);
+
+        // Tømming av cachen tar blurhashene også
+        clear_cache(&cache_dir).unwrap();
+        assert!(!cached.exists());
+    }
+
+    #[test]
+    fn test_animated_gif_uses_first_frame() {
+        use image::codecs::gif::GifEncoder;
+        use image::Frame;
+
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let animated = dir.path().join("animert.gif");
+        let still = dir.path().join("stille.gif");
+
+        let red = RgbaImage::from_pixel(40, 20, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(40, 20, Rgba([0, 0, 255, 255]));
+        GifEncoder::new(File::create(&animated).unwrap())
+            .encode_frames(vec![Frame::new(red.clone()), Frame::new(blue)])
+            .unwrap();
+        DynamicImage::ImageRgba8(red).save(&still).unwrap();
+
+        let info = get_thumbnail_info(&animated, &cache_dir, &ThumbnailConfig::default()).unwrap();
+        assert!(info.animated);
+        let thumb = image::open(&info.path).unwrap().to_rgba8();
+        let pixel = thumb.get_pixel(thumb.width() / 2, thumb.height() / 2);
+        assert!(pixel[0] > 200 && pixel[2] < 50, "Første bilde (rødt) skal brukes, fikk {:?}", pixel);
+
+        let info = get_thumbnail_info(&still, &cache_dir, &ThumbnailConfig::default()).unwrap();
+        assert!(!info.animated);
+        assert_eq!(image::open(&info.path).unwrap().dimensions(), (200, 100));
+    }
+
     #[test]
     fn test_thumbnail_caching() {
         let dir = tempdir().unwrap();
@@ -243,16 +940,34 @@ mod tests {
         // Lag noen test-thumbnails
         fs::write(cache_dir.join("thumb1.jpg"), "fake").unwrap();
         fs::write(cache_dir.join("thumb2.jpg"), "fake").unwrap();
+        fs::write(cache_dir.join("thumb3.png"), "fake").unwrap();
         fs::write(cache_dir.join("other.txt"), "not a thumbnail").unwrap();
 
         let result = clear_cache(&cache_dir);
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), 2); // Kun .jpg filer slettet
+        assert_eq!(result.unwrap(), 3); // Kun thumbnail-filer slettet
 
         // Verifiser at .txt filen fortsatt finnes
         assert!(cache_dir.join("other.txt").exists());
     }
 
+    #[test]
+    fn test_clear_cache_reports_bytes() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        fs::create_dir_all(&cache_dir).unwrap();
+        fs::write(cache_dir.join("a.jpg"), [0u8; 100]).unwrap();
+        fs::write(cache_dir.join("b.png"), [0u8; 50]).unwrap();
+
+        let result = clear_cache_with_stats(&cache_dir).unwrap();
+        assert_eq!(result.files_removed, 2);
+        assert_eq!(result.bytes_freed, 150);
+        assert!(result.errors.is_empty());
+
+        let missing = clear_cache_with_stats(&dir.path().join("mangler")).unwrap();
+        assert_eq!((missing.files_removed, missing.bytes_freed), (0, 0));
+    }
+
     #[test]
     fn test_thumbnail_maintains_aspect_ratio() {
         let dir = tempdir().unwrap();
@@ -272,6 +987,165 @@ mod tests {
         assert!(height < width, "Bredt bilde skal gi thumbnail som er bredere enn det er høyt");
     }
 
+    #[test]
+    fn test_apply_orientation_all_values() {
+        // 2x1-bilde: venstre piksel rød, høyre piksel blå
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+        let mut img = RgbaImage::new(2, 1);
+        img.put_pixel(0, 0, red);
+        img.put_pixel(1, 0, blue);
+        let img = image::DynamicImage::ImageRgba8(img);
+
+        let oriented = |o| apply_orientation(img.clone(), o).to_rgba8();
+
+        // Ingen endring for 1 og ugyldige verdier
+        assert_eq!(oriented(1).get_pixel(0, 0), &red);
+        assert_eq!(oriented(0).get_pixel(0, 0), &red);
+
+        // Speiling og 180° beholder dimensjonene
+        assert_eq!(oriented(2).get_pixel(0, 0), &blue);
+        assert_eq!(oriented(3).get_pixel(0, 0), &blue);
+        assert_eq!(oriented(4).get_pixel(0, 0), &red);
+
+        // 90°/270°-varianter bytter bredde og høyde
+        for o in 5..=8 {
+            assert_eq!(oriented(o).dimensions(), (1, 2), "orientering {}", o);
+        }
+        assert_eq!(oriented(5).get_pixel(0, 0), &red); // transponert
+        assert_eq!(oriented(6).get_pixel(0, 0), &red); // 90° med klokka
+        assert_eq!(oriented(7).get_pixel(0, 0), &blue); // transversert
+        assert_eq!(oriented(8).get_pixel(0, 0), &blue); // 270° med klokka
+    }
+
+    #[test]
+    fn test_thumbnail_data_url() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let image_path = dir.path().join("test_image.png");
+        create_test_image(300, 300).save(&image_path).unwrap();
+
+        let thumbnail_path = get_or_create_thumbnail(&image_path, &cache_dir).unwrap();
+        let data_url = thumbnail_data_url(&thumbnail_path).unwrap();
+
+        // Thumbnails lagres som JPEG selv om kilden er PNG
+        assert!(data_url.starts_with("data:image/jpeg;base64,"));
+    }
+
+    #[test]
+    fn test_thumbnail_config_size_and_format() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let image_path = dir.path().join("test_image.png");
+        create_test_image(400, 200).save(&image_path).unwrap();
+
+        let config = ThumbnailConfig {
+            max_edge: 100,
+            format: "png".to_string(),
+            quality: 90,
+            ..Default::default()
+        };
+        let small = get_or_create_thumbnail_with_config(&image_path, &cache_dir, &config).unwrap();
+        let default = get_or_create_thumbnail(&image_path, &cache_dir).unwrap();
+
+        // Ulike innstillinger skal aldri dele cache-fil
+        assert_ne!(small, default);
+        assert!(small.to_string_lossy().ends_with(".png"));
+        assert_eq!(image::open(&small).unwrap().dimensions(), (100, 50));
+        assert_eq!(image::open(&default).unwrap().dimensions(), (THUMBNAIL_SIZE, 100));
+    }
+
+    #[cfg(feature = "webp")]
+    #[test]
+    fn test_webp_thumbnail() {
+        let dir = tempdir().unwrap();
+        let image_path = dir.path().join("test_image.png");
+        create_test_image(400, 200).save(&image_path).unwrap();
+
+        let config = ThumbnailConfig {
+            format: "webp".to_string(),
+            ..Default::default()
+        };
+        let thumbnail_path = get_or_create_thumbnail_with_config(&image_path, &dir.path().join("cache"), &config).unwrap();
+
+        assert!(thumbnail_path.to_string_lossy().ends_with(".webp"));
+        assert!(thumbnail_data_url(&thumbnail_path).unwrap().starts_with("data:image/webp;base64,"));
+    }
+
+    #[test]
+    fn test_codec_availability() {
+        assert_eq!(ThumbnailCodec::Avif(80).available().is_ok(), cfg!(feature = "avif"));
+        assert_eq!(ThumbnailCodec::WebP(80).available().is_ok(), cfg!(feature = "webp"));
+        assert_eq!(ThumbnailCodec::Png.available(), Ok(ThumbnailCodec::Png));
+
+        let webp = ThumbnailConfig {
+            format: "webp".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(webp.check_format().is_ok(), cfg!(feature = "webp"));
+        let unknown = ThumbnailConfig {
+            format: "tiff".to_string(),
+            ..Default::default()
+        };
+        assert!(unknown.check_format().is_err());
+        assert_eq!(ThumbnailCodec::WebP(80).for_video(), ThumbnailCodec::Jpeg(80, ChromaSubsampling::default()));
+        assert_eq!(ThumbnailCodec::Png.for_video(), ThumbnailCodec::Png);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let dir = tempdir().unwrap();
+        let cache_dir = dir.path().join("cache");
+        let image = create_test_image(300, 300);
+        let paths: Vec<PathBuf> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| {
+                let path = dir.path().join(format!("{}.png", name));
+                image.save(&path).unwrap();
+                path
+            })
+            .collect();
+
+        let unlimited = ThumbnailConfig {
+            format: "png".to_string(),
+            max_cache_bytes: 0,
+            ..Default::default()
+        };
+        let thumbs: Vec<PathBuf> = paths[..3]
+            .iter()
+            .map(|path| get_or_create_thumbnail_with_config(path, &cache_dir, &unlimited).unwrap())
+            .collect();
+        let thumb_size = fs::metadata(&thumbs[0]).unwrap().len();
+
+        // Bruk "a" igjen, så "b" blir den eldst brukte
+        get_or_create_thumbnail_with_config(&paths[0], &cache_dir, &unlimited).unwrap();
+
+        let capped = ThumbnailConfig {
+            max_cache_bytes: thumb_size * 3,
+            ..unlimited
+        };
+        let newest = get_or_create_thumbnail_with_config(&paths[3], &cache_dir, &capped).unwrap();
+
+        assert!(thumbs[0].exists());
+        assert!(!thumbs[1].exists(), "Minst nylig brukte thumbnail skal slettes");
+        assert!(thumbs[2].exists());
+        assert!(newest.exists());
+    }
+
+    #[test]
+    fn test_thumbnail_config_rejects_unknown_format() {
+        let dir = tempdir().unwrap();
+        let image_path = dir.path().join("test_image.png");
+        create_test_image(50, 50).save(&image_path).unwrap();
+
+        let config = ThumbnailConfig {
+            format: "tiff".to_string(),
+            ..Default::default()
+        };
+        let result = get_or_create_thumbnail_with_config(&image_path, &dir.path().join("cache"), &config);
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_nonexistent_image() {
         let dir = tempdir().unwrap();
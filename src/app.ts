@@ -5,7 +5,7 @@ import { open } from '@tauri-apps/plugin-dialog';
 
 import { state } from "./modules/state";
 import { ScanResult } from "./modules/types";
-import { elements, updateStatus, showImportSuccess, toggleView } from "./modules/ui";
+import { elements, errorMessage, updateStatus, showImportSuccess, toggleView } from "./modules/ui";
 import { initGallery } from "./modules/gallery";
 import { renderVirtualItems } from "./modules/virtual-scroll";
 
@@ -29,7 +29,7 @@ export function setupApp() {
             }
         } catch (error) {
             console.error("Feil ved valg av mappe:", error);
-            updateStatus(`Feil ved valg av mappe: ${error}`);
+            updateStatus(`Feil ved valg av mappe: ${errorMessage(error)}`);
             toggleView('import');
         }
     });
@@ -89,6 +89,7 @@ async function performScan(path: string) {
         const result = await invoke<ScanResult>("scan_folder", { path });
 
         state.setImages(result.images);
+        state.currentFolder = path;
         state.clearSelection();
 
         showImportSuccess(result.imageCount, result.totalSizeBytes);
@@ -96,6 +97,6 @@ async function performScan(path: string) {
 
     } catch (error) {
         console.error("Feil ved skanning:", error);
-        updateStatus(`Feil: ${error}`);
+        updateStatus(`Feil: ${errorMessage(error)}`);
     }
 }
@@ -2,7 +2,7 @@ import { convertFileSrc } from "@tauri-apps/api/core";
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "./toast";
 import { ImageInfo, DuplicateGroup, OperationResult } from "./types";
-import { updateStatus } from "./ui";
+import { errorMessage, updateStatus } from "./ui";
 
 export class ComparisonManager {
     private overlay: HTMLElement | null = null;
@@ -156,7 +156,7 @@ export class ComparisonManager {
 
         } catch (e) {
             console.error("Feil ved resolving:", e);
-            toast.show("Kunne ikke utføre handling: " + e, "error");
+            toast.show("Kunne ikke utføre handling: " + errorMessage(e), "error");
         }
     }
 
@@ -2,9 +2,9 @@ import { invoke } from "@tauri-apps/api/core";
 import { toast } from "./toast";
 import { convertFileSrc } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { DuplicateResult, DuplicateGroup, ImageInfo, OperationResult } from "./types";
+import { DuplicateResult, DuplicateGroup, DuplicateProgress, ImageInfo, OperationResult } from "./types";
 import { state, CONFIG } from "./state";
-import { updateStatus } from "./ui";
+import { errorMessage, updateStatus } from "./ui";
 import { comparisonManager } from "./comparison";
 
 export async function startDuplicateDetection() {
@@ -18,18 +18,21 @@ export async function startDuplicateDetection() {
         btn?.classList.add("loading");
         updateStatus(`Analyserer ${state.currentImages.length} bilder...`);
 
-        const paths = state.currentImages.map((img) => img.path);
-
-        let processedCount = 0;
-        const unlisten = await listen("progress", () => {
-            processedCount++;
-            updateStatus(`Analyserer ${processedCount}/${paths.length} bilder...`);
+        const unlisten = await listen<DuplicateProgress>("duplicate-progress", (event) => {
+            const { processed, total } = event.payload;
+            updateStatus(`Analyserer ${processed}/${total} bilder...`);
         });
 
-        const result = await invoke<DuplicateResult>("find_duplicates", {
-            paths,
-            threshold: CONFIG.DUPLICATE_THRESHOLD,
-        });
+        // Hele mappen: backend gjenbruker skanningen i stedet for å få alle stiene tilsendt
+        const result = state.currentFolder
+            ? await invoke<DuplicateResult>("find_duplicates_in_folder", {
+                path: state.currentFolder,
+                threshold: CONFIG.DUPLICATE_THRESHOLD,
+            })
+            : await invoke<DuplicateResult>("find_duplicates", {
+                paths: state.currentImages.map((img) => img.path),
+                threshold: CONFIG.DUPLICATE_THRESHOLD,
+            });
 
         unlisten();
 
@@ -44,7 +47,7 @@ export async function startDuplicateDetection() {
 
     } catch (error) {
         console.error("Feil ved duplikatdeteksjon:", error);
-        updateStatus(`Feil: ${error}`);
+        updateStatus(`Feil: ${errorMessage(error)}`);
     } finally {
         btn?.classList.remove("loading");
     }
@@ -195,6 +198,6 @@ async function deleteSelectedDuplicates(section: HTMLElement) {
             item?.remove();
         });
     } catch (error) {
-        toast.show(`Feil: ${error}`, "error");
+        toast.show(`Feil: ${errorMessage(error)}`, "error");
     }
 }
@@ -5,7 +5,7 @@ import { ImageInfo, OperationResult } from "./types";
 import { state } from "./state";
 import { setupVirtualScroll, renderVirtualItems } from "./virtual-scroll";
 import { startDuplicateDetection } from "./duplicates"; // Forward reference
-import { updateStatus } from "./ui";
+import { errorMessage, updateStatus } from "./ui";
 import { toast } from "./toast";
 
 let scrollContainer: HTMLDivElement | null = null;
@@ -232,7 +232,7 @@ async function deleteSelected() {
 
     } catch (error) {
         console.error("Feil ved sletting:", error);
-        toast.show(`Feil ved sletting: ${error}`, "error");
+        toast.show(`Feil ved sletting: ${errorMessage(error)}`, "error");
     } finally {
         btn?.classList.remove("loading");
     }
@@ -278,7 +278,7 @@ async function moveSelected() {
         }
 
     } catch (error) {
-        toast.show(`Feil ved flytting: ${error}`, "error");
+        toast.show(`Feil ved flytting: ${errorMessage(error)}`, "error");
     } finally {
         document.getElementById("move-selected")?.classList.remove("loading");
     }
@@ -368,11 +368,14 @@ async function sortImages() {
         });
 
         let message = `Sortering ferdig: ${result.success} kopiert, ${result.errors} feil.`;
+        if (result.mtimeDated.length > 0) {
+            message += ` ${result.mtimeDated.length} filer manglet EXIF-dato og ble sortert etter endringsdato.`;
+        }
         updateStatus(message);
-        toast.show(message, result.errors > 0 ? "warning" : "success");
+        toast.show(message, result.errors > 0 || result.mtimeDated.length > 0 ? "warning" : "success");
 
     } catch (error) {
-        updateStatus(`Feil ved sortering: ${error}`);
+        updateStatus(`Feil ved sortering: ${errorMessage(error)}`);
     } finally {
         document.getElementById("sort-images")?.classList.remove("loading");
     }
@@ -16,6 +16,8 @@ export interface VirtualScrollState {
 
 class AppState {
     currentImages: ImageInfo[] = [];
+    /** Mappen som sist ble skannet; backend husker stiene fra skanningen */
+    currentFolder: string | null = null;
     selectedPaths: Set<string> = new Set();
     thumbnailCache: Map<string, string> = new Map();
 
@@ -1,20 +1,320 @@
 
 
 export interface ImageInfo {
+    /** Tapsfri sti: kan inneholde tegn fra det private Unicode-området for ugyldige byte. Send uendret tilbake. */
     path: string;
+    /** Til visning */
     filename: string;
     extension: string;
     sizeBytes: number;
+    mediaType: MediaType;
+    /** Kun satt når skanningen kjøres med `readDimensions` */
+    width: number | null;
+    height: number | null;
+    megapixels: number | null;
+    /** Kildemappen, kun satt av `find_duplicates_across` */
+    sourceFolder: string | null;
+    /** Plassholder (blurhash) mens thumbnailen lastes; kun med includeBlurhash ved skanning */
+    blurhash: string | null;
+}
+
+export type MediaType = 'image' | 'video';
+
+export interface ScanOptions {
+    allowedExtensions?: string[];
+    /** Ta med videofiler (mp4, mov, avi, mkv, ...) */
+    includeVideos?: boolean;
+    minSizeBytes?: number;
+    maxSizeBytes?: number;
+    /** 0 = kun valgt mappe. Utelatt = ubegrenset */
+    maxDepth?: number;
+    /** Les bredde/høyde fra filheader (litt tregere skanning) */
+    readDimensions?: boolean;
+    /** Glob-mønstre som hoppes over, f.eks. ["@eaDir", "*.tmp"]. Uten "/" matches navnet på alle nivåer */
+    ignorePatterns?: string[];
+    /** Ikke følg symbolske lenker (standard: følg, løkker hoppes over og rapporteres) */
+    skipSymlinks?: boolean;
+    /** Ta med skjulte filer og mapper (punktum først, og skjult/system på Windows). Standard: nei */
+    includeHidden?: boolean;
+}
+
+export interface ExtStat {
+    count: number;
+    totalBytes: number;
+}
+
+export interface FolderStats {
+    /** Nøklet på filendelse med små bokstaver */
+    byExtension: Record<string, ExtStat>;
+    totalFiles: number;
+    totalBytes: number;
+    /** RFC 3339 */
+    oldest: string | null;
+    newest: string | null;
 }
 
 export interface ScanResult {
     imageCount: number;
     totalSizeBytes: number;
     images: ImageInfo[];
+    errors: ScanError[];
+    cancelled: boolean;
+}
+
+/** Payload for `scan-batch` fra `scan_folder_streaming` */
+export interface ScanBatch {
+    images: ImageInfo[];
+}
+
+/** Payload for `scan-complete`, og returverdien til `scan_folder_streaming` */
+export interface ScanSummary {
+    imageCount: number;
+    totalSizeBytes: number;
+    errors: ScanError[];
+    cancelled: boolean;
+}
+
+/** Feil fra backend-kommandoene */
+export type ErrorCode =
+    | "not_found"
+    | "permission_denied"
+    | "unsupported_format"
+    | "invalid_input"
+    | "io"
+    | "decode"
+    | "other";
+
+export interface AppError {
+    code: ErrorCode;
+    message: string;
+}
+
+export interface ScanError {
+    path: string;
+    message: string;
+}
+
+/** Chroma subsampling for JPEG (thumbnails og convert_images) */
+export type ChromaSubsampling = "yuv444" | "yuv422" | "yuv420";
+
+export interface ThumbnailConfig {
+    maxEdge?: number;
+    format?: "jpeg" | "png" | "webp" | "avif";
+    /** 1-100; verdier utenfor gir feil */
+    quality?: number;
+    /** Kun JPEG; standard er yuv444 */
+    subsampling?: ChromaSubsampling;
+    /** Maks størrelse på thumbnail-cachen i bytes (0 = ubegrenset) */
+    maxCacheBytes?: number;
+}
+
+export interface GpsCoordinates {
+    latitude: number;
+    longitude: number;
+}
+
+/** Hvor en dato kom fra; `file_mtime` og `file_ctime` er upålitelige */
+export type DateSource =
+    | "sidecar"
+    | "exif_original"
+    | "exif_digitized"
+    | "exif_date_time"
+    | "video"
+    | "file_mtime"
+    | "file_ctime";
+
+export interface ExposureInfo {
+    iso: number | null;
+    /** F.eks. "f/2.8" */
+    fNumber: string | null;
+    /** F.eks. "1/250" eller "2s" */
+    exposureTime: string | null;
+    /** F.eks. "50 mm" */
+    focalLength: string | null;
+}
+
+export interface ImageMetadata {
+    date: string | null;
+    dateSource: DateSource | null;
+    gps: GpsCoordinates | null;
+    cameraMake: string | null;
+    cameraModel: string | null;
+    lens: string | null;
+    exposure: ExposureInfo;
+    width: number | null;
+    height: number | null;
+    /** 0-5 stjerner fra XMP-sidecar */
+    rating: number | null;
+}
+
+export interface CacheClearResult {
+    filesRemoved: number;
+    bytesFreed: number;
+    errors: string[];
+}
+
+export interface ThumbnailResult {
+    path: string;
+    thumbnailPath: string | null;
+    /** Animert GIF; thumbnailen viser første bilde */
+    animated: boolean;
+    error: string | null;
+}
+
+/** Resultat fra prewarm_thumbnails; hver ny thumbnail kommer også som "thumbnail-ready" (ThumbnailResult) */
+export interface PrewarmResult {
+    generated: number;
+    alreadyCached: number;
+    errors: ScanError[];
+    cancelled: boolean;
+}
+
+/** Filformat for export_duplicates */
+export type ExportFormat = "json" | "csv";
+
+/** Resultat fra export_duplicates */
+export interface DuplicateExport {
+    groups: number;
+    files: number;
 }
 
 export interface DuplicateGroup {
+    /** For `get_duplicate_group_page`; ugyldig etter neste duplikatsøk */
+    id: number;
+    /** Hele gruppens størrelse, også når `images` bare er første side (`groupPageSize`) */
+    imageCount: number;
     images: ImageInfo[];
+    /** Likhet mot første bilde i gruppen, samme rekkefølge som `images` */
+    scores: SimilarityScore[];
+}
+
+export interface SimilarityScore {
+    distance: number;
+    /** 0-100 */
+    similarity: number;
+}
+
+/** Treff fra `find_similar`, sortert med mest like først */
+export interface SimilarImage {
+    image: ImageInfo;
+    score: SimilarityScore;
+}
+
+/** Et bilde fra find_already_imported som allerede finnes i referansemappen */
+export interface ImportedMatch {
+    candidate: ImageInfo;
+    /** Nærmeste treff i referansemappen */
+    reference: ImageInfo;
+    score: SimilarityScore;
+}
+
+export interface AlreadyImportedResult {
+    /** Kandidatene med treff, i samme rekkefølge som candidatePaths */
+    matches: ImportedMatch[];
+    cancelled: boolean;
+}
+
+/** Hash for ett bilde, samme format som i hash-cachen (base64, kommaseparert for flere utsnitt) */
+export interface ImageWithHash {
+    info: ImageInfo;
+    hash: string;
+}
+
+/** Filer i samme mappe med samme navn bortsett fra kopi-suffikser (`find_name_duplicates`) */
+export interface NameDuplicateGroup {
+    /** Normalisert navn, f.eks. "photo.jpg" */
+    name: string;
+    /** Korteste navn (som regel originalen) først */
+    paths: string[];
+    /** Byte-identiske filer; null hvis innholdet ikke ble sjekket */
+    contentsMatch: boolean | null;
+}
+
+/** Filter for nedskaleringen før hashing: "nearest" er raskest, "lanczos3" skarpest */
+export type ResizeFilter = "nearest" | "triangle" | "lanczos3";
+
+/** Resultat av `hash_images`. Hver bolk sendes også som `hash-chunk`-event */
+export interface HashImagesResult {
+    hashes: ImageWithHash[];
+    errors: ScanError[];
+    cancelled: boolean;
+}
+
+/** Resultat av `compare_images`. Distansene er Hamming-distanse (0-64) */
+export interface ComparisonReport {
+    differenceDistance: number;
+    perceptualDistance: number;
+    averageDistance: number;
+    exactMatch: boolean;
+    /** [bredde, høyde], null hvis formatet ikke kan leses */
+    dimensionsA: [number, number] | null;
+    dimensionsB: [number, number] | null;
+    sameDimensions: boolean;
+}
+
+/** Hvorfor `find_problem_images` markerte et bilde */
+export type ProblemReason =
+    | { kind: "zero_bytes" }
+    | { kind: "decode_failed"; message: string }
+    | { kind: "too_small"; width: number; height: number };
+
+export interface ProblemImage {
+    path: string;
+    reason: ProblemReason;
+}
+
+/** Hva `audit_dates` fant galt med datoen */
+export type DateIssueKind = "no_exif" | "epoch" | "future" | "far_past";
+
+/** Resultat fra group_by_orientation; kvadratisk = forhold 0,95-1,05 */
+export interface OrientationGroups {
+    landscape: string[];
+    portrait: string[];
+    square: string[];
+    /** Dimensjonene kunne ikke leses */
+    unreadable: string[];
+}
+
+export interface DateIssue {
+    path: string;
+    issue: DateIssueKind;
+    /** ISO 8601, null for no_exif */
+    date: string | null;
+    dateSource: DateSource | null;
+}
+
+export type KeepStrategy = "largest" | "smallest" | "newest" | "oldest" | "highest_resolution";
+
+export interface KeeperSuggestion {
+    keeper: string;
+    delete: string[];
+    /** Andre filer under en beskyttet mappe (protectedPrefixes), som også beholdes */
+    protected: string[];
+}
+
+/** Fil i en gruppe til duplicate_waste; en ImageInfo kan sendes direkte */
+export interface SizedPath {
+    path: string;
+    sizeBytes: number;
+}
+
+/** Resultat fra duplicate_waste */
+export interface DuplicateWaste {
+    reclaimableBytes: number;
+    filesRemovable: number;
+}
+
+export interface DuplicateProgress {
+    processed: number;
+    total: number;
+    errors: number;
+}
+
+/** Payload for `sort-progress`-eventet */
+export interface SortProgress {
+    processed: number;
+    total: number;
+    currentFile: string;
 }
 
 export interface DuplicateResult {
@@ -22,6 +322,34 @@ export interface DuplicateResult {
     totalDuplicates: number;
     processed: number;
     errors: number;
+    cancelled: boolean;
+}
+
+export interface CrossFolderDuplicateResult {
+    /** Grupper som spenner over flere kildemapper */
+    crossFolder: DuplicateGroup[];
+    withinFolder: DuplicateGroup[];
+    totalDuplicates: number;
+    processed: number;
+    errors: number;
+    cancelled: boolean;
+}
+
+export interface RetryPolicy {
+    /** Totalt antall forsøk, inkludert det første; 1 = ingen nye forsøk */
+    attempts?: number;
+    /** Ventetid før andre forsøk, dobles for hvert forsøk */
+    initialBackoffMs?: number;
+}
+
+/** Resultat fra verify_manifest; stiene er `new_path` fra manifestet */
+export interface VerifyReport {
+    ok: number;
+    /** Flyttet, omdøpt eller slettet siden sorteringen */
+    missing: string[];
+    sizeMismatch: string[];
+    /** Kun når verifyChecksums er satt og manifestet har sha256 */
+    checksumMismatch: string[];
 }
 
 export interface OperationResult {
@@ -29,8 +357,108 @@ export interface OperationResult {
     success: number;
     errors: number;
     errorMessages: string[];
+    /** [kilde, mål] per fil ved tørrkjøring */
+    planned: [string, string][];
+    /** Sendes til `undo_operation` for å angre */
+    operationId: string | null;
+    /** Slettede filer (inkl. sidecars); send `originalPath` til `restore_from_trash` */
+    trashed: TrashedItem[];
+    /** Filer hoppet over pga. navnekollisjon (ikke feil) */
+    skipped: string[];
+    /** CSV-manifest i målmappen, når `writeManifest` er satt */
+    manifestPath: string | null;
+    /** Filer sortert etter filsystemets dato (mangler EXIF-dato) */
+    mtimeDated: string[];
+    /** Slettet permanent fordi papirkurven ikke var tilgjengelig (kun med `permanent`) */
+    permanentlyDeleted: string[];
+    /** Filer som allerede lå riktig og ble latt være (skipAlreadySorted) */
+    alreadySorted: string[];
+    /** Byte kopiert/flyttet, inkl. sidecars (lenker teller ikke) */
+    bytesProcessed: number;
+    /** Byte slettet eller lagt i papirkurven, inkl. sidecars */
+    bytesFreed: number;
 }
+
+export interface QuarantineResult {
+    /** Selve flyttingen; `operationId` kan brukes til å angre den */
+    operation: OperationResult;
+    /** Filen som ble beholdt i hver gruppe */
+    kept: string[];
+    /** [opprinnelig sti, ny sti] per flyttet fil, inkl. sidecars (planlagt ved tørrkjøring) */
+    moved: [string, string][];
+}
+
+export interface DeletionSummary {
+    count: number;
+    /** Inkludert sidecars */
+    totalBytes: number;
+    sidecars: number;
+    missing: string[];
+}
+
+export interface TrashedItem {
+    originalPath: string;
+    trashId: string | null;
+}
+
+export interface UndoResult {
+    restored: number;
+    skipped: string[];
+    errors: string[];
+}
+export type SortMethod = "copy" | "move" | "hardlink" | "symlink";
+
 export interface SortConfig {
     useDayFolder: boolean;
     useMonthNames: boolean;
+    /** Egne navn for {month_name}, 12 stk. fra januar (f.eks. engelsk). Standard: norske navn */
+    monthNames?: string[];
+    /** F.eks. "{year}/{month:02}-{month_name}/{camera_model}". Også {camera_make}, {lens} og {orientation} (Liggende/Stående/Kvadratisk). Tom = standardoppsett */
+    template?: string;
+    dryRun?: boolean;
+    preserveTimestamps?: boolean;
+    mtimeFromExif?: boolean;
+    collision?: CollisionStrategy;
+    /** Skriv manifest-<tid>.csv i målmappen: original_path, new_path, date_used, date_source */
+    writeManifest?: boolean;
+    /** Ta med SHA-256 i manifestet, så verify_manifest kan sjekke innholdet */
+    manifestChecksums?: boolean;
+    /** Nye forsøk ved forbigående filfeil (nettverksdisker). Standard: 3 forsøk, fra 100 ms */
+    retry?: RetryPolicy;
+    /** Mappenavn for filer uten dato (standard "Uten dato") */
+    undatedFolder?: string;
+    /** Mappe per filtype først: JPEG/2024/03, RAW/2024/03, VIDEO/... */
+    groupByType?: boolean;
+    /** Filsystemdato for filer uten dato i metadata. Utelatt: ingen for bilder, mtime for videoer */
+    dateFallback?: DateFallback;
+    /** Alt rett i målmappen som "20240307_142501_IMG_0001.jpg", uten datomapper */
+    flatten?: boolean;
+    /** Kildemappens navn som album under datomappen: 2024/03/Hyttetur/... */
+    preserveParent?: boolean;
+    /** Sorter etter GPS-posisjon i stedet for dato ("59.9N_10.7E", stedsnavn eller "Ukjent sted") */
+    location?: LocationConfig;
+    /** La filer som allerede ligger riktig i målmappen være (kilder kan ligge i målmappen) */
+    skipAlreadySorted?: boolean;
 }
+
+export interface LocationConfig {
+    /** Rutestørrelse i grader, standard 0.1 */
+    bucketDegrees?: number;
+    /** Kjente steder; bilder innenfor radiusen havner i en mappe med stedsnavnet */
+    places?: Place[];
+}
+
+export interface Place {
+    name: string;
+    latitude: number;
+    longitude: number;
+    /** Standard 10 km */
+    radiusKm?: number;
+}
+
+export type DateFallback = "mtime" | "ctime" | "none";
+
+export type CollisionStrategy = "rename" | "skip" | "overwrite" | "skip_if_identical";
+
+/** merge_folders: hva som skjer med filer som allerede finnes med likt innhold i målet */
+export type MergeDuplicateAction = "skip_if_identical" | "rename" | "trash";
@@ -1,3 +1,5 @@
+import { AppError } from "./types";
+
 export const elements = {
     get toolbar() { return document.getElementById("toolbar-container") },
     get changeFolderBtn() { return document.getElementById("change-folder-btn") },
@@ -8,6 +10,14 @@ export const elements = {
     get pathDisplay() { return document.getElementById("folder-path-display") },
 };
 
+/** Lesbar melding fra en feil kastet av `invoke` (AppError) eller andre kilder */
+export function errorMessage(error: unknown): string {
+    if (typeof error === "object" && error !== null && "message" in error) {
+        return String((error as AppError).message);
+    }
+    return String(error);
+}
+
 export function updateStatus(message: string) {
     if (elements.statusText) {
         elements.statusText.textContent = message;